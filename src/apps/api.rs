@@ -2,10 +2,15 @@
 use actix_web::{guard, web, HttpResponse};
 
 use crate::apps::{
-    email::views as email, ids::views as ids, institution::views as institution,
-    profiles::views as profiles, user::views as user,
+    email::views as email, events::views as events, ids::views as ids,
+    institution::views as institution, messaging::views as messaging,
+    notifications::views as notifications, profiles::views as profiles, user::views as user,
+    webhooks::views as webhooks,
 };
 
+/// Largest institution import, in bytes
+const IMPORT_PAYLOAD_LIMIT: usize = 4 * 1024 * 1024;
+
 /// Configures the app service
 pub fn api(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -74,11 +79,78 @@ pub fn api(cfg: &mut web::ServiceConfig) {
                             .route(web::post().to(institution::create_institution))
                             .route(web::get().to(institution::get_all_institutions)),
                     )
+                    .service(
+                        web::resource("/digests")
+                            .route(web::get().to(institution::get_digest_subscriptions)),
+                    )
+                    .service(
+                        web::resource("/import")
+                            .data(web::PayloadConfig::new(IMPORT_PAYLOAD_LIMIT))
+                            .route(web::post().to(institution::import_institutions)),
+                    )
+                    .service(
+                        web::resource("/search")
+                            .route(web::get().to(institution::search_institutions)),
+                    )
+                    .service(
+                        web::resource("/suggestions")
+                            .route(web::get().to(institution::get_institution_suggestions)),
+                    )
                     .service(
                         web::resource("/{id}")
                             .route(web::get().to(institution::get_institution_detail))
                             .route(web::put().to(institution::update_institution)),
                     )
+                    .service(
+                        web::resource("/{id}/registration_formats")
+                            .route(web::post().to(institution::add_registration_format))
+                            .route(web::get().to(institution::get_registration_formats)),
+                    )
+                    .service(
+                        web::resource("/{id}/digest")
+                            .route(web::post().to(institution::subscribe_to_digest))
+                            .route(web::delete().to(institution::unsubscribe_from_digest)),
+                    )
+                    .service(
+                        web::resource("/{id}/domains")
+                            .route(web::post().to(institution::add_institution_domain))
+                            .route(web::get().to(institution::get_institution_domains)),
+                    )
+                    .service(
+                        web::resource("/{id}/domains/{domain_id}")
+                            .route(web::delete().to(institution::remove_institution_domain)),
+                    )
+                    .service(
+                        web::resource("/{id}/domains/{domain_id}/verify")
+                            .route(web::post().to(institution::verify_institution_domain)),
+                    )
+                    .service(
+                        web::resource("/{id}/staff")
+                            .route(web::post().to(institution::invite_institution_staff))
+                            .route(web::get().to(institution::get_institution_staff)),
+                    )
+                    .service(
+                        web::resource("/{id}/staff/{user_id}")
+                            .route(web::delete().to(institution::remove_institution_staff)),
+                    )
+                    .service(
+                        web::resource("/{id}/campuses")
+                            .route(web::post().to(institution::add_campus))
+                            .route(web::get().to(institution::get_campuses)),
+                    )
+                    .service(
+                        web::resource("/{id}/pickup_points")
+                            .route(web::post().to(institution::add_pickup_point))
+                            .route(web::get().to(institution::get_pickup_points)),
+                    )
+                    .service(
+                        web::resource("/{id}/pickup_points/{point_id}")
+                            .route(web::put().to(institution::update_pickup_point)),
+                    )
+                    .service(
+                        web::resource("/{id}/merge")
+                            .route(web::post().to(institution::merge_institution)),
+                    )
                     .service(
                         web::resource("/user/change")
                             .route(web::post().to(institution::change_institution)),
@@ -93,6 +165,15 @@ pub fn api(cfg: &mut web::ServiceConfig) {
                     .service(web::resource("/remove").route(web::put().to(email::remove_email)))
                     .service(
                         web::resource("/activate").route(web::put().to(email::change_active_email)),
+                    )
+                    .service(web::resource("/bounces").route(web::post().to(email::receive_bounce)))
+                    .service(
+                        web::resource("/outbox/failed")
+                            .route(web::get().to(email::get_failed_emails)),
+                    )
+                    .service(
+                        web::resource("/outbox/{pk}/resend")
+                            .route(web::post().to(email::resend_email)),
                     ),
             )
             .service(
@@ -105,11 +186,35 @@ pub fn api(cfg: &mut web::ServiceConfig) {
                         web::resource("/institution/{institution_id}")
                             .route(web::get().to(ids::get_ids_by_institution_pk)),
                     )
+                    .service(
+                        web::resource("/institution/{institution_id}/upload")
+                            .route(web::post().to(ids::upload_found_ids)),
+                    )
+                    .service(
+                        web::resource("/search/registration")
+                            .route(web::get().to(ids::search_by_registration_no)),
+                    )
                     .service(web::resource("/mine").route(web::get().to(ids::get_user_idts)))
                     .service(web::resource("claim/mine").route(web::post().to(ids::claim_idt)))
                     .service(
                         web::resource("/posted/me").route(web::get().to(ids::get_user_posted_idts)),
                     )
+                    .service(
+                        web::resource("/posted/me/requests")
+                            .route(web::get().to(ids::get_posted_requests)),
+                    )
+                    .service(
+                        web::resource("/requests/{pk}/approve")
+                            .route(web::post().to(ids::approve_ownership_request)),
+                    )
+                    .service(
+                        web::resource("/requests/{pk}/handover")
+                            .route(web::post().to(ids::hand_over_identification)),
+                    )
+                    .service(
+                        web::resource("/requests/{pk}/reject")
+                            .route(web::post().to(ids::reject_ownership_request)),
+                    )
                     .service(
                         web::resource("/claim/user").route(web::get().to(ids::retrieve_user_claim)),
                     )
@@ -118,7 +223,28 @@ pub fn api(cfg: &mut web::ServiceConfig) {
                             .route(web::put().to(ids::update_idt_claim))
                             .route(web::get().to(ids::retrieve_claim)),
                     )
+                    .service(
+                        web::resource("/claim/{pk}/withdraw")
+                            .route(web::post().to(ids::withdraw_claim)),
+                    )
+                    .service(
+                        web::resource("/claim/{pk}/renew").route(web::post().to(ids::renew_claim)),
+                    )
                     .service(web::resource("/claim").route(web::post().to(ids::create_idt_claim)))
+                    .service(
+                        web::resource("/matches/sweep")
+                            .route(web::post().to(ids::sweep_matches))
+                            .route(web::get().to(ids::get_match_sweeps)),
+                    )
+                    .service(
+                        web::resource("/searches")
+                            .route(web::post().to(ids::create_saved_search))
+                            .route(web::get().to(ids::get_saved_searches)),
+                    )
+                    .service(
+                        web::resource("/searches/{pk}")
+                            .route(web::delete().to(ids::remove_saved_search)),
+                    )
                     .service(
                         web::resource("/{pk}")
                             .route(web::get().to(ids::get_idt))
@@ -130,6 +256,92 @@ pub fn api(cfg: &mut web::ServiceConfig) {
                     .service(web::resource("/lose/{pk}").route(web::post().to(ids::lose_idt)))
                     .service(web::resource("/found/{pk}").route(web::post().to(ids::is_now_found))),
             )
+            .service(web::resource("/events").route(web::get().to(events::event_stream)))
+            .service(
+                web::resource("/events/token").route(web::get().to(events::event_stream_token)),
+            )
+            .service(
+                web::scope("/conversations")
+                    .service(
+                        web::resource("")
+                            .route(web::get().to(messaging::get_conversations))
+                            .route(web::post().to(messaging::open_conversation)),
+                    )
+                    .service(
+                        web::resource("/reports")
+                            .route(web::get().to(messaging::get_message_reports)),
+                    )
+                    .service(
+                        web::resource("/messages/{pk}/report")
+                            .route(web::post().to(messaging::report_message)),
+                    )
+                    .service(
+                        web::resource("/{pk}").route(web::get().to(messaging::get_conversation)),
+                    )
+                    .service(
+                        web::resource("/{pk}/messages")
+                            .route(web::get().to(messaging::get_messages))
+                            .route(web::post().to(messaging::send_message)),
+                    )
+                    .service(
+                        web::resource("/{pk}/read")
+                            .route(web::post().to(messaging::mark_messages_read)),
+                    )
+                    .service(
+                        web::resource("/{pk}/contact")
+                            .route(web::put().to(messaging::share_contact)),
+                    ),
+            )
+            .service(
+                web::scope("/notifications")
+                    .service(
+                        web::resource("").route(web::get().to(notifications::get_notifications)),
+                    )
+                    .service(
+                        web::resource("/unread")
+                            .route(web::get().to(notifications::get_unread_count)),
+                    )
+                    .service(
+                        web::resource("/read").route(web::post().to(notifications::mark_all_read)),
+                    )
+                    .service(
+                        web::resource("/preferences")
+                            .route(web::get().to(notifications::get_preferences))
+                            .route(web::put().to(notifications::update_preferences)),
+                    )
+                    .service(
+                        web::resource("/settings")
+                            .route(web::put().to(notifications::update_settings)),
+                    )
+                    .service(
+                        web::resource("/unsubscribe")
+                            .route(web::get().to(notifications::confirm_unsubscribe))
+                            .route(web::post().to(notifications::unsubscribe)),
+                    )
+                    .service(
+                        web::resource("/{pk}/read").route(web::post().to(notifications::mark_read)),
+                    ),
+            )
+            .service(
+                web::scope("/webhooks")
+                    .service(
+                        web::resource("")
+                            .route(web::get().to(webhooks::get_subscriptions))
+                            .route(web::post().to(webhooks::create_subscription)),
+                    )
+                    .service(
+                        web::resource("/deliveries/{pk}/replay")
+                            .route(web::post().to(webhooks::replay_delivery)),
+                    )
+                    .service(
+                        web::resource("/{pk}")
+                            .route(web::delete().to(webhooks::deactivate_subscription)),
+                    )
+                    .service(
+                        web::resource("/{pk}/deliveries")
+                            .route(web::get().to(webhooks::get_deliveries)),
+                    ),
+            )
             .service(web::resource("/").route(web::get().to(|| HttpResponse::Ok().body("Aha"))))
             .default_service(
                 // 404 GET
@@ -0,0 +1,288 @@
+//! Bounce and complaint processing
+//!
+//! Delivery status notifications (DSN, RFC 3464) and abuse feedback
+//! reports (ARF, RFC 5965) are posted as raw RFC 822 messages, e.g
+//! piped in by the local MTA.
+//!
+//! A hard bounce or a complaint marks the email undeliverable at once.
+//! Soft bounces only do after `SOFT_BOUNCE_LIMIT` of them in a row: a
+//! successful send clears the count. Delays aren't bounces, as the mail
+//! is still retried. Undeliverable emails are skipped when mailing users.
+//! If it was their active email, the user is asked to pick another.
+//!
+//! Addresses are compared case-insensitively.
+
+use super::models::Email;
+use crate::{
+    apps::{events::models::EventKind, notifications::models::Notification},
+    diesel_cfg::config::connect_to_db,
+    errors::error::ResError,
+};
+
+use diesel::{self, dsl::sql, prelude::*, sql_types::Text};
+use serde::Serialize;
+use serde_json::json;
+
+/// Soft bounces in a row after which an email is undeliverable
+const SOFT_BOUNCE_LIMIT: i32 = 5;
+
+/// Kinds of bounces
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BounceKind {
+    /// Permanent failure, e.g the mailbox doesn't exist
+    Hard,
+    /// Transient failure, e.g a full mailbox
+    Soft,
+    /// The recipient reported the mail as spam
+    Complaint,
+}
+
+/// A bounce of mail to one recipient
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bounce {
+    pub recipient: String,
+    pub kind: BounceKind,
+    /// Enhanced status code, e.g `5.1.1`
+    pub status: Option<String>,
+    /// Diagnostic code of a bounce, or the feedback type
+    /// of a complaint
+    pub diagnostic: Option<String>,
+}
+
+/// Outcome of processing a report
+#[derive(Debug, Default, Serialize)]
+pub struct BounceReport {
+    pub bounces: Vec<Bounce>,
+    /// Recipients not recognised as emails of users
+    pub unknown: Vec<String>,
+    /// Emails that became undeliverable
+    pub undeliverable: Vec<String>,
+}
+
+impl Bounce {
+    /// Parses the bounces of a DSN or ARF message.
+    ///
+    /// Recipients of a DSN that were delivered, relayed,
+    /// expanded or only delayed are left out.
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::email::bounces::{Bounce, BounceKind};
+    ///
+    /// let dsn = "Content-Type: multipart/report; report-type=delivery-status;\r\n \
+    ///     boundary=\"b\"\r\n\r\n--b\r\nContent-Type: message/delivery-status\r\n\r\n\
+    ///     Reporting-MTA: dns; mx.example.com\r\n\r\n\
+    ///     Final-Recipient: rfc822; <gone@example.com>\r\nAction: failed\r\n\
+    ///     Status: 5.1.1\r\nDiagnostic-Code: smtp; 550 5.1.1 No such user\r\n\r\n\
+    ///     Final-Recipient: rfc822; full@example.com\r\nAction: delayed\r\nStatus: 4.2.2\r\n\r\n--b--\r\n";
+    /// let bounces = Bounce::parse(dsn);
+    ///
+    /// assert_eq!(bounces.len(), 1);
+    /// assert_eq!(bounces[0].recipient, "gone@example.com");
+    /// assert_eq!(bounces[0].kind, BounceKind::Hard);
+    /// assert_eq!(bounces[0].status.as_deref(), Some("5.1.1"));
+    ///
+    /// let full = "Final-Recipient: rfc822; full@example.com\r\nAction: failed\r\nStatus: 4.2.2\r\n";
+    /// assert_eq!(Bounce::parse(full)[0].kind, BounceKind::Soft);
+    ///
+    /// let arf = "To: abuse@got-ya-id.local\nContent-Type: multipart/report; report-type=feedback-report\n\n\
+    ///     Feedback-Type: abuse\nOriginal-Rcpt-To: annoyed@example.com\n";
+    /// let complaints = Bounce::parse(arf);
+    ///
+    /// assert_eq!(complaints[0].recipient, "annoyed@example.com");
+    /// assert_eq!(complaints[0].kind, BounceKind::Complaint);
+    /// ```
+    pub fn parse(raw: &str) -> Vec<Self> {
+        let groups = field_groups(raw);
+        let find = |name: &str| {
+            groups
+                .iter()
+                .flatten()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        if let Some(feedback_type) = find("feedback-type") {
+            // The original recipient, or the `To` of the original
+            // message, which follows the headers of the report
+            let recipient = find("original-rcpt-to").or_else(|| {
+                groups
+                    .iter()
+                    .flatten()
+                    .rev()
+                    .find(|(field, _)| field == "to")
+                    .map(|(_, value)| value.clone())
+            });
+            return recipient
+                .map(|to| Bounce {
+                    recipient: address(&to),
+                    kind: BounceKind::Complaint,
+                    status: None,
+                    diagnostic: Some(feedback_type),
+                })
+                .into_iter()
+                .collect();
+        }
+
+        groups
+            .iter()
+            .filter_map(|group| {
+                let get = |name: &str| {
+                    group
+                        .iter()
+                        .find(|(field, _)| field == name)
+                        .map(|(_, value)| value.clone())
+                };
+                let recipient = get("final-recipient").or_else(|| get("original-recipient"))?;
+                let status = get("status");
+                let temporary = status.as_deref().map_or(false, |s| s.starts_with('4'));
+
+                let kind = match get("action").map(|a| a.to_lowercase()).as_deref() {
+                    Some("failed") if temporary => BounceKind::Soft,
+                    Some("failed") => BounceKind::Hard,
+                    // Delivered, relayed, expanded, or delayed and
+                    // still retried by the sending MTA
+                    Some(_) => return None,
+                    None if temporary => BounceKind::Soft,
+                    None if status.as_deref().map_or(false, |s| s.starts_with('5')) => {
+                        BounceKind::Hard
+                    }
+                    None => return None,
+                };
+                Some(Bounce {
+                    recipient: address(&recipient),
+                    kind,
+                    status,
+                    diagnostic: get("diagnostic-code"),
+                })
+            })
+            .collect()
+    }
+
+    /// Records the bounces against the emails of users
+    pub fn record(bounces: Vec<Self>) -> Result<BounceReport, ResError> {
+        use crate::diesel_cfg::schema::emails::dsl::{
+            bounces as bounce_count, emails, undeliverable,
+        };
+
+        let mut report = BounceReport::default();
+        let conn = connect_to_db();
+
+        for bounce in &bounces {
+            let found = emails
+                .filter(sql::<Text>("lower(email)").eq(bounce.recipient.to_lowercase()))
+                .load::<Email>(&conn)?;
+            if found.is_empty() {
+                report.unknown.push(bounce.recipient.clone());
+                continue;
+            }
+
+            for found in found {
+                let count = found.bounces + 1;
+                let dead = found.undeliverable
+                    || bounce.kind != BounceKind::Soft
+                    || count >= SOFT_BOUNCE_LIMIT;
+                let updated = diesel::update(&found)
+                    .set((bounce_count.eq(count), undeliverable.eq(dead)))
+                    .get_result::<Email>(&conn)?;
+
+                info!(
+                    "Bounces: {:?} bounce of {} ({} so far)",
+                    bounce.kind, updated.email, updated.bounces
+                );
+                if dead && !found.undeliverable {
+                    report.undeliverable.push(updated.email.clone());
+                    prompt_new_email(&updated)?;
+                }
+            }
+        }
+
+        report.bounces = bounces;
+        Ok(report)
+    }
+}
+
+/// Clears the soft bounces of an address mail was sent to.
+///
+/// Undeliverable emails stay so.
+pub fn clear_soft_bounces(address: &str) -> Result<(), ResError> {
+    use crate::diesel_cfg::schema::emails::dsl::{bounces, emails, undeliverable};
+
+    diesel::update(
+        emails.filter(
+            sql::<Text>("lower(email)")
+                .eq(address.to_lowercase())
+                .and(undeliverable.eq(false))
+                .and(bounces.gt(0)),
+        ),
+    )
+    .set(bounces.eq(0))
+    .execute(&connect_to_db())?;
+    Ok(())
+}
+
+/// Asks the owner of an undeliverable email to pick
+/// another active email, if it was the active one
+fn prompt_new_email(undeliverable: &Email) -> Result<(), ResError> {
+    if !undeliverable.is_active() {
+        return Ok(());
+    }
+
+    Notification::notify(
+        &[undeliverable.user_id],
+        EventKind::EmailUndeliverable,
+        "We can't reach your email",
+        &format!(
+            "Mail to {} keeps bouncing. Add or pick another active email to keep getting emails.",
+            undeliverable.email
+        ),
+        json!({ "email": undeliverable.email }),
+    );
+    Ok(())
+}
+
+/// Groups of the header fields of a message and of its parts,
+/// split at blank lines.
+///
+/// Folded lines are unfolded, and field names lowercased.
+fn field_groups(raw: &str) -> Vec<Vec<(String, String)>> {
+    let mut groups = vec![];
+    let mut group: Vec<(String, String)> = vec![];
+
+    for line in raw.lines() {
+        let line = line.trim_end_matches('\r');
+
+        if line.trim().is_empty() {
+            if !group.is_empty() {
+                groups.push(group);
+                group = vec![];
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = group.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some(colon) = line.find(':') {
+            let (field, value) = line.split_at(colon);
+            group.push((field.trim().to_lowercase(), value[1..].trim().to_string()));
+        }
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+    groups
+}
+
+/// The bare address of a recipient field,
+/// e.g `rfc822; <a@b.co>`
+fn address(value: &str) -> String {
+    let value = match value.find(';') {
+        Some(semicolon) => &value[semicolon + 1..],
+        None => value,
+    };
+    let value = match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    value.trim().to_string()
+}
@@ -1,2 +1,4 @@
+pub mod bounces;
 pub mod models;
+pub mod outbox;
 pub mod views;
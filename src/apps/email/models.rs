@@ -37,6 +37,12 @@ pub struct Email {
     pub created_at: NaiveDateTime,
     #[serde(deserialize_with = "from_timestamp")]
     updated_at: NaiveDateTime,
+
+    /// Mail to the email bounces, or was reported as spam.
+    /// Undeliverable emails are not mailed.
+    pub undeliverable: bool,
+    /// Number of bounces reported
+    pub bounces: i32,
 }
 
 /// Holds new email data
@@ -60,6 +66,11 @@ pub struct NewEmail<'a> {
 }
 
 impl Email {
+    /// Whether the email is the active email of its user
+    pub fn is_active(&self) -> bool {
+        self.active && !self.removed
+    }
+
     /// Retrieves a User object owning a given email
     pub fn user(curious_email: &str) -> Result<User, diesel::result::Error> {
         use crate::diesel_cfg::schema::emails::dsl::*;
@@ -155,6 +166,11 @@ impl Email {
                 msg: "Email needs to be verified".into(),
                 status: 403,
             });
+        } else if this_email.undeliverable {
+            return Err(ResError {
+                msg: "Mail to this email bounces. Verify it again, or pick another".into(),
+                status: 403,
+            });
         }
 
         this_email.active = true;
@@ -170,16 +186,21 @@ impl Email {
 
     /// Marks an email object `verified` as true.
     ///
+    /// Following the link proves mail reaches the email,
+    /// so its bounces are cleared.
+    ///
     /// ## Arguments
     /// verf_key: Verification token
     pub fn verify(verf_key: &str) -> Result<(), ResError> {
-        use crate::diesel_cfg::schema::emails::dsl::{email, emails, verified};
+        use crate::diesel_cfg::schema::emails::dsl::{
+            bounces, email, emails, undeliverable, verified,
+        };
 
         let user_email =
             validate::decode_auth_token(verf_key, Some("verification".to_owned()))?.sub;
 
         diesel::update(emails.filter(email.eq(user_email)))
-            .set(verified.eq(true))
+            .set((verified.eq(true), undeliverable.eq(false), bounces.eq(0)))
             .execute(&connect_to_db())?;
 
         Ok(())
@@ -0,0 +1,198 @@
+//! Durable email outbox
+//!
+//! Emails are not sent by the handlers that trigger them. They are
+//! saved to the outbox, in the transaction of the triggering change
+//! where there is one, and sent in the background by a `SendEmail`
+//! job, retried with backoff until the transport accepts them.
+//!
+//! Each email keeps a count of its attempts, and the response of the
+//! transport or the error of the last one. Emails the job gave up on
+//! can be listed and resent by admins.
+
+use crate::{
+    apps::{
+        email::bounces,
+        jobs::models::{JobKind, JobStatus},
+        user::utils::from_timestamp,
+    },
+    core::mail::{Mail, MailTransport},
+    diesel_cfg::{config::connect_to_db, schema::email_outbox},
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    self,
+    dsl::sql,
+    prelude::*,
+    sql_types::{Integer, Varchar},
+    PgConnection,
+};
+use serde::{Deserialize, Serialize};
+
+/// Status of an email in the outbox
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Not attempted yet
+    Pending,
+    /// Accepted by the transport
+    Sent,
+    /// The last attempt failed. Retried until the job gives up
+    Failed,
+}
+
+varchar_enum!(OutboxStatus {
+    Pending => "pending",
+    Sent => "sent",
+    Failed => "failed"
+});
+
+/// Queryable model of the email_outbox table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "email_outbox"]
+pub struct OutboxEmail {
+    pub id: i32,
+    pub to_addr: String,
+    pub to_name: String,
+    pub subject: String,
+
+    #[serde(skip)]
+    pub content: String,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+
+    /// Response of the transport to the last attempt
+    pub response: Option<String>,
+    pub last_error: Option<String>,
+    pub sent_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    pub updated_at: NaiveDateTime,
+
+    /// Plain text alternative of the content
+    #[serde(skip)]
+    pub text_content: Option<String>,
+    #[serde(skip)]
+    pub unsubscribe_link: Option<String>,
+}
+
+impl OutboxEmail {
+    /// Adds a mail to the outbox, and queues its sending.
+    ///
+    /// Both rows are written on the given connection, so a
+    /// transaction running on it covers the mail.
+    pub fn enqueue_on(conn: &PgConnection, mail: &Mail) -> QueryResult<Self> {
+        use crate::diesel_cfg::schema::email_outbox::dsl::*;
+
+        let email = diesel::insert_into(email_outbox)
+            .values((
+                to_addr.eq(&mail.to_addr),
+                to_name.eq(&mail.to_name),
+                subject.eq(&mail.subject),
+                content.eq(&mail.content),
+                text_content.eq(&mail.text),
+                unsubscribe_link.eq(&mail.unsubscribe),
+            ))
+            .get_result::<Self>(conn)?;
+
+        JobKind::SendEmail { email_id: email.id }.enqueue_on(conn)?;
+        Ok(email)
+    }
+
+    /// Adds a mail to the outbox, outside of any transaction
+    pub fn enqueue(mail: &Mail) -> Result<Self, ResError> {
+        let conn = connect_to_db();
+
+        Ok(conn.transaction(|| Self::enqueue_on(&conn, mail))?)
+    }
+
+    /// Finds an email by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::email_outbox::dsl::email_outbox;
+
+        Ok(email_outbox.find(pk).first::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the emails whose `SendEmail` job gave up, newest first.
+    ///
+    /// Emails that failed but are still being retried aren't included.
+    pub fn retrieve_failed() -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::{email_outbox::dsl::*, jobs};
+
+        let given_up = jobs::table
+            .select(sql::<Integer>("(payload->>'email_id')::int"))
+            .filter(jobs::kind.eq("send_email"))
+            .filter(jobs::status.eq(JobStatus::Failed));
+
+        Ok(email_outbox
+            .filter(status.eq(OutboxStatus::Failed))
+            .filter(id.eq_any(given_up))
+            .order(id.desc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Hands the email to the mail transport, recording the outcome.
+    ///
+    /// Sent emails are not sent again. An attempt the transport
+    /// doesn't accept is an error, so the job retries it. Once
+    /// accepted, the soft bounces of the address are cleared.
+    pub fn attempt(&self, transport: &dyn MailTransport) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::email_outbox::dsl::*;
+
+        if self.status == OutboxStatus::Sent {
+            return Ok(());
+        }
+
+        let mut mail = Mail::new(&self.to_addr, &self.to_name, &self.subject, &self.content);
+        mail.text = self.text_content.clone();
+        mail.unsubscribe = self.unsubscribe_link.clone();
+        let outcome = transport.send(&mail);
+        let tries = attempts.eq(attempts + 1);
+
+        match outcome {
+            Ok(reply) => {
+                diesel::update(self)
+                    .set((
+                        tries,
+                        status.eq(OutboxStatus::Sent),
+                        response.eq(reply),
+                        last_error.eq(None::<String>),
+                        sent_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(&connect_to_db())?;
+
+                if let Err(e) = bounces::clear_soft_bounces(&self.to_addr) {
+                    error!(
+                        "Outbox: Error clearing bounces of {}: {}",
+                        self.to_addr, e.msg
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => {
+                diesel::update(self)
+                    .set((tries, status.eq(OutboxStatus::Failed), last_error.eq(&e)))
+                    .execute(&connect_to_db())?;
+                Err(ResError::new(
+                    format!("Outbox: Email {} failed: {}", self.id, e),
+                    502,
+                ))
+            }
+        }
+    }
+
+    /// Sends the email again, recording the outcome
+    pub fn resend(&self, transport: &dyn MailTransport) -> Result<Self, ResError> {
+        if self.status == OutboxStatus::Sent {
+            return Err(ResError::new("Email was already sent".into(), 409));
+        }
+
+        self.attempt(transport)?;
+        Self::find_by_id(self.id)
+    }
+}
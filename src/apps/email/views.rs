@@ -4,17 +4,28 @@ use crate::{
         utils::get_url,
         views::send_activation_link,
     },
-    core::response::{err, respond},
+    core::{
+        mail::MailTransport,
+        response::{err, respond},
+    },
+    errors::error::ResError,
     hashmap,
 };
 
-use super::models::{Email, NewEmail};
+use super::{
+    bounces::Bounce,
+    models::{Email, NewEmail},
+    outbox::OutboxEmail,
+};
 
 use actix_web::{http::StatusCode, web, Error, HttpRequest, HttpResponse, Result};
 use validator::Validate;
 
+use ring::constant_time;
 use serde_json::json;
 
+use std::{env, sync::Arc};
+
 /// Adds a new email for a user account.
 ///
 /// A verification link is sent to a newly added
@@ -52,11 +63,10 @@ pub async fn add_email(
         &new_email.email,
         Some(&user.username),
         &path,
-        "email_verification.html",
+        "email_verification",
+        user.locale(),
         false,
-        None,
-    )
-    .await?;
+    )?;
 
     let msg_ = format!(
         "Success. A verification link has been sent to {}",
@@ -147,3 +157,113 @@ pub async fn verify_email(ver_token: web::Path<String>) -> Result<HttpResponse,
         ).await
     }
 }
+
+/// Retrieves the emails that couldn't be sent, newest first
+///
+/// Emails are retried with backoff, and only listed once
+/// their job gives up.
+/// The response of the transport and the last error are
+/// included, but not the content.
+///
+/// # Url
+/// `/emails/outbox/failed`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+/// Admins only
+pub async fn get_failed_emails(req: HttpRequest) -> Result<HttpResponse, Error> {
+    admin(&req)?;
+
+    let emails = OutboxEmail::retrieve_failed()?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Failed emails retrieved"];
+
+    respond(msg, Some(emails), None).unwrap().await
+}
+
+/// Sends a failed email again, through the mail transport
+/// shared with the app
+///
+/// # Url
+/// `/emails/outbox/{pk}/resend`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// Admins only
+pub async fn resend_email(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    mailer: web::Data<Arc<dyn MailTransport>>,
+) -> Result<HttpResponse, Error> {
+    admin(&req)?;
+
+    let email = OutboxEmail::find_by_id(*pk)?.resend(&**mailer)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Email resent"];
+
+    respond(msg, Some(email), None).unwrap().await
+}
+
+/// Processes a bounce or complaint report, posted as a raw
+/// RFC 822 message: a delivery status notification, or an
+/// abuse feedback report.
+///
+/// Hard bounced and complained about emails are marked
+/// undeliverable, as are soft bounced ones after repeated
+/// bounces. They are no longer mailed.
+///
+/// # Url
+/// `/emails/bounces`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The `X-Bounce-Token` header, matching the `BOUNCE_TOKEN` ENV variable
+///
+/// ## Example
+/// ```none
+/// curl -X POST -H "X-Bounce-Token: $BOUNCE_TOKEN" \
+///     --data-binary @bounce.eml localhost:8888/api/emails/bounces
+/// ```
+pub async fn receive_bounce(req: HttpRequest, message: String) -> Result<HttpResponse, Error> {
+    let expected = env::var("BOUNCE_TOKEN").unwrap_or_default();
+    let given = req
+        .headers()
+        .get("X-Bounce-Token")
+        .and_then(|token| token.to_str().ok())
+        .unwrap_or_default();
+    if expected.is_empty()
+        || constant_time::verify_slices_are_equal(given.as_bytes(), expected.as_bytes()).is_err()
+    {
+        return Err(ResError::unauthorized().into());
+    }
+
+    let bounces = Bounce::parse(&message);
+    if bounces.is_empty() {
+        return err(
+            "400",
+            "No bounces or complaints found in the message".into(),
+        )
+        .await;
+    }
+
+    let report = Bounce::record(bounces)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Bounces recorded"];
+
+    respond(msg, Some(report), None).unwrap().await
+}
+
+/// Retrieves the user, if they are an admin
+fn admin(req: &HttpRequest) -> Result<User, ResError> {
+    let user = User::from_token(req)?;
+    if !user.is_admin() {
+        return Err(ResError::unauthorized());
+    }
+    Ok(user)
+}
@@ -0,0 +1,188 @@
+//! Fan-out of events to connected clients
+//!
+//! Each server instance LISTENs on the events channel, and forwards
+//! the events of a user to the event streams the user has open on
+//! the instance.
+//!
+//! The listener connects over TLS as the `sslmode` of the database
+//! URL asks, as libpq does: `disable` never uses TLS, `prefer` (the
+//! default) tries it first, and `require` insists on it. Like libpq,
+//! these don't check the server certificate. `verify-ca` checks it,
+//! and `verify-full` checks the host name too.
+
+use super::models::{Event, CHANNEL};
+use crate::diesel_cfg::config::database_url;
+
+use actix_rt::time::delay_for;
+use actix_web::web::Bytes;
+use futures::{
+    channel::{mpsc, oneshot},
+    stream, StreamExt,
+};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use tokio_postgres::AsyncMessage;
+use url::Url;
+
+use std::{collections::HashMap, error::Error, sync::Mutex, time::Duration};
+
+/// Pause between keep-alive comments to open streams.
+///
+/// Streams whose client went away are dropped on the next one.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Pause before reconnecting a dropped listener
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    /// The event streams open on this server instance
+    pub static ref BROKER: Broker = Broker::default();
+}
+
+/// The event streams of users, by user id
+#[derive(Default)]
+pub struct Broker {
+    clients: Mutex<HashMap<i32, Vec<mpsc::UnboundedSender<Bytes>>>>,
+}
+
+impl Broker {
+    /// Opens an event stream for the user
+    pub fn subscribe(&self, user_id: i32) -> mpsc::UnboundedReceiver<Bytes> {
+        let (tx, rx) = mpsc::unbounded();
+
+        // Tells EventSource clients how long to wait before reconnecting
+        let _ = tx.unbounded_send(Bytes::from_static(b"retry: 5000\n\n"));
+
+        self.clients
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_default()
+            .push(tx);
+        rx
+    }
+
+    /// Sends an event to the open streams of its user
+    pub fn dispatch(&self, event: &Event) {
+        let mut clients = self.clients.lock().unwrap();
+
+        if let Some(streams) = clients.get_mut(&event.user_id) {
+            let frame = Bytes::from(event.to_sse());
+            streams.retain(|tx| tx.unbounded_send(frame.clone()).is_ok());
+
+            if streams.is_empty() {
+                clients.remove(&event.user_id);
+            }
+        }
+    }
+
+    /// Sends a keep-alive comment down every stream,
+    /// dropping the closed ones
+    fn heartbeat(&self) {
+        let mut clients = self.clients.lock().unwrap();
+
+        for streams in clients.values_mut() {
+            streams.retain(|tx| tx.unbounded_send(Bytes::from_static(b":\n\n")).is_ok());
+        }
+        clients.retain(|_, streams| !streams.is_empty());
+    }
+}
+
+/// Starts the events listener and the stream heartbeat
+/// on the current actix runtime
+pub fn spawn_listener() {
+    actix_rt::spawn(run_listener());
+    actix_rt::spawn(async {
+        loop {
+            delay_for(HEARTBEAT).await;
+            BROKER.heartbeat();
+        }
+    });
+}
+
+/// LISTENs on the events channel, reconnecting if the
+/// connection drops
+async fn run_listener() {
+    loop {
+        if let Err(e) = listen().await {
+            error!("Events: Listener error: {}", e);
+        }
+        delay_for(RECONNECT_DELAY).await;
+    }
+}
+
+/// The database URL as `tokio_postgres` reads it, and the TLS
+/// connector its `sslmode` calls for
+fn tls_config(database_url: &str) -> Result<(String, MakeTlsConnector), Box<dyn Error>> {
+    let mut url = Url::parse(database_url)?;
+    let ssl_mode = url
+        .query_pairs()
+        .find(|(key, _)| key == "sslmode")
+        .map(|(_, mode)| mode.into_owned())
+        .unwrap_or_default();
+
+    let mut connector = TlsConnector::builder();
+    match ssl_mode.as_str() {
+        "verify-full" => (),
+        "verify-ca" => {
+            connector.danger_accept_invalid_hostnames(true);
+        }
+        _ => {
+            connector.danger_accept_invalid_certs(true);
+        }
+    }
+
+    // tokio_postgres doesn't know the verify modes. The
+    // connector does the verifying, so TLS is only required
+    if ssl_mode.starts_with("verify-") {
+        let pairs = url
+            .query_pairs()
+            .map(|(key, value)| {
+                if key == "sslmode" {
+                    (key.into_owned(), "require".to_string())
+                } else {
+                    (key.into_owned(), value.into_owned())
+                }
+            })
+            .collect::<Vec<_>>();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    Ok((url.into_string(), MakeTlsConnector::new(connector.build()?)))
+}
+
+/// Forwards events from the channel to the broker
+/// until the connection closes
+async fn listen() -> Result<(), Box<dyn Error>> {
+    let (url, tls) = tls_config(database_url())?;
+    let (client, mut connection) = tokio_postgres::connect(&url, tls).await?;
+    let (closed_tx, closed_rx) = oneshot::channel::<()>();
+
+    // The connection yields the notifications as it is polled
+    actix_rt::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => {
+                    match serde_json::from_str::<Event>(n.payload()) {
+                        Ok(event) => BROKER.dispatch(&event),
+                        Err(e) => error!("Events: Invalid payload {}: {}", n.payload(), e),
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    error!("Events: Connection error: {}", e);
+                    break;
+                }
+            }
+        }
+        let _ = closed_tx.send(());
+    });
+
+    client.batch_execute(&format!("LISTEN {}", CHANNEL)).await?;
+    debug!("Events: Listening on {}", CHANNEL);
+
+    let _ = closed_rx.await;
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod broker;
+pub mod models;
+pub mod views;
@@ -0,0 +1,133 @@
+//! Live events pushed to users
+//!
+//! Events are published on a Postgres notification channel, so every
+//! server instance gets them and forwards them to the users it
+//! streams to. See `events::broker`.
+
+use crate::{diesel_cfg::config::connect_to_db, varchar_enum};
+
+use diesel::{
+    self,
+    prelude::*,
+    sql_types::{Text, Varchar},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The Postgres notification channel of events
+pub const CHANNEL: &str = "gotya_events";
+
+/// Kinds of live events, and of the notifications they carry
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// An Identification matched a claim of the user
+    MatchRecorded,
+    /// An Identification the user is concerned with was marked found, or lost
+    FoundStatusChanged,
+    /// An Identification was handed over to its owner
+    Handover,
+    /// A message arrived in a conversation of the user
+    Message,
+    /// A claimant asked for an Identification the user posted
+    OwnershipRequested,
+    /// An ownership request of, or to, the user was decided
+    OwnershipDecided,
+    /// A claim of the user is about to expire
+    ClaimExpiring,
+    /// A claim of the user expired
+    ClaimExpired,
+    /// Mail to the active email of the user keeps bouncing
+    EmailUndeliverable,
+    /// An Identification matching a saved search of the user was posted
+    SavedSearchMatched,
+    /// The user was made staff of an institution
+    InstitutionRoleChanged,
+    /// A digest of the IDs posted at an institution the user follows
+    InstitutionDigest,
+}
+
+varchar_enum!(EventKind {
+    MatchRecorded => "match_recorded",
+    FoundStatusChanged => "found_status_changed",
+    Handover => "handover",
+    Message => "message",
+    OwnershipRequested => "ownership_requested",
+    OwnershipDecided => "ownership_decided",
+    ClaimExpiring => "claim_expiring",
+    ClaimExpired => "claim_expired",
+    EmailUndeliverable => "email_undeliverable",
+    SavedSearchMatched => "saved_search_matched",
+    InstitutionRoleChanged => "institution_role_changed",
+    InstitutionDigest => "institution_digest"
+});
+
+impl EventKind {
+    /// All kinds of events
+    pub const ALL: [EventKind; 12] = [
+        EventKind::MatchRecorded,
+        EventKind::FoundStatusChanged,
+        EventKind::Handover,
+        EventKind::Message,
+        EventKind::OwnershipRequested,
+        EventKind::OwnershipDecided,
+        EventKind::ClaimExpiring,
+        EventKind::ClaimExpired,
+        EventKind::EmailUndeliverable,
+        EventKind::SavedSearchMatched,
+        EventKind::InstitutionRoleChanged,
+        EventKind::InstitutionDigest,
+    ];
+}
+
+/// An event for one user.
+///
+/// The `data` is kept small, to fit within the payload
+/// limit of Postgres notifications.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub user_id: i32,
+    pub data: Value,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, user_id: i32, data: Value) -> Self {
+        Event {
+            kind,
+            user_id,
+            data,
+        }
+    }
+
+    /// Publishes the event to all server instances.
+    ///
+    /// Events are best-effort: a failure to publish is logged,
+    /// and doesn't fail the change that raised the event.
+    pub fn publish(&self) {
+        let payload = serde_json::to_string(self).unwrap();
+
+        let published = diesel::sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(CHANNEL)
+            .bind::<Text, _>(&payload)
+            .execute(&connect_to_db());
+
+        if let Err(e) = published {
+            error!("Events: Error publishing {}: {}", self.kind.as_str(), e);
+        }
+    }
+
+    /// The event as an SSE frame
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::events::models::{Event, EventKind};
+    /// use serde_json::json;
+    ///
+    /// let event = Event::new(EventKind::Message, 1, json!({"message_id": 4}));
+    /// assert_eq!(event.to_sse(), "event: message\ndata: {\"message_id\":4}\n\n");
+    /// ```
+    pub fn to_sse(&self) -> String {
+        format!("event: {}\ndata: {}\n\n", self.kind.as_str(), self.data)
+    }
+}
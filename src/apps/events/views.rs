@@ -0,0 +1,96 @@
+//! Implementations of Http endpoints for the Events resource
+
+use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::broker::BROKER;
+use crate::{apps::user::models::User, core::response::respond2 as respond, hashmap};
+
+/// Minutes an event stream token is valid for
+const STREAM_TOKEN_MINUTES: i64 = 60;
+
+/// Issuer of event stream tokens
+const STREAM_TOKEN_ISSUER: &str = "events";
+
+/// Authentication of an event stream
+#[derive(Deserialize)]
+pub struct EventStreamQuery {
+    /// An event stream token, for clients that can't
+    /// send an Authorization header
+    pub token: Option<String>,
+}
+
+/// A token to open an event stream with
+#[derive(Serialize, Deserialize)]
+pub struct EventStreamToken {
+    pub token: String,
+    /// Seconds the token is valid for
+    pub expires_in: i64,
+}
+
+/// Gives a short-lived token to open an event stream with.
+///
+/// Browser `EventSource` clients can't send the Authorization
+/// header. They open `/events?token={token}` instead. The token
+/// only opens event streams, and a new one is fetched once it
+/// expires.
+///
+/// # Url
+/// `/events/token`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn event_stream_token(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let email = User::token_subject(&req)?;
+    let token = User::create_token(
+        &email,
+        Some(STREAM_TOKEN_MINUTES),
+        STREAM_TOKEN_ISSUER.into(),
+    )?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Event stream token created"];
+    respond(
+        msg,
+        Some(EventStreamToken {
+            token,
+            expires_in: STREAM_TOKEN_MINUTES * 60,
+        }),
+    )
+    .await
+}
+
+/// Opens a Server-Sent Events stream of the user's live events.
+///
+/// Each event carries a new notification of the user as JSON data,
+/// the event name being the notification `kind`: e.g
+/// `match_recorded`, `found_status_changed`, `handover` or `message`.
+///
+/// # Url
+/// `/events?token={token}`
+///
+/// `token` is an event stream token from `/events/token`, given in
+/// place of the Authorization header.
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn event_stream(
+    req: HttpRequest,
+    query: web::Query<EventStreamQuery>,
+) -> Result<HttpResponse, Error> {
+    let user = match &query.token {
+        Some(token) => User::from_issued_token(token, STREAM_TOKEN_ISSUER)?,
+        None => User::from_token(&req)?,
+    };
+
+    let events = BROKER.subscribe(user.id).map(Ok::<_, Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(events))
+}
@@ -0,0 +1,142 @@
+//! Candidate blocking for Claim-Identification matching
+//!
+//! Identifications and Claims are indexed by a set of blocking keys.
+//! The matcher only scores pairs that share at least one key,
+//! instead of every Claim against every missing Identification.
+//!
+//! Keys are derived from:
+//! - The registration number, as an exact key
+//! - Each word of the name, as a phonetic (Soundex) and a
+//! leading trigram key
+//! - The graduation year, bucketed together with the phonetic
+//! code of each name word
+
+use crate::{
+    diesel_cfg::config::connect_to_db, errors::error::ResError, similarity::phonetic::soundex,
+};
+
+use chrono::{Datelike, NaiveDate};
+use diesel::{self, prelude::*, PgConnection};
+
+use std::collections::BTreeSet;
+
+/// Name words shorter than this are not indexed
+const MIN_WORD_LEN: usize = 2;
+
+/// Computes the blocking keys of a record.
+///
+/// ```rust
+/// use got_ya_id::apps::ids::blocking::block_keys;
+/// use chrono::NaiveDate;
+///
+/// let keys = block_keys("Ann Wanjiku", Some("p15/1234/2016"), NaiveDate::from_ymd_opt(2020, 6, 1));
+///
+/// assert!(keys.contains(&"reg:P1512342016".to_string()));
+/// assert!(keys.contains(&"ph:W522".to_string()));
+/// assert!(keys.contains(&"tri:wan".to_string()));
+/// assert!(keys.contains(&"yr:2020:W522".to_string()));
+///
+/// // Misspellings fall in the same phonetic block
+/// assert!(block_keys("Ann Wanjicu", None, None).contains(&"ph:W522".to_string()));
+/// ```
+pub fn block_keys(
+    name: &str,
+    registration_no: Option<&str>,
+    graduation: Option<NaiveDate>,
+) -> Vec<String> {
+    let mut keys = BTreeSet::new();
+
+    if let Some(reg_no) = registration_no {
+        let reg_key = reg_no
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_uppercase();
+        if !reg_key.is_empty() {
+            keys.insert(format!("reg:{}", reg_key));
+        }
+    }
+
+    for word in name.split_whitespace() {
+        let word = word.to_lowercase();
+        if word.chars().count() < MIN_WORD_LEN {
+            continue;
+        }
+
+        keys.insert(format!("tri:{}", word.chars().take(3).collect::<String>()));
+
+        if let Some(code) = soundex(&word) {
+            if let Some(date) = graduation {
+                keys.insert(format!("yr:{}:{}", date.year(), code));
+            }
+            keys.insert(format!("ph:{}", code));
+        }
+    }
+
+    keys.into_iter().collect()
+}
+
+/// Replaces the stored blocking keys of an Identification on the
+/// given connection. Run it in the transaction that saves the
+/// Identification, so the keys are never out of step with it.
+pub fn save_identification_blocks(
+    conn: &PgConnection,
+    idt_id: i32,
+    keys: Vec<String>,
+) -> QueryResult<()> {
+    use crate::diesel_cfg::schema::identification_blocks::dsl::*;
+
+    let rows = keys
+        .into_iter()
+        .map(|key| (identification_id.eq(idt_id), block_key.eq(key)))
+        .collect::<Vec<_>>();
+
+    diesel::delete(identification_blocks.filter(identification_id.eq(idt_id))).execute(conn)?;
+    if !rows.is_empty() {
+        diesel::insert_into(identification_blocks)
+            .values(&rows)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Replaces the stored blocking keys of a Claim on the given
+/// connection. Run it in the transaction that saves the Claim.
+pub fn save_claim_blocks(conn: &PgConnection, claim_pk: i32, keys: Vec<String>) -> QueryResult<()> {
+    use crate::diesel_cfg::schema::claim_blocks::dsl::*;
+
+    let rows = keys
+        .into_iter()
+        .map(|key| (claim_id.eq(claim_pk), block_key.eq(key)))
+        .collect::<Vec<_>>();
+
+    diesel::delete(claim_blocks.filter(claim_id.eq(claim_pk))).execute(conn)?;
+    if !rows.is_empty() {
+        diesel::insert_into(claim_blocks)
+            .values(&rows)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Ids of the Identifications sharing a blocking key with `keys`
+pub fn identifications_in_blocks(keys: &[String]) -> Result<Vec<i32>, ResError> {
+    use crate::diesel_cfg::schema::identification_blocks::dsl::*;
+
+    Ok(identification_blocks
+        .filter(block_key.eq_any(keys))
+        .select(identification_id)
+        .distinct()
+        .load::<i32>(&connect_to_db())?)
+}
+
+/// Ids of the Claims sharing a blocking key with `keys`
+pub fn claims_in_blocks(keys: &[String]) -> Result<Vec<i32>, ResError> {
+    use crate::diesel_cfg::schema::claim_blocks::dsl::*;
+
+    Ok(claim_blocks
+        .filter(block_key.eq_any(keys))
+        .select(claim_id)
+        .distinct()
+        .load::<i32>(&connect_to_db())?)
+}
@@ -0,0 +1,318 @@
+//! Bulk upload of found IDs
+//!
+//! Security desks log the IDs handed in to them in spreadsheets. A
+//! desk uploads its sheet as CSV, with a header row of the fields of
+//! a new Identification, and optionally a ZIP of photos of the IDs.
+//! Photos are keyed by row: `3.jpg` is the photo of the third row
+//! after the header.
+//!
+//! Each row is saved as a posted Identification is, through the same
+//! validation and duplicate check. The Identifications created are
+//! matched against claims and saved searches in one background job.
+
+use super::models::{Identification, NewIdentification};
+use crate::{
+    apps::{
+        institution::memberships::{InstitutionRole, Membership},
+        jobs::models::JobKind,
+        profiles::utils::make_temp_file,
+        user::models::User,
+    },
+    core::py_interface::create_py_mod,
+    errors::error::ResError,
+};
+
+use csv::{ReaderBuilder, Trim};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use zip::ZipArchive;
+
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+
+/// Most rows taken in one upload
+const MAX_UPLOAD_ROWS: usize = 500;
+
+/// Largest photo taken, in bytes
+const MAX_PHOTO_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Most bytes unpacked from a ZIP of photos
+const MAX_PHOTOS_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Kinds of photos taken
+const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Folder the photos are uploaded to
+const PHOTOS_DIR: &str = "got_ya_id/identifications/";
+
+/// A sheet of found IDs, with their photos
+pub struct FoundIdUpload {
+    /// The CSV sheet
+    pub sheet: Vec<u8>,
+    /// A ZIP of photos keyed by row
+    pub photos: Option<Vec<u8>>,
+}
+
+/// What became of a row
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    Created,
+    /// An Identification of the same details was posted before
+    Duplicate,
+    /// The row couldn't be read, or has invalid fields
+    Invalid,
+}
+
+/// Report on a row of an upload
+#[derive(Serialize, Deserialize)]
+pub struct UploadRow {
+    /// Number of the row, from 1, not counting the header
+    pub row: usize,
+    pub status: UploadStatus,
+    pub identification_id: Option<i32>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    pub errors: Option<String>,
+}
+
+/// Report on an upload
+#[derive(Serialize, Deserialize, Default)]
+pub struct UploadReport {
+    pub created: usize,
+    pub duplicate: usize,
+    pub invalid: usize,
+    pub rows: Vec<UploadRow>,
+
+    /// Photos of no row, or that couldn't be taken
+    pub unmatched_photos: Vec<String>,
+}
+
+/// A photo of a row
+struct Photo {
+    name: String,
+    extension: String,
+    data: Vec<u8>,
+}
+
+impl UploadReport {
+    fn add(&mut self, row: UploadRow) {
+        match row.status {
+            UploadStatus::Created => self.created += 1,
+            UploadStatus::Duplicate => self.duplicate += 1,
+            UploadStatus::Invalid => self.invalid += 1,
+        }
+        self.rows.push(row);
+    }
+}
+
+impl FoundIdUpload {
+    /// Posts the IDs of the sheet as handed in to the institution.
+    ///
+    /// Takes a custodian or admin of the institution.
+    pub async fn save(&self, inst_id: i32, usr: &User) -> Result<UploadReport, ResError> {
+        Membership::require(usr, Some(inst_id), InstitutionRole::Custodian)?;
+
+        let rows = ReaderBuilder::new()
+            .trim(Trim::All)
+            .from_reader(&self.sheet[..])
+            .deserialize::<NewIdentification<'static>>()
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Err(ResError::new("The sheet has no IDs".into(), 400));
+        }
+        if rows.len() > MAX_UPLOAD_ROWS {
+            return Err(ResError::new(
+                format!("Upload up to {} IDs at a time", MAX_UPLOAD_ROWS),
+                400,
+            ));
+        }
+
+        let mut report = UploadReport::default();
+        let mut photos = match &self.photos {
+            Some(archive) => read_photos(archive, &mut report.unmatched_photos)?,
+            None => BTreeMap::new(),
+        };
+
+        let mut created = vec![];
+        for (i, row) in rows.into_iter().enumerate() {
+            let row_no = i + 1;
+            let photo = photos.remove(&row_no);
+
+            let mut new_idt = match row {
+                Ok(new_idt) => new_idt,
+                Err(e) => {
+                    report.add(UploadRow {
+                        row: row_no,
+                        status: UploadStatus::Invalid,
+                        identification_id: None,
+                        name: None,
+                        picture: None,
+                        errors: Some(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+            new_idt.hand_in(inst_id, usr);
+            let name = Some(new_idt.name.to_string());
+
+            let idt = match save_row(&mut new_idt).await {
+                Ok(idt) => idt,
+                Err(e) => {
+                    if e.status >= 500 {
+                        error!("Found ID upload: Error saving row {}: {}", row_no, e.msg);
+                    }
+                    report.add(UploadRow {
+                        row: row_no,
+                        status: if e.status == 409 {
+                            UploadStatus::Duplicate
+                        } else {
+                            UploadStatus::Invalid
+                        },
+                        identification_id: None,
+                        name,
+                        picture: None,
+                        errors: Some(e.msg),
+                    });
+                    continue;
+                }
+            };
+            created.push(idt.id);
+
+            // The ID is kept if its photo fails, and the
+            // photo can be added later
+            let (picture, errors) = match photo.map(|photo| upload_photo(&idt, &photo)) {
+                Some(Ok(idt)) => (idt.picture, None),
+                Some(Err(e)) => (None, Some(e.msg)),
+                None => (None, None),
+            };
+            report.add(UploadRow {
+                row: row_no,
+                status: UploadStatus::Created,
+                identification_id: Some(idt.id),
+                name,
+                picture,
+                errors,
+            });
+        }
+
+        report
+            .unmatched_photos
+            .extend(photos.into_iter().map(|(_, photo)| photo.name));
+
+        if !created.is_empty() {
+            JobKind::MatchIds {
+                identification_ids: created,
+            }
+            .enqueue()?;
+        }
+        Ok(report)
+    }
+}
+
+/// Saves a row as a posted Identification is saved
+async fn save_row(new_idt: &mut NewIdentification<'_>) -> Result<Identification, ResError> {
+    new_idt.resolve_pickup_point()?;
+    new_idt
+        .validate()
+        .map_err(|e| ResError::new(e.to_string(), 400))?;
+    new_idt.save().await
+}
+
+/// Reads the photos of a ZIP archive, by the row they belong to.
+///
+/// Files that aren't photos of a row, or are larger than
+/// `MAX_PHOTO_BYTES`, are added to `unmatched`. The sizes the
+/// archive gives aren't trusted: photos are cut off as they are
+/// unpacked, and an archive unpacking past `MAX_PHOTOS_BYTES`
+/// is refused.
+fn read_photos(
+    archive: &[u8],
+    unmatched: &mut Vec<String>,
+) -> Result<BTreeMap<usize, Photo>, ResError> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))
+        .map_err(|e| ResError::new(format!("Give the photos as a ZIP archive: {}", e), 400))?;
+
+    let unreadable = |e: &dyn std::fmt::Display| {
+        ResError::new(format!("The ZIP archive is unreadable: {}", e), 400)
+    };
+
+    let mut photos = BTreeMap::new();
+    let mut unpacked = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| unreadable(&e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+
+        let path = Path::new(&name);
+        let row = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<usize>().ok());
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .filter(|ext| PHOTO_EXTENSIONS.contains(&ext.as_str()));
+
+        match (row, extension) {
+            (Some(row), Some(extension)) if !photos.contains_key(&row) => {
+                // One byte past the limit tells an oversized photo
+                let mut data = vec![];
+                file.take(MAX_PHOTO_BYTES + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|e| unreadable(&e))?;
+
+                unpacked += data.len() as u64;
+                if unpacked > MAX_PHOTOS_BYTES {
+                    return Err(ResError::new(
+                        format!(
+                            "The photos unpack to over {} MB. Upload fewer at a time",
+                            MAX_PHOTOS_BYTES / (1024 * 1024)
+                        ),
+                        413,
+                    ));
+                }
+                if data.len() as u64 > MAX_PHOTO_BYTES {
+                    unmatched.push(name);
+                    continue;
+                }
+                photos.insert(
+                    row,
+                    Photo {
+                        name,
+                        extension,
+                        data,
+                    },
+                );
+            }
+            _ => unmatched.push(name),
+        }
+    }
+    Ok(photos)
+}
+
+/// Uploads the photo of an Identification, and sets it as its picture
+fn upload_photo(idt: &Identification, photo: &Photo) -> Result<Identification, ResError> {
+    let failed = || {
+        ResError::new(
+            format!("The photo {} couldn't be uploaded", photo.name),
+            502,
+        )
+    };
+
+    let (mut file, path) = make_temp_file(Some(format!(
+        "identification-{}.{}",
+        idt.id, photo.extension
+    )))
+    .map_err(|_| failed())?;
+    file.write_all(&photo.data).map_err(|_| failed())?;
+
+    let url = create_py_mod(path, PHOTOS_DIR).map_err(|_| failed())?;
+    idt.set_picture(&url)
+}
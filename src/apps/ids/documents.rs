@@ -0,0 +1,264 @@
+//! Kinds of identification documents
+//!
+//! Each document type has a field schema: the fields it requires,
+//! and the fields that take part in matching.
+
+use super::registration::default_canonical;
+use crate::{errors::error::ResError, varchar_enum};
+
+use chrono::NaiveDate;
+use diesel::sql_types::Varchar;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The kind of document an Identification or a Claim is of
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    StudentId,
+    NationalId,
+    Passport,
+    DrivingLicence,
+    StaffCard,
+    LibraryCard,
+    BankCard,
+}
+
+varchar_enum!(DocumentType {
+    StudentId => "student_id",
+    NationalId => "national_id",
+    Passport => "passport",
+    DrivingLicence => "driving_licence",
+    StaffCard => "staff_card",
+    LibraryCard => "library_card",
+    BankCard => "bank_card"
+});
+
+impl Default for DocumentType {
+    fn default() -> Self {
+        DocumentType::StudentId
+    }
+}
+
+impl DocumentType {
+    /// Whether documents of this type are issued by an institution.
+    ///
+    /// Claims of these types follow their owner across
+    /// institution changes.
+    pub fn is_institutional(&self) -> bool {
+        match self {
+            DocumentType::StudentId | DocumentType::StaffCard | DocumentType::LibraryCard => true,
+            _ => false,
+        }
+    }
+}
+
+/// Document type filter of Identification listings
+#[derive(Deserialize)]
+pub struct DocumentTypeQuery {
+    pub document_type: Option<DocumentType>,
+}
+
+/// A field of an identification document, other than the name.
+///
+/// The name is required of, and matched on, every document type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    /// Course or department
+    Course,
+    /// Issue or entry date
+    ValidFrom,
+    /// Expiry or graduation date
+    ValidTill,
+    /// The document number
+    RegistrationNo,
+    Institution,
+}
+
+impl Field {
+    /// Name of the field in requests
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Field::Course => "course",
+            Field::ValidFrom => "valid_from",
+            Field::ValidTill => "valid_till",
+            Field::RegistrationNo => "registration_no",
+            Field::Institution => "institution_id",
+        }
+    }
+}
+
+/// The fields of a document type
+pub struct DocumentSchema {
+    /// Fields a posted Identification must have
+    pub required: &'static [Field],
+
+    /// Fields a Claim must have
+    pub claim_required: &'static [Field],
+
+    /// Fields compared when matching Claims to Identifications
+    pub matching: &'static [Field],
+
+    /// Pattern of the document number, in its default canonical
+    /// form (uppercased alphanumerics)
+    pub registration_pattern: Option<&'static str>,
+}
+
+/// The fields given on an Identification or a Claim.
+///
+/// Claims give their entry and graduation years as
+/// `valid_from` and `valid_till`.
+#[derive(Default)]
+pub struct DocumentFields<'a> {
+    pub course: Option<&'a str>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_till: Option<NaiveDate>,
+    pub registration_no: Option<&'a str>,
+    pub institution_id: Option<i32>,
+}
+
+impl<'a> DocumentFields<'a> {
+    /// Whether the field is given
+    pub fn has(&self, field: Field) -> bool {
+        match field {
+            Field::Course => self.course.map_or(false, |c| !c.trim().is_empty()),
+            Field::ValidFrom => self.valid_from.is_some(),
+            Field::ValidTill => self.valid_till.is_some(),
+            Field::RegistrationNo => self
+                .registration_no
+                .map_or(false, |r| !default_canonical(r).is_empty()),
+            Field::Institution => self.institution_id.is_some(),
+        }
+    }
+}
+
+impl DocumentType {
+    /// The field schema of the document type
+    pub fn schema(&self) -> DocumentSchema {
+        use Field::*;
+
+        match self {
+            DocumentType::StudentId => DocumentSchema {
+                required: &[Course, Institution],
+                claim_required: &[],
+                matching: &[Course, ValidFrom, ValidTill, RegistrationNo],
+                registration_pattern: None,
+            },
+            DocumentType::NationalId => DocumentSchema {
+                required: &[RegistrationNo],
+                claim_required: &[RegistrationNo],
+                matching: &[RegistrationNo],
+                registration_pattern: Some(r"^[A-Z0-9]{5,15}$"),
+            },
+            DocumentType::Passport => DocumentSchema {
+                required: &[RegistrationNo, ValidTill],
+                claim_required: &[RegistrationNo],
+                matching: &[ValidTill, RegistrationNo],
+                registration_pattern: Some(r"^[A-Z0-9]{6,9}$"),
+            },
+            DocumentType::DrivingLicence => DocumentSchema {
+                required: &[RegistrationNo],
+                claim_required: &[],
+                matching: &[ValidTill, RegistrationNo],
+                registration_pattern: Some(r"^[A-Z0-9]{4,16}$"),
+            },
+            DocumentType::StaffCard => DocumentSchema {
+                required: &[Institution],
+                claim_required: &[],
+                matching: &[Course, RegistrationNo],
+                registration_pattern: None,
+            },
+            DocumentType::LibraryCard => DocumentSchema {
+                required: &[Institution],
+                claim_required: &[],
+                matching: &[ValidTill, RegistrationNo],
+                registration_pattern: None,
+            },
+            // Only the last four digits of a card are kept
+            DocumentType::BankCard => DocumentSchema {
+                required: &[RegistrationNo],
+                claim_required: &[RegistrationNo],
+                matching: &[RegistrationNo],
+                registration_pattern: Some(r"^[0-9]{4}$"),
+            },
+        }
+    }
+
+    /// Whether the field takes part in matching documents of this type
+    pub fn matches_on(&self, field: Field) -> bool {
+        self.schema().matching.contains(&field)
+    }
+
+    /// Validates the fields of a posted Identification
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::ids::documents::{DocumentFields, DocumentType};
+    ///
+    /// let card = DocumentFields {
+    ///     registration_no: Some("4111 1111 1111 1111"),
+    ///     ..Default::default()
+    /// };
+    /// assert!(DocumentType::BankCard.validate_identification(&card).is_err());
+    ///
+    /// let card = DocumentFields {
+    ///     registration_no: Some("1111"),
+    ///     ..Default::default()
+    /// };
+    /// assert!(DocumentType::BankCard.validate_identification(&card).is_ok());
+    /// assert!(DocumentType::StudentId.validate_identification(&card).is_err());
+    /// ```
+    pub fn validate_identification(&self, fields: &DocumentFields) -> Result<(), ResError> {
+        self.validate(self.schema().required, fields)
+    }
+
+    /// Validates the fields of a Claim
+    pub fn validate_claim(&self, fields: &DocumentFields) -> Result<(), ResError> {
+        self.validate(self.schema().claim_required, fields)
+    }
+
+    /// Checks that the `required` fields are given, and that the
+    /// document number is of the type's pattern.
+    fn validate(&self, required: &[Field], fields: &DocumentFields) -> Result<(), ResError> {
+        let missing = required
+            .iter()
+            .filter(|f| !fields.has(**f))
+            .map(|f| f.as_str())
+            .collect::<Vec<&str>>();
+
+        if !missing.is_empty() {
+            return Err(ResError::new(
+                format!("A {} should have: {}", self.label(), missing.join(", ")),
+                400,
+            ));
+        }
+
+        if let (Some(pattern), Some(reg_no)) =
+            (self.schema().registration_pattern, fields.registration_no)
+        {
+            if !Regex::new(pattern)
+                .unwrap()
+                .is_match(&default_canonical(reg_no))
+            {
+                return Err(ResError::new(
+                    format!("registration_no is not valid for a {}", self.label()),
+                    400,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Human readable name of the document type
+    pub fn label(&self) -> &'static str {
+        match self {
+            DocumentType::StudentId => "student ID",
+            DocumentType::NationalId => "national ID",
+            DocumentType::Passport => "passport",
+            DocumentType::DrivingLicence => "driving licence",
+            DocumentType::StaffCard => "staff card",
+            DocumentType::LibraryCard => "library card",
+            DocumentType::BankCard => "bank card (last four digits)",
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Claim lifecycle
+//!
+//! A Claim is `open` until an Identification matches it, `matched`
+//! while it has matches, and `verified` once an ownership request of
+//! a matched Identification is approved. It is `fulfilled` when the
+//! Identification is handed over to its owner.
+//!
+//! Open and matched claims are active. Only active claims are matched,
+//! and count towards a user's claim limit.
+//!
+//! An active claim expires `CLAIM_EXPIRY_DAYS` after its last status
+//! change. Its owner is reminded `CLAIM_EXPIRY_REMINDER_DAYS` before,
+//! and may renew the claim to keep it active.
+
+use super::{models::ClaimableIdentification, utils::send_expiry_reminder};
+use crate::{
+    apps::{
+        events::models::EventKind,
+        institution::memberships::{InstitutionRole, Membership},
+        jobs::models::JobKind,
+        notifications::models::Notification,
+        user::models::User,
+    },
+    diesel_cfg::config::connect_to_db,
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{self, prelude::*, sql_types::Varchar, PgConnection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use std::env;
+
+/// Days an active claim stays unchanged before it expires
+const DEFAULT_EXPIRY_DAYS: i64 = 180;
+
+/// Days before expiry that the owner of a claim is reminded
+const DEFAULT_REMINDER_DAYS: i64 = 14;
+
+/// Status of a Claim
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimStatus {
+    /// No Identification matches the claim
+    Open,
+    /// Has matching Identifications
+    Matched,
+    /// Ownership of a matched Identification is confirmed
+    Verified,
+    /// The Identification was handed over to the owner
+    Fulfilled,
+    /// Closed by its owner
+    Withdrawn,
+    /// Left inactive for too long
+    Expired,
+}
+
+varchar_enum!(ClaimStatus {
+    Open => "open",
+    Matched => "matched",
+    Verified => "verified",
+    Fulfilled => "fulfilled",
+    Withdrawn => "withdrawn",
+    Expired => "expired"
+});
+
+impl Default for ClaimStatus {
+    fn default() -> Self {
+        ClaimStatus::Open
+    }
+}
+
+impl ClaimStatus {
+    /// Statuses of claims that are still looked for
+    pub const ACTIVE: [ClaimStatus; 2] = [ClaimStatus::Open, ClaimStatus::Matched];
+
+    /// Whether the claim is still looked for
+    pub fn is_active(self) -> bool {
+        Self::ACTIVE.contains(&self)
+    }
+
+    /// Statuses of claims that expire when left inactive
+    pub fn expirable() -> Vec<ClaimStatus> {
+        Self::ACTIVE
+            .iter()
+            .copied()
+            .filter(|s| s.can_become(ClaimStatus::Expired))
+            .collect()
+    }
+
+    /// Whether a claim of this status may change to `next`
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::ids::lifecycle::ClaimStatus::*;
+    ///
+    /// assert!(Open.can_become(Matched));
+    /// assert!(Matched.can_become(Verified));
+    /// assert!(Verified.can_become(Fulfilled));
+    /// assert!(Expired.can_become(Open));
+    ///
+    /// assert!(!Open.can_become(Verified));
+    /// assert!(!Matched.can_become(Fulfilled));
+    /// assert!(!Withdrawn.can_become(Open));
+    /// assert!(!Fulfilled.can_become(Withdrawn));
+    /// ```
+    pub fn can_become(self, next: ClaimStatus) -> bool {
+        use ClaimStatus::*;
+
+        match (self, next) {
+            (Open, Matched) | (Open, Withdrawn) | (Open, Expired) => true,
+            (Matched, Open) | (Matched, Verified) | (Matched, Withdrawn) | (Matched, Expired) => {
+                true
+            }
+            (Verified, Fulfilled) => true,
+            (Expired, Open) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Counts from a completed expiry run
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ExpiryReport {
+    /// Owners emailed a reminder of their claims' expiry
+    pub reminded: usize,
+
+    /// Reminder emails that could not be sent
+    pub reminders_failed: usize,
+
+    /// Claims that expired
+    pub expired: usize,
+}
+
+impl ClaimableIdentification {
+    /// Moves the claim to the `next` status.
+    ///
+    /// This fails with a conflict if the transition isn't allowed,
+    /// or the claim's status was changed in the meantime.
+    pub fn transition(&self, next: ClaimStatus) -> Result<Self, ResError> {
+        self.transition_on(&connect_to_db(), next)
+    }
+
+    /// Moves the claim to the `next` status on the given connection
+    pub fn transition_on(&self, conn: &PgConnection, next: ClaimStatus) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+            claimed_identifications, expiry_reminded_at, id, status, status_changed_at,
+        };
+
+        if !self.status.can_become(next) {
+            return Err(ResError::new(
+                format!("A {} claim can't be {}", self.status, next),
+                409,
+            ));
+        }
+
+        diesel::update(claimed_identifications.filter(id.eq(self.id).and(status.eq(self.status))))
+            .set((
+                status.eq(next),
+                status_changed_at.eq(Utc::now().naive_utc()),
+                expiry_reminded_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result::<Self>(conn)
+            .optional()?
+            .ok_or_else(|| ResError::new("The claim was changed. Try again".into(), 409))
+    }
+
+    /// Closes a claim on the request of its owner, or of an
+    /// admin of its institution moderating it
+    pub fn withdraw(&self, usr: &User) -> Result<Self, ResError> {
+        if self.user_id != usr.id
+            && !Membership::holds(usr, self.institution_id, InstitutionRole::Admin)?
+        {
+            return Err(ResError::unauthorized());
+        }
+        self.transition(ClaimStatus::Withdrawn)
+    }
+
+    /// Keeps a claim active on its owner's request.
+    ///
+    /// An expired claim is reopened and matched again, if the user
+    /// holds fewer active claims than allowed. The expiry of an
+    /// active claim is postponed.
+    pub fn renew(&self, usr: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+            expiry_reminded_at, status_changed_at,
+        };
+
+        if self.user_id != usr.id {
+            return Err(ResError::unauthorized());
+        }
+
+        if self.status == ClaimStatus::Expired {
+            ClaimableIdentification::check_claim_limit(usr)?;
+            let claim = self.transition(ClaimStatus::Open)?;
+            JobKind::MatchClaim { claim_id: claim.id }.enqueue()?;
+            return Ok(claim);
+        }
+        if !self.status.is_active() {
+            return Err(ResError::new(
+                format!("A {} claim can't be renewed", self.status),
+                409,
+            ));
+        }
+
+        Ok(diesel::update(self)
+            .set((
+                status_changed_at.eq(Utc::now().naive_utc()),
+                expiry_reminded_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the active Claims
+    pub fn retrieve_active() -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+            claimed_identifications, status,
+        };
+
+        Ok(claimed_identifications
+            .filter(status.eq_any(&ClaimStatus::ACTIVE[..]))
+            .load::<Self>(&connect_to_db())?)
+    }
+}
+
+/// Marks an open claim as matched
+pub(crate) fn mark_matched(claim_pk: i32) -> Result<(), diesel::result::Error> {
+    use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+        claimed_identifications, expiry_reminded_at, id, status, status_changed_at,
+    };
+
+    diesel::update(
+        claimed_identifications.filter(id.eq(claim_pk).and(status.eq(ClaimStatus::Open))),
+    )
+    .set((
+        status.eq(ClaimStatus::Matched),
+        status_changed_at.eq(Utc::now().naive_utc()),
+        expiry_reminded_at.eq(None::<NaiveDateTime>),
+    ))
+    .execute(&connect_to_db())?;
+    Ok(())
+}
+
+/// Reopens a matched claim left without matches
+pub(crate) fn reopen_if_unmatched(claim_pk: i32) -> Result<(), diesel::result::Error> {
+    use crate::diesel_cfg::schema::{claimed_identifications as claims, matched_identifications};
+    use diesel::dsl::{exists, not};
+
+    diesel::update(
+        claims::table.filter(
+            claims::id
+                .eq(claim_pk)
+                .and(claims::status.eq(ClaimStatus::Matched))
+                .and(not(exists(
+                    matched_identifications::table
+                        .filter(matched_identifications::claim_id.eq(claim_pk)),
+                ))),
+        ),
+    )
+    .set((
+        claims::status.eq(ClaimStatus::Open),
+        claims::status_changed_at.eq(Utc::now().naive_utc()),
+        claims::expiry_reminded_at.eq(None::<NaiveDateTime>),
+    ))
+    .execute(&connect_to_db())?;
+    Ok(())
+}
+
+/// Reads a number of days from an ENV variable
+fn days_from_env(var: &str, default: i64) -> Duration {
+    Duration::days(
+        env::var(var)
+            .ok()
+            .and_then(|d| d.parse::<i64>().ok())
+            .unwrap_or(default),
+    )
+}
+
+impl ExpiryReport {
+    /// Reminds the owners of claims close to expiry, and expires
+    /// claims whose owners were reminded long enough ago.
+    ///
+    /// A claim only expires once its owner has been reminded. The
+    /// reminder is recorded as a notification, so a failed reminder
+    /// email doesn't postpone the expiry.
+    pub async fn run() -> Result<ExpiryReport, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+            claimed_identifications, expiry_reminded_at, status, status_changed_at,
+        };
+
+        let mut report = ExpiryReport::default();
+
+        let expiry = days_from_env("CLAIM_EXPIRY_DAYS", DEFAULT_EXPIRY_DAYS);
+        let notice = days_from_env("CLAIM_EXPIRY_REMINDER_DAYS", DEFAULT_REMINDER_DAYS);
+        let now = Utc::now().naive_utc();
+        let expirable = ClaimStatus::expirable();
+
+        let due_reminders = claimed_identifications
+            .filter(
+                status
+                    .eq_any(&expirable)
+                    .and(status_changed_at.le(now - expiry + notice))
+                    .and(expiry_reminded_at.is_null()),
+            )
+            .load::<ClaimableIdentification>(&connect_to_db())?;
+
+        for claim in due_reminders {
+            let expires_on = (claim.status_changed_at + expiry).max(now + notice);
+
+            Notification::notify(
+                &[claim.user_id],
+                EventKind::ClaimExpiring,
+                "Your claim is about to expire",
+                &format!(
+                    "Your claim for the identification of {} expires on {}. Renew it to keep it active",
+                    claim.name,
+                    expires_on.format("%d %B %Y")
+                ),
+                json!({"claim_id": claim.id}),
+            );
+            diesel::update(&claim)
+                .set(expiry_reminded_at.eq(now))
+                .execute(&connect_to_db())?;
+
+            match send_expiry_reminder(&claim, expires_on.date()).await {
+                Ok(_) => report.reminded += 1,
+                Err(e) => {
+                    error!(
+                        "Claim expiry: Error reminding owner of claim {}: {}",
+                        claim.id, e.msg
+                    );
+                    report.reminders_failed += 1;
+                }
+            }
+        }
+
+        let expired = diesel::update(
+            claimed_identifications.filter(
+                status
+                    .eq_any(&expirable)
+                    .and(status_changed_at.le(now - expiry))
+                    .and(expiry_reminded_at.le(now - notice)),
+            ),
+        )
+        .set((status.eq(ClaimStatus::Expired), status_changed_at.eq(now)))
+        .get_results::<ClaimableIdentification>(&connect_to_db())?;
+
+        for claim in &expired {
+            Notification::notify(
+                &[claim.user_id],
+                EventKind::ClaimExpired,
+                "Your claim expired",
+                &format!(
+                    "Your claim for the identification of {} expired. Renew it to have it matched again",
+                    claim.name
+                ),
+                json!({"claim_id": claim.id}),
+            );
+        }
+        report.expired = expired.len();
+
+        Ok(report)
+    }
+}
@@ -1,4 +1,15 @@
+pub mod blocking;
+pub mod bulk;
+pub mod documents;
+pub mod lifecycle;
 pub mod models;
+pub mod ownership;
+pub mod registration;
+pub mod searches;
+pub mod sweep;
 pub mod utils;
 pub mod validators;
 pub mod views;
+
+#[cfg(test)]
+pub mod tests;
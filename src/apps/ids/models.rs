@@ -1,9 +1,22 @@
 //! Identification card models
 
-use super::validators::regexes;
+use super::{
+    blocking,
+    documents::{DocumentFields, DocumentType, Field},
+    lifecycle::{self, ClaimStatus},
+    registration::{self, Checksum, RegistrationFormat},
+    validators::regexes,
+};
 use crate::{
-    apps::user::models::{AccessLevel, User},
+    apps::events::models::EventKind,
+    apps::institution::{
+        memberships::{InstitutionRole, Membership},
+        pickup::PickupPoint,
+    },
+    apps::notifications::models::Notification,
+    apps::user::models::User,
     apps::user::utils::from_timestamp,
+    apps::webhooks::models::WebhookEvent,
     diesel_cfg::{
         config::connect_to_db,
         schema::{claimed_identifications, identifications, matched_identifications},
@@ -13,19 +26,38 @@ use crate::{
 };
 
 use chrono::{NaiveDate, NaiveDateTime};
-use diesel::{self, prelude::*};
+use diesel::{self, prelude::*, PgConnection};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use validator::Validate;
 use validator_derive::Validate;
 
 // use diesel_geometry::data_types::PgPoint;
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap, env};
 
 use actix_web::HttpRequest;
 
 use futures::future::join;
 
+/// Minimum similarity of a Claim and an Identification
+/// of the same institution.
+///
+/// A name-only match is .90*.6
+const MATCH_THRESHOLD: f64 = 0.54;
+
+/// Minimum similarity of a Claim and an Identification when either
+/// has no institution.
+///
+/// Such pairs are compared across institutions, so a name-only match
+/// does not qualify. It takes a closely matching name together with
+/// the course, or the registration number.
+const CROSS_INSTITUTION_THRESHOLD: f64 = 0.75;
+
+/// Number of claims a user may hold if
+/// `MAX_CLAIMS_PER_USER` is missing
+const DEFAULT_MAX_CLAIMS: i64 = 5;
+
 /// Represents a matched Identification-Claim
 #[derive(Queryable, Serialize, Deserialize, Identifiable)]
 #[table_name = "matched_identifications"]
@@ -47,7 +79,7 @@ pub struct Identification {
     pub name: String,
 
     /// Major undertaken by holder or the Department
-    pub course: String,
+    pub course: Option<String>,
 
     /// Holder's starting Date(y-m-d)
     pub valid_from: Option<NaiveDate>,
@@ -60,7 +92,7 @@ pub struct Identification {
     pub location_name: String,
 
     pub picture: Option<String>,
-    posted_by: Option<i32>,
+    pub posted_by: Option<i32>,
     is_found: bool,
 
     #[serde(deserialize_with = "from_timestamp")]
@@ -75,7 +107,7 @@ pub struct Identification {
     about: Option<String>,
 
     /// The user the Identification belongs to
-    owner: Option<i32>,
+    pub owner: Option<i32>,
 
     /// Latitude  representation of the id location point
     /// To be used together with `location_longitude`
@@ -89,6 +121,15 @@ pub struct Identification {
 
     /// The Foreign key of the institution the Identification belongs to.
     pub institution_id: Option<i32>,
+
+    /// Normalized `registration_no`, used in matching and search
+    registration_no_canonical: Option<String>,
+
+    /// The kind of document the Identification is
+    pub document_type: DocumentType,
+
+    /// The pickup point the Identification can be collected from
+    pub pickup_point_id: Option<i32>,
 }
 
 /// The Insertable new Identification record
@@ -100,17 +141,19 @@ pub struct NewIdentification<'a> {
     pub name: Cow<'a, str>,
 
     #[validate(regex(path = "regexes::ALPHA_REGEX", message = "should just have letters"))]
-    pub course: Cow<'a, str>,
+    pub course: Option<Cow<'a, str>>,
 
     pub valid_from: Option<NaiveDate>,
     pub valid_till: Option<NaiveDate>,
 
     institution_id: Option<i32>,
 
+    /// Taken from the pickup point, if one is given
     #[validate(regex(
         path = "regexes::LOCATION_REGEX",
         message = "should have letters, digits or -_`"
     ))]
+    #[serde(default)]
     location_name: Cow<'a, str>,
 
     pub posted_by: Option<i32>,
@@ -119,6 +162,17 @@ pub struct NewIdentification<'a> {
     location_latitude: Option<f64>,
     location_longitude: Option<f64>,
     registration_no: Option<String>,
+
+    #[serde(skip_deserializing)]
+    registration_no_canonical: Option<String>,
+
+    /// The kind of document posted. A student ID if not given
+    #[serde(default)]
+    pub document_type: DocumentType,
+
+    /// Where the Identification can be collected from,
+    /// in place of the location name
+    pub pickup_point_id: Option<i32>,
 }
 
 /// Identification model to be used in updating
@@ -151,6 +205,27 @@ pub struct UpdatableIdentification<'a> {
     location_latitude: Option<f64>,
     location_longitude: Option<f64>,
     registration_no: Option<String>,
+
+    document_type: Option<DocumentType>,
+
+    pickup_point_id: Option<i32>,
+}
+
+impl<'a> UpdatableIdentification<'a> {
+    /// Moves the Identification to a pickup point, if one is given.
+    ///
+    /// The location name becomes that of the point, and an
+    /// Identification of no institution takes its institution.
+    pub fn resolve_pickup_point(&mut self, idt: &Identification) -> Result<(), ResError> {
+        if let Some(point_id) = self.pickup_point_id {
+            let point = PickupPoint::find_by_id(point_id)?;
+            point.takes_ids_of(self.institution_id.or(idt.institution_id))?;
+
+            self.institution_id = Some(point.institution_id);
+            self.location_name = Some(Cow::Owned(point.name));
+        }
+        Ok(())
+    }
 }
 
 /// The queryable model of claimed identifications
@@ -166,7 +241,7 @@ pub struct ClaimableIdentification {
     pub id: i32,
     pub user_id: i32,
     pub name: String,
-    pub course: String,
+    pub course: Option<String>,
     entry_year: Option<NaiveDate>,
     graduation_year: Option<NaiveDate>,
 
@@ -178,6 +253,22 @@ pub struct ClaimableIdentification {
     registration_no: Option<String>,
 
     pub institution_id: Option<i32>,
+
+    /// Normalized `registration_no`, used in matching and search
+    registration_no_canonical: Option<String>,
+
+    /// The kind of document claimed
+    pub document_type: DocumentType,
+
+    pub status: ClaimStatus,
+
+    /// When the claim last changed status, or was renewed
+    #[serde(deserialize_with = "from_timestamp")]
+    pub status_changed_at: NaiveDateTime,
+
+    /// When the owner was reminded of the claim's expiry
+    #[serde(skip)]
+    pub expiry_reminded_at: Option<NaiveDateTime>,
 }
 
 /// The Insertable model of Claimable Identifications
@@ -200,6 +291,13 @@ pub struct NewClaimableIdt<'a> {
     institution_id: Option<i32>,
 
     registration_no: Option<Cow<'a, str>>,
+
+    #[serde(skip_deserializing)]
+    registration_no_canonical: Option<String>,
+
+    /// The kind of document claimed. A student ID if not given
+    #[serde(default)]
+    pub document_type: DocumentType,
 }
 
 /// The Insertable model to be used in updating
@@ -220,15 +318,30 @@ pub struct UpdatableClaimableIdt<'a> {
     pub institution_id: Option<i32>,
 
     pub registration_no: Option<Cow<'a, str>>,
+
+    pub document_type: Option<DocumentType>,
+}
+
+/// A Claim and its matches
+#[derive(Serialize, Deserialize)]
+pub struct ClaimMatchStatus {
+    #[serde(flatten)]
+    pub claim: ClaimableIdentification,
+
+    /// Whether any Identification matches the claim
+    pub is_matched: bool,
+
+    /// Ids of the Identifications matching the claim
+    pub matched_identifications: Vec<i32>,
 }
 
 /// Json Model for an Identification claim request
 #[derive(Deserialize)]
 pub struct MatchedIdtJson {
     /// The identification ID a User wants to claim
-    idt: i32,
+    pub idt: i32,
     /// A (possibly) matching Claimable Identification ID
-    claim: i32,
+    pub claim: i32,
 }
 
 impl PartialEq<NewClaimableIdt<'_>> for ClaimableIdentification {
@@ -236,13 +349,13 @@ impl PartialEq<NewClaimableIdt<'_>> for ClaimableIdentification {
         let match_fields = [
             self.entry_year.eq(&claim.entry_year),
             self.graduation_year.eq(&claim.graduation_year),
-            if claim.course.is_some() {
-                self.course.eq(claim.course.as_ref().unwrap())
-            } else {
-                false
-            },
+            claim
+                .course
+                .as_deref()
+                .map_or(false, |c| self.course.as_deref() == Some(c)),
             self.institution_id.eq(&claim.institution_id),
             self.name.eq(&claim.name),
+            self.document_type.eq(&claim.document_type),
         ];
 
         match_fields.iter().all(|&field| field)
@@ -256,11 +369,10 @@ impl PartialEq<ClaimableIdentification> for NewClaimableIdt<'_> {
             self.institution_id.eq(&claim.institution_id),
             self.name.eq(&claim.name),
             self.graduation_year.eq(&claim.graduation_year),
-            if self.course.is_some() {
-                self.course.as_ref().unwrap().eq(&claim.course)
-            } else {
-                false
-            },
+            self.document_type.eq(&claim.document_type),
+            self.course
+                .as_deref()
+                .map_or(false, |c| claim.course.as_deref() == Some(c)),
         ];
 
         match_fields.iter().all(|&field| field)
@@ -271,7 +383,8 @@ impl PartialEq<Identification> for NewIdentification<'_> {
     fn eq(&self, idt: &Identification) -> bool {
         let comp_vec = vec![
             self.name.eq(&idt.name),
-            self.course.eq(&idt.course),
+            self.course.as_deref().eq(&idt.course.as_deref()),
+            self.document_type.eq(&idt.document_type),
             self.valid_from.eq(&idt.valid_from),
             self.valid_till.eq(&idt.valid_till),
             self.institution_id.eq(&idt.institution_id),
@@ -279,7 +392,8 @@ impl PartialEq<Identification> for NewIdentification<'_> {
             self.location_latitude.eq(&idt.location_latitude),
             self.location_longitude.eq(&idt.location_longitude),
             self.posted_by.eq(&idt.posted_by),
-            self.registration_no.eq(&idt.registration_no),
+            self.registration_no_canonical
+                .eq(&idt.registration_no_canonical),
         ];
 
         let is_equal = comp_vec.into_iter().all(|v| v);
@@ -293,7 +407,8 @@ impl PartialEq<NewIdentification<'_>> for Identification {
     fn eq(&self, idt: &NewIdentification) -> bool {
         let comp_vec = vec![
             self.name.eq(&idt.name),
-            self.course.eq(&idt.course),
+            self.course.as_deref().eq(&idt.course.as_deref()),
+            self.document_type.eq(&idt.document_type),
             self.valid_from.eq(&idt.valid_from),
             self.valid_till.eq(&idt.valid_till),
             self.institution_id.eq(&idt.institution_id),
@@ -301,7 +416,8 @@ impl PartialEq<NewIdentification<'_>> for Identification {
             self.location_latitude.eq(&idt.location_latitude),
             self.location_longitude.eq(&idt.location_longitude),
             self.posted_by.eq(&idt.posted_by),
-            self.registration_no.eq(&idt.registration_no),
+            self.registration_no_canonical
+                .eq(&idt.registration_no_canonical),
         ];
 
         let is_equal = comp_vec.into_iter().all(|v| v);
@@ -310,17 +426,29 @@ impl PartialEq<NewIdentification<'_>> for Identification {
 }
 impl<'a> NewIdentification<'a> {
     /// Saves a new ID record to the Identifications table
-    pub async fn save(&self) -> Result<Identification, ResError> {
+    ///
+    /// The registration number is normalized to the formats
+    /// of the institution.
+    pub async fn save(&mut self) -> Result<Identification, ResError> {
         use crate::diesel_cfg::schema::identifications::dsl::{
-            course, identifications as _identifications, institution_id, name, registration_no,
+            document_type, identifications as _identifications, institution_id, name,
+            registration_no_canonical,
         };
 
+        self.document_type
+            .validate_identification(&self.document_fields())?;
+
+        self.registration_no_canonical = registration::canonical_registration_no(
+            self.registration_no.as_deref(),
+            self.institution_id,
+        )?;
+
         let presents = _identifications
             .filter(
                 name.eq(&self.name)
-                    .and(course.eq(&self.course))
+                    .and(document_type.eq(self.document_type))
                     .and(institution_id.eq(&self.institution_id))
-                    .and(registration_no.eq(&self.registration_no)),
+                    .and(registration_no_canonical.eq(&self.registration_no_canonical)),
             )
             .load::<Identification>(&connect_to_db())?;
         for ident in &presents {
@@ -332,19 +460,72 @@ impl<'a> NewIdentification<'a> {
             }
         }
 
-        let idt = diesel::insert_into(identifications::table)
-            .values(&*self)
-            .get_result::<Identification>(&connect_to_db())?;
+        let conn = connect_to_db();
+        let idt = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let idt = diesel::insert_into(identifications::table)
+                .values(&*self)
+                .get_result::<Identification>(&conn)?;
+            idt.refresh_blocks_on(&conn)?;
+            Ok(idt)
+        })?;
 
+        WebhookEvent::IdentificationCreated.publish(
+            idt.institution_id,
+            json!({ "identification": idt.webhook_data() }),
+        );
         Ok(idt)
     }
 
+    /// Posts the Identification as handed in to the institution,
+    /// and logged by the user
+    pub fn hand_in(&mut self, inst_id: i32, usr: &User) {
+        self.institution_id = Some(inst_id);
+        self.posted_by = Some(usr.id);
+    }
+
+    /// Places the Identification at its pickup point, if it has one.
+    ///
+    /// The Identification takes the name and coordinates of the point
+    /// as its location, and its institution if it has none.
+    pub fn resolve_pickup_point(&mut self) -> Result<(), ResError> {
+        if let Some(point_id) = self.pickup_point_id {
+            let point = PickupPoint::find_by_id(point_id)?;
+            point.takes_ids_of(self.institution_id)?;
+
+            self.institution_id = Some(point.institution_id);
+            self.location_name = Cow::Owned(point.name);
+            if self.location_latitude.is_none() {
+                self.location_latitude = point.latitude;
+                self.location_longitude = point.longitude;
+            }
+        }
+        Ok(())
+    }
+
+    /// The fields given on the Identification
+    fn document_fields(&self) -> DocumentFields {
+        DocumentFields {
+            course: self.course.as_deref(),
+            valid_from: self.valid_from,
+            valid_till: self.valid_till,
+            registration_no: self.registration_no.as_deref(),
+            institution_id: self.institution_id,
+        }
+    }
+
     /// Finds the similarity between a Claim and this Identification,
     /// returning true if the Claim is a  possible match.
     ///
     /// Matching metric is cosine similarity.
-    pub async fn is_possible_match(idt: &Identification, claim: &ClaimableIdentification) -> bool {
-        ClaimableIdentification::is_matching_idt(claim, idt).await
+    ///
+    /// `checksum` is the check digit scheme of the registration
+    /// numbers compared.
+    pub async fn is_possible_match(
+        idt: &Identification,
+        claim: &ClaimableIdentification,
+        checksum: Option<Checksum>,
+    ) -> bool {
+        ClaimableIdentification::is_matching_idt(claim, idt, checksum).await
     }
 }
 impl Identification {
@@ -358,6 +539,15 @@ impl Identification {
         Ok(idt)
     }
 
+    /// Sets the url of the photo of the Identification
+    pub fn set_picture(&self, url: &str) -> Result<Identification, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::picture;
+
+        Ok(diesel::update(&*self)
+            .set(picture.eq(url))
+            .get_result::<Identification>(&connect_to_db())?)
+    }
+
     /// Retrieves all existing Identifications
     ///
     /// # Arguments
@@ -369,24 +559,30 @@ impl Identification {
     /// The default is missing, for a status argument that
     /// does not match any of the three options.
     ///
+    /// ## doc_type: Option<DocumentType>
+    /// Only retrieve Idts of this document type
+    ///
     /// # Returns
     /// An empty vec if none is present
-    pub fn retrieve_all(status: &str) -> Result<Vec<Identification>, ResError> {
-        use crate::diesel_cfg::schema::identifications::dsl::{identifications, is_found as found};
-
-        let idts = if status == "all" {
-            identifications.load::<Identification>(&connect_to_db())?
-        } else if status == "found" {
-            identifications
-                .filter(found.eq(true))
-                .load::<Self>(&connect_to_db())?
-        } else {
-            identifications
-                .filter(found.eq(false))
-                .load::<Self>(&connect_to_db())?
+    pub fn retrieve_all(
+        status: &str,
+        doc_type: Option<DocumentType>,
+    ) -> Result<Vec<Identification>, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::{
+            document_type, identifications, is_found as found,
         };
 
-        Ok(idts)
+        let mut query = identifications.into_boxed();
+        if status == "found" {
+            query = query.filter(found.eq(true));
+        } else if status != "all" {
+            query = query.filter(found.eq(false));
+        }
+        if let Some(doc_type) = doc_type {
+            query = query.filter(document_type.eq(doc_type));
+        }
+
+        Ok(query.load::<Identification>(&connect_to_db())?)
     }
 
     /// Retrieves all Identifications matching a given
@@ -396,19 +592,26 @@ impl Identification {
     /// ## institution: &str
     /// The name of the institution to search for.
     ///
+    /// ## doc_type: Option<DocumentType>
+    /// Only retrieve Idts of this document type
+    ///
     /// # Returns
     /// Resulting vector of Identifications.
     pub fn retrieve_by_institution_id(
         institution_pk: i32,
+        doc_type: Option<DocumentType>,
     ) -> Result<Vec<Identification>, ResError> {
         use crate::diesel_cfg::schema::identifications::dsl::{
-            identifications, institution_id as _institution,
+            document_type, identifications, institution_id as _institution,
         };
-        let idts = identifications
+        let mut query = identifications
             .filter(_institution.eq(institution_pk))
-            .load::<Identification>(&connect_to_db())?;
+            .into_boxed();
+        if let Some(doc_type) = doc_type {
+            query = query.filter(document_type.eq(doc_type));
+        }
 
-        Ok(idts)
+        Ok(query.load::<Identification>(&connect_to_db())?)
     }
 
     /// Marks the identification matching the given key as found
@@ -423,6 +626,12 @@ impl Identification {
         } else {
             idt.is_found = true;
             idt.save_changes::<Identification>(&connect_to_db())?;
+            idt.publish_found_status()?;
+
+            WebhookEvent::IdentificationFound.publish(
+                idt.institution_id,
+                json!({ "identification": idt.webhook_data() }),
+            );
             Ok(idt)
         }
     }
@@ -452,11 +661,65 @@ impl Identification {
         } else {
             idt.is_found = false;
             idt.save_changes::<Identification>(&connect_to_db())?;
+            idt.publish_found_status()?;
             Ok(idt)
         }
     }
 
+    /// The Identification as posted to webhooks.
+    ///
+    /// Who posted and owns it are left out.
+    pub fn webhook_data(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "document_type": self.document_type,
+            "institution_id": self.institution_id,
+            "location_name": self.location_name,
+            "is_found": self.is_found,
+        })
+    }
+
+    /// Tells the poster, the owner, and the claimants matched to
+    /// the Identification of a change of its found status
+    fn publish_found_status(&self) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::{claimed_identifications, matched_identifications};
+
+        let mut users = matched_identifications::table
+            .inner_join(claimed_identifications::table)
+            .filter(matched_identifications::identification_id.eq(self.id))
+            .select(claimed_identifications::user_id)
+            .load::<i32>(&connect_to_db())?;
+        users.extend(self.posted_by);
+        users.extend(self.owner);
+        users.sort_unstable();
+        users.dedup();
+
+        let (title, body) = if self.is_found {
+            (
+                "Identification found",
+                format!("The identification of {} was marked found", self.name),
+            )
+        } else {
+            (
+                "Identification lost",
+                format!("The identification of {} was marked lost", self.name),
+            )
+        };
+        Notification::notify(
+            &users,
+            EventKind::FoundStatusChanged,
+            title,
+            &body,
+            json!({"identification_id": self.id, "is_found": self.is_found}),
+        );
+        Ok(())
+    }
+
     /// Updates the Idt with the given data
+    ///
+    /// Only its poster, or custodians and admins of its
+    /// institution, may update it.
     pub fn update(
         &self,
         auth_tk: &HttpRequest,
@@ -464,15 +727,35 @@ impl Identification {
     ) -> Result<Identification, ResError> {
         let this_user = User::from_token(auth_tk)?;
         if let Some(pu_id) = self.posted_by {
-            if this_user.id != pu_id && this_user.id != AccessLevel::Moderator as i32 {
+            if this_user.id != pu_id
+                && !Membership::holds(&this_user, self.institution_id, InstitutionRole::Custodian)?
+            {
                 return Err(ResError::unauthorized());
             }
         }
 
-        let new_idt = diesel::update(&*self)
-            .set(data)
-            .get_result::<Identification>(&connect_to_db())?;
-        Ok(new_idt)
+        // The fields of the document once updated
+        let doc_type = data.document_type.unwrap_or(self.document_type);
+        doc_type.validate_identification(&DocumentFields {
+            course: data.course.as_deref().or_else(|| self.course.as_deref()),
+            valid_from: data.valid_from.or(self.valid_from),
+            valid_till: data.valid_till.or(self.valid_till),
+            registration_no: data
+                .registration_no
+                .as_deref()
+                .or_else(|| self.registration_no.as_deref()),
+            institution_id: data.institution_id.or(self.institution_id),
+        })?;
+
+        let conn = connect_to_db();
+        conn.transaction::<_, ResError, _>(|| {
+            let new_idt = diesel::update(&*self)
+                .set(data)
+                .get_result::<Identification>(&conn)?
+                .normalize_registration_no(&conn)?;
+            new_idt.refresh_blocks_on(&conn)?;
+            Ok(new_idt)
+        })
     }
 
     /// Retrieves the idenfications that have been posted by the passed user instance.
@@ -506,10 +789,16 @@ impl Identification {
 
     /// Checks if the Identification and Claim IDs given in
     /// the MatchedIdtJson request match each other.
+    ///
+    /// The claim should be the user's, and still open to verification.
+    /// The Identification should not have an owner.
+    ///
+    /// # Returns
+    /// The matching Identification and Claim
     pub fn search_matching_claim(
         data: &MatchedIdtJson,
         usr: &User,
-    ) -> Result<Identification, ResError> {
+    ) -> Result<(Identification, ClaimableIdentification), ResError> {
         use crate::diesel_cfg::schema::claimed_identifications::dsl::claimed_identifications;
         use crate::diesel_cfg::schema::identifications::dsl::identifications;
         use crate::diesel_cfg::schema::matched_identifications::dsl::*;
@@ -525,44 +814,239 @@ impl Identification {
         if this_claim.user_id != usr.id {
             return Err(ResError::unauthorized());
         }
+        if !this_claim.status.can_become(ClaimStatus::Verified) {
+            return Err(ResError::new(
+                format!("A {} claim can't be verified", this_claim.status),
+                409,
+            ));
+        }
 
-        let mut this_idt = identifications
+        let this_idt = identifications
             .find(idt_match.identification_id)
             .first::<Identification>(&connect_to_db())?;
 
-        this_idt.owner = Some(usr.id);
-        let saved_idt = this_idt.save_changes::<Identification>(&connect_to_db())?;
+        if this_idt.owner.is_some() {
+            return Err(ResError::new(
+                "Identification already belongs to someone".into(),
+                409,
+            ));
+        }
+        Ok((this_idt, this_claim))
+    }
+
+    /// Makes the owner of the claim the owner of the Identification,
+    /// on the given connection, so it is undone with a transaction
+    /// running on it.
+    ///
+    /// The claim is verified. This fails with a conflict if the
+    /// Identification was handed over in the meantime.
+    pub fn transfer_on(
+        &self,
+        conn: &PgConnection,
+        claim: &ClaimableIdentification,
+    ) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::{id, identifications, owner};
+
+        let saved_idt = diesel::update(identifications.filter(id.eq(self.id).and(owner.is_null())))
+            .set(owner.eq(claim.user_id))
+            .get_result::<Identification>(conn)
+            .optional()?
+            .ok_or_else(|| {
+                ResError::new("Identification already belongs to someone".into(), 409)
+            })?;
+
+        claim.transition_on(conn, ClaimStatus::Verified)?;
         Ok(saved_idt)
     }
 
+    /// Tells the claimant and the poster that the Identification
+    /// was handed over to the owner of the claim
+    pub fn announce_transfer(&self, claim: &ClaimableIdentification) {
+        let mut parties = vec![claim.user_id];
+        parties.extend(self.posted_by);
+        Notification::notify(
+            &parties,
+            EventKind::Handover,
+            "Identification handed over",
+            &format!(
+                "The identification of {} was handed over to its owner",
+                self.name
+            ),
+            json!({"identification_id": self.id, "claim_id": claim.id}),
+        );
+
+        WebhookEvent::OwnershipTransferred.publish(
+            self.institution_id,
+            json!({"identification": self.webhook_data(), "claim_id": claim.id}),
+        );
+    }
+
     /// Finds Claims that match an Identification.
     ///
     /// This method should be analogous to `NewClaim.match_idt`
     ///
     /// # Returns
     /// true: If a claim match is found for the Identification.
+    ///
+    /// Only claims that were not matched before are returned, so
+    /// re-matching an Identification doesn't repeat notifications.
+    ///
+    /// Candidate claims are active, of the same document type and share a blocking
+    /// key with the Identification. Claims of institution-issued documents
+    /// are of its institution or have none. An Identification without an
+    /// institution is compared to claims of all institutions.
     pub async fn match_claims(&self) -> Result<(bool, Vec<ClaimableIdentification>), ResError> {
         use crate::diesel_cfg::schema::claimed_identifications::dsl::{
-            claimed_identifications, institution_id,
+            claimed_identifications, document_type, id, institution_id, status,
         };
 
         let mut has_match = false;
         let mut matched_claims = vec![];
 
-        let idt_claims = claimed_identifications
-            .filter(institution_id.eq(&self.institution_id))
-            .load::<ClaimableIdentification>(&connect_to_db())?;
+        let checksum = RegistrationFormat::checksum_of(self.institution_id)?;
+        let candidates = blocking::claims_in_blocks(&self.block_keys())?;
+        let mut query = claimed_identifications
+            .filter(
+                id.eq_any(candidates)
+                    .and(document_type.eq(self.document_type))
+                    .and(status.eq_any(&ClaimStatus::ACTIVE[..])),
+            )
+            .into_boxed();
+        if let (true, Some(inst)) = (self.document_type.is_institutional(), self.institution_id) {
+            query = query.filter(institution_id.eq(inst).or(institution_id.is_null()));
+        }
+        let idt_claims = query.load::<ClaimableIdentification>(&connect_to_db())?;
 
         for claim in idt_claims.into_iter() {
-            if NewIdentification::is_possible_match(self, &claim).await {
+            if NewIdentification::is_possible_match(self, &claim, checksum).await
+                && MatchedIDt::save(&claim, self).await? > 0
+            {
                 has_match = true;
-                MatchedIDt::save(&claim, self).await?;
-
                 matched_claims.push(claim);
             }
         }
         Ok((has_match, matched_claims))
     }
+
+    /// Blocking keys of the Identification
+    ///
+    /// The validity end date stands in for the graduation year.
+    pub fn block_keys(&self) -> Vec<String> {
+        blocking::block_keys(
+            &self.name,
+            self.registration_no_canonical.as_deref(),
+            self.valid_till,
+        )
+    }
+
+    /// The Identification as listed publicly.
+    ///
+    /// The number of a document not issued by an institution,
+    /// e.g a national ID or a passport, is masked.
+    pub fn masked(mut self) -> Self {
+        if !self.document_type.is_institutional() {
+            self.registration_no = self
+                .registration_no
+                .as_deref()
+                .map(registration::mask_registration_no);
+            self.registration_no_canonical = None;
+        }
+        self
+    }
+
+    /// Replaces the stored blocking keys of the Identification
+    pub fn refresh_blocks(&self) -> Result<(), ResError> {
+        let conn = connect_to_db();
+        conn.transaction(|| self.refresh_blocks_on(&conn))?;
+        Ok(())
+    }
+
+    /// Replaces the stored blocking keys of the Identification on
+    /// the connection saving it
+    pub fn refresh_blocks_on(&self, conn: &PgConnection) -> QueryResult<()> {
+        blocking::save_identification_blocks(conn, self.id, self.block_keys())
+    }
+
+    /// Recomputes the canonical registration number, saving
+    /// it on the given connection if it changed.
+    pub fn normalize_registration_no(self, conn: &PgConnection) -> Result<Self, ResError> {
+        let formats = match self.institution_id {
+            Some(inst) => RegistrationFormat::for_institution(inst)?,
+            None => vec![],
+        };
+        Ok(self.normalize_registration_no_under(conn, &formats)?.0)
+    }
+
+    /// Recomputes the canonical registration number under the
+    /// formats of the institution, saving it on the given
+    /// connection if it changed.
+    ///
+    /// # Returns
+    /// The Identification, and whether its canonical number changed
+    pub fn normalize_registration_no_under(
+        mut self,
+        conn: &PgConnection,
+        formats: &[RegistrationFormat],
+    ) -> QueryResult<(Self, bool)> {
+        let canonical = registration::canonical_under(self.registration_no.as_deref(), formats);
+
+        if canonical == self.registration_no_canonical {
+            return Ok((self, false));
+        }
+        self.registration_no_canonical = canonical;
+        Ok((self.save_changes::<Identification>(conn)?, true))
+    }
+
+    /// Finds Identifications by registration number.
+    ///
+    /// The number is compared in its canonical form, under the
+    /// formats of `institution`, or of any institution if none is given.
+    ///
+    /// Takes a custodian of the institution, or a moderator. Other
+    /// users may only search the numbers of their own claims.
+    pub fn search_by_registration_no(
+        reg_no: &str,
+        institution: Option<i32>,
+        usr: &User,
+    ) -> Result<Vec<Identification>, ResError> {
+        use crate::diesel_cfg::schema::{
+            claimed_identifications,
+            identifications::dsl::{identifications, institution_id, registration_no_canonical},
+        };
+
+        let mut forms = registration::search_forms(reg_no, institution)?;
+
+        if !Membership::holds(usr, institution, InstitutionRole::Custodian)? {
+            forms = claimed_identifications::table
+                .filter(
+                    claimed_identifications::user_id
+                        .eq(usr.id)
+                        .and(claimed_identifications::registration_no_canonical.eq_any(forms)),
+                )
+                .select(claimed_identifications::registration_no_canonical)
+                .load::<Option<String>>(&connect_to_db())?
+                .into_iter()
+                .flatten()
+                .collect();
+            if forms.is_empty() {
+                return Err(ResError::unauthorized());
+            }
+        }
+
+        let idts = match institution {
+            Some(inst) => identifications
+                .filter(
+                    registration_no_canonical
+                        .eq_any(forms)
+                        .and(institution_id.eq(inst)),
+                )
+                .load::<Identification>(&connect_to_db())?,
+            None => identifications
+                .filter(registration_no_canonical.eq_any(forms))
+                .load::<Identification>(&connect_to_db())?,
+        };
+        Ok(idts)
+    }
 }
 
 impl<'a> NewClaimableIdt<'a> {
@@ -572,17 +1056,30 @@ impl<'a> NewClaimableIdt<'a> {
             claimed_identifications as cl_idt_table,
             institution_id as c_institution,
             name as c_name,
+            status as c_status,
             //registration_no as c_reg_no,
         };
 
-        self.has_claim(&this_user).await?;
+        self.document_type.validate_claim(&DocumentFields {
+            course: self.course.as_deref(),
+            valid_from: self.entry_year,
+            valid_till: self.graduation_year,
+            registration_no: self.registration_no.as_deref(),
+            institution_id: self.institution_id,
+        })?;
+        ClaimableIdentification::check_claim_limit(&this_user)?;
         self.user_id = this_user.id;
+        self.registration_no_canonical = registration::canonical_registration_no(
+            self.registration_no.as_deref(),
+            self.institution_id,
+        )?;
 
         let existing_claims = cl_idt_table
             .filter(
                 c_name
                     .eq(self.name.as_ref())
-                    .and(c_institution.eq(self.institution_id.as_ref())),
+                    .and(c_institution.eq(self.institution_id.as_ref()))
+                    .and(c_status.eq_any(&ClaimStatus::ACTIVE[..])),
                 // .and(c_reg_no.eq(self.registration_no.as_ref())),
                 // Only non-option values are matched here.
                 // If there's need to change how claims work,
@@ -591,9 +1088,14 @@ impl<'a> NewClaimableIdt<'a> {
             .load::<ClaimableIdentification>(&connect_to_db())?;
         self.is_unique(&existing_claims)?;
 
-        let idt_claim = diesel::insert_into(claimed_identifications::table)
-            .values(&*self)
-            .get_result::<ClaimableIdentification>(&connect_to_db())?;
+        let conn = connect_to_db();
+        let idt_claim = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let idt_claim = diesel::insert_into(claimed_identifications::table)
+                .values(&*self)
+                .get_result::<ClaimableIdentification>(&conn)?;
+            idt_claim.refresh_blocks_on(&conn)?;
+            Ok(idt_claim)
+        })?;
 
         Ok(idt_claim)
     }
@@ -610,27 +1112,37 @@ impl<'a> NewClaimableIdt<'a> {
             Ok(!duplicate)
         }
     }
+}
 
-    /// Checks if a User has an existing claim.
+impl ClaimableIdentification {
+    /// Fails if the User holds as many active claims as the
+    /// `MAX_CLAIMS_PER_USER` ENV variable allows.
     ///
-    /// Users should make just one claim by default,
-    /// which they can then give controlled edits.
-    pub async fn has_claim(&self, current_user: &User) -> Result<bool, ResError> {
-        //
-        let user_claims = ClaimableIdentification::belonging_to(current_user)
-            .load::<ClaimableIdentification>(&connect_to_db())?;
-        if user_claims.is_empty() {
-            Ok(false)
+    /// Users may hold several active claims, e.g a student ID and a
+    /// national ID lost together.
+    pub fn check_claim_limit(current_user: &User) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::status;
+
+        let max_claims = env::var("MAX_CLAIMS_PER_USER")
+            .ok()
+            .and_then(|n| n.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_MAX_CLAIMS);
+
+        let claim_count = ClaimableIdentification::belonging_to(current_user)
+            .filter(status.eq_any(&ClaimStatus::ACTIVE[..]))
+            .count()
+            .get_result::<i64>(&connect_to_db())?;
+
+        if claim_count < max_claims {
+            Ok(())
         } else {
             Err(ResError {
-                msg: "Dude, you created a claim some while back".into(),
+                msg: format!("You can hold at most {} claims at a time", max_claims),
                 status: 409,
             })
         }
     }
-}
 
-impl ClaimableIdentification {
     /// Finds an Identification by its primary key
     pub fn find_by_id(key: i32) -> Result<Self, ResError> {
         use crate::diesel_cfg::schema::claimed_identifications::dsl::claimed_identifications;
@@ -654,23 +1166,64 @@ impl ClaimableIdentification {
             return Err(ResError::unauthorized());
         }
 
-        let updated_idt = diesel::update(&*self)
-            .set(data)
-            .get_result::<Self>(&connect_to_db())?;
-
-        Ok(updated_idt)
+        // The fields of the claim once updated
+        let doc_type = data.document_type.unwrap_or(self.document_type);
+        doc_type.validate_claim(&DocumentFields {
+            course: data.course.as_deref().or_else(|| self.course.as_deref()),
+            valid_from: data.entry_year.or(self.entry_year),
+            valid_till: data.graduation_year.or(self.graduation_year),
+            registration_no: data
+                .registration_no
+                .as_deref()
+                .or_else(|| self.registration_no.as_deref()),
+            institution_id: data.institution_id.or(self.institution_id),
+        })?;
+
+        let conn = connect_to_db();
+        conn.transaction::<_, ResError, _>(|| {
+            let updated_idt = diesel::update(&*self)
+                .set(data)
+                .get_result::<Self>(&conn)?
+                .normalize_registration_no(&conn)?;
+            updated_idt.refresh_blocks_on(&conn)?;
+            Ok(updated_idt)
+        })
     }
 
-    /// Get the Claimed Identification that belongs to
+    /// Get the Claimed Identifications that belong to
     /// this user
-    pub fn belonging_to_me(usr: &User) -> Result<Self, ResError> {
-        let mut idt_claim =
-            ClaimableIdentification::belonging_to(usr).load::<Self>(&connect_to_db())?;
-        if idt_claim.is_empty() {
-            Err(ResError::not_found())
-        } else {
-            Ok(idt_claim.pop().unwrap())
+    pub fn belonging_to_me(usr: &User) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::id;
+
+        Ok(ClaimableIdentification::belonging_to(usr)
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Get the Claims of this user together with their matches
+    pub fn belonging_to_me_with_matches(usr: &User) -> Result<Vec<ClaimMatchStatus>, ResError> {
+        let claims = Self::belonging_to_me(usr)?;
+        let claim_ids = claims.iter().map(|c| c.id).collect::<Vec<i32>>();
+
+        let mut matches = HashMap::<i32, Vec<i32>>::new();
+        for matched in MatchedIDt::for_claims(&claim_ids)? {
+            matches
+                .entry(matched.claim_id)
+                .or_default()
+                .push(matched.identification_id);
         }
+
+        Ok(claims
+            .into_iter()
+            .map(|claim| {
+                let matched_identifications = matches.remove(&claim.id).unwrap_or_default();
+                ClaimMatchStatus {
+                    claim,
+                    is_matched: !matched_identifications.is_empty(),
+                    matched_identifications,
+                }
+            })
+            .collect())
     }
 
     /// Matches Identifications that would belong to a claim.
@@ -685,19 +1238,161 @@ impl ClaimableIdentification {
     /// # Returns
     /// bool: If a match of the claim is found, otherwise false.
     pub async fn match_idt(&self) -> Result<(bool, Vec<Identification>), ResError> {
-        use crate::diesel_cfg::schema::identifications::dsl::{
-            identifications, institution_id, is_found,
+        if !self.status.is_active() {
+            return Ok((false, vec![]));
+        }
+        let idts = self.missing_idts()?;
+        let checksum = RegistrationFormat::checksum_of(self.institution_id)?;
+
+        self.find_similarity(idts, checksum).await
+    }
+
+    /// Recomputes the matches of this claim.
+    ///
+    /// Missing Identifications that now match the claim are recorded,
+    /// and recorded matches that no longer qualify are removed.
+    /// A matched claim left without matches is reopened.
+    ///
+    /// Only matches of Identifications still missing and without an
+    /// owner are removed. Matches of found or handed over
+    /// Identifications are kept, as ownership requests rely on them.
+    ///
+    /// Inactive claims keep their matches, and aren't matched.
+    ///
+    /// # Returns
+    /// (Newly matched Identifications, Number of removed matches)
+    pub async fn rematch(&self) -> Result<(Vec<Identification>, usize), ResError> {
+        use crate::diesel_cfg::schema::{
+            identifications,
+            matched_identifications::dsl::{claim_id, identification_id, matched_identifications},
         };
 
-        let idts = identifications
+        if !self.status.is_active() {
+            return Ok((vec![], 0));
+        }
+
+        let mut qualifying = vec![];
+        let mut new_matches = vec![];
+        let checksum = RegistrationFormat::checksum_of(self.institution_id)?;
+
+        for idt in self.missing_idts()? {
+            if Self::is_matching_idt(self, &idt, checksum).await {
+                qualifying.push(idt.id);
+
+                if MatchedIDt::save(self, &idt).await? > 0 {
+                    new_matches.push(idt);
+                }
+            }
+        }
+
+        let candidates = identifications::table
             .filter(
-                institution_id
-                    .eq(&self.institution_id)
-                    .and(is_found.eq(false)),
+                identifications::is_found
+                    .eq(false)
+                    .and(identifications::owner.is_null()),
             )
-            .load::<Identification>(&connect_to_db())?;
+            .select(identifications::id);
+        let pruned = diesel::delete(
+            matched_identifications.filter(
+                claim_id
+                    .eq(self.id)
+                    .and(identification_id.ne_all(qualifying))
+                    .and(identification_id.eq_any(candidates)),
+            ),
+        )
+        .execute(&connect_to_db())?;
+        lifecycle::reopen_if_unmatched(self.id)?;
+
+        Ok((new_matches, pruned))
+    }
+
+    /// Retrieves the Claims of an institution
+    pub fn retrieve_by_institution_id(institution_pk: i32) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::claimed_identifications::dsl::{
+            claimed_identifications, institution_id,
+        };
+
+        Ok(claimed_identifications
+            .filter(institution_id.eq(institution_pk))
+            .load::<Self>(&connect_to_db())?)
+    }
 
-        Ok(self.find_similarity(idts).await?)
+    /// Blocking keys of the Claim
+    pub fn block_keys(&self) -> Vec<String> {
+        blocking::block_keys(
+            &self.name,
+            self.registration_no_canonical.as_deref(),
+            self.graduation_year,
+        )
+    }
+
+    /// Replaces the stored blocking keys of the Claim
+    pub fn refresh_blocks(&self) -> Result<(), ResError> {
+        let conn = connect_to_db();
+        conn.transaction(|| self.refresh_blocks_on(&conn))?;
+        Ok(())
+    }
+
+    /// Replaces the stored blocking keys of the Claim on
+    /// the connection saving it
+    pub fn refresh_blocks_on(&self, conn: &PgConnection) -> QueryResult<()> {
+        blocking::save_claim_blocks(conn, self.id, self.block_keys())
+    }
+
+    /// Recomputes the canonical registration number, saving
+    /// it on the given connection if it changed.
+    pub fn normalize_registration_no(self, conn: &PgConnection) -> Result<Self, ResError> {
+        let formats = match self.institution_id {
+            Some(inst) => RegistrationFormat::for_institution(inst)?,
+            None => vec![],
+        };
+        Ok(self.normalize_registration_no_under(conn, &formats)?.0)
+    }
+
+    /// Recomputes the canonical registration number under the
+    /// formats of the institution, saving it on the given
+    /// connection if it changed.
+    ///
+    /// # Returns
+    /// The Claim, and whether its canonical number changed
+    pub fn normalize_registration_no_under(
+        mut self,
+        conn: &PgConnection,
+        formats: &[RegistrationFormat],
+    ) -> QueryResult<(Self, bool)> {
+        let canonical = registration::canonical_under(self.registration_no.as_deref(), formats);
+
+        if canonical == self.registration_no_canonical {
+            return Ok((self, false));
+        }
+        self.registration_no_canonical = canonical;
+        Ok((self.save_changes::<Self>(conn)?, true))
+    }
+
+    /// Retrieves the missing Identifications the claim
+    /// could be matched against.
+    ///
+    /// These are non-found Identifications of the claim's document type,
+    /// sharing a blocking key with the claim. Institution-issued documents
+    /// are of the claim's institution or of none. A claim without an
+    /// institution is compared to Identifications of all institutions.
+    fn missing_idts(&self) -> Result<Vec<Identification>, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::{
+            document_type, id, identifications, institution_id, is_found,
+        };
+
+        let candidates = blocking::identifications_in_blocks(&self.block_keys())?;
+        let mut query = identifications
+            .filter(
+                id.eq_any(candidates)
+                    .and(is_found.eq(false))
+                    .and(document_type.eq(self.document_type)),
+            )
+            .into_boxed();
+        if let (true, Some(inst)) = (self.document_type.is_institutional(), self.institution_id) {
+            query = query.filter(institution_id.eq(inst).or(institution_id.is_null()));
+        }
+        Ok(query.load::<Identification>(&connect_to_db())?)
     }
 
     /// Compares the fields of a claim to given Identifications to ascertain
@@ -705,17 +1400,22 @@ impl ClaimableIdentification {
     ///
     /// The similarity metric is cosine distance of the Identification and the
     /// Claim fields
+    ///
+    /// Only Identifications that were not matched to the claim before
+    /// are returned.
     async fn find_similarity(
         &self,
         idents: Vec<Identification>,
-    ) -> Result<(bool, Vec<Identification>), diesel::result::Error> {
+        checksum: Option<Checksum>,
+    ) -> Result<(bool, Vec<Identification>), ResError> {
         let mut is_matched = false;
         let mut matched_idts = vec![];
 
         for idt in idents.into_iter() {
-            if Self::is_matching_idt(self, &idt).await {
+            if Self::is_matching_idt(self, &idt, checksum).await
+                && MatchedIDt::save(self, &idt).await? > 0
+            {
                 is_matched = true;
-                MatchedIDt::save(self, &idt).await?;
                 matched_idts.push(idt);
             }
         }
@@ -726,7 +1426,20 @@ impl ClaimableIdentification {
     /// returning true if they match.
     ///
     /// Cosine threshold: .90
-    async fn is_matching_idt(claim: &ClaimableIdentification, idt: &Identification) -> bool {
+    ///
+    /// Only the matching fields of the document type are compared.
+    /// Pairs not known to be of the same institution, and documents not
+    /// issued by institutions, are held to the stricter
+    /// `CROSS_INSTITUTION_THRESHOLD`.
+    ///
+    /// Registration numbers are compared in their canonical form, a
+    /// close but unequal number getting partial credit where it is
+    /// evidently a misreading, see `registration_credit`.
+    async fn is_matching_idt(
+        claim: &ClaimableIdentification,
+        idt: &Identification,
+        checksum: Option<Checksum>,
+    ) -> bool {
         // Use cosine similarity here
         // name, course,
         // valid-from, valid-till
@@ -735,8 +1448,11 @@ impl ClaimableIdentification {
 
         let mut overall_significance: f64 = 0.0;
 
-        // Min threshold from matching name only -> .90*.6
-        let min_threshold = 0.54;
+        let doc_type = claim.document_type;
+        let min_threshold = match (claim.institution_id, idt.institution_id) {
+            (Some(a), Some(b)) if a == b && doc_type.is_institutional() => MATCH_THRESHOLD,
+            _ => CROSS_INSTITUTION_THRESHOLD,
+        };
 
         let nm_sig: f64 = 0.6; // Cosine of 1 contributes .60
         let crse_sig: f64 = 0.25; // Cosine of 1 contributes .25
@@ -745,60 +1461,99 @@ impl ClaimableIdentification {
                                    // Was added later. But if registration
                                    // number matches, it makes sense
                                    // to assume a full match.
+                                   // Near matches contribute a share of it,
+                                   // if evidently misread
 
         let name_s = cosine_similarity(claim.name.as_ref(), &idt.name);
 
-        let course_s = cosine_similarity(claim.course.as_ref(), &idt.course);
+        let (claim_course, idt_course) = match (&claim.course, &idt.course) {
+            (Some(c), Some(i)) if doc_type.matches_on(Field::Course) => (c.as_str(), i.as_str()),
+            _ => ("", ""),
+        };
+        let course_s = cosine_similarity(claim_course, idt_course);
 
         let (name_s, course_s) = join(name_s, course_s).await;
 
         overall_significance += name_s * nm_sig;
         overall_significance += course_s * crse_sig;
 
-        if idt.valid_from.is_some()
+        if doc_type.matches_on(Field::ValidFrom)
+            && idt.valid_from.is_some()
             && claim.entry_year.is_some()
             && claim.entry_year.unwrap().eq(&idt.valid_from.unwrap())
         {
             overall_significance += tm_sig / 2.;
         }
-        if idt.valid_till.is_some()
+        if doc_type.matches_on(Field::ValidTill)
+            && idt.valid_till.is_some()
             && claim.graduation_year.is_some()
             && claim.graduation_year.unwrap().eq(&idt.valid_till.unwrap())
         {
             overall_significance += tm_sig / 2.;
         }
 
-        if idt.registration_no.is_some()
-            && claim.registration_no.is_some()
-            && claim
-                .registration_no
-                .as_ref()
-                .unwrap()
-                .eq(idt.registration_no.as_ref().unwrap())
-        {
-            overall_significance += regno_sig;
+        if let (true, Some(claim_reg_no), Some(idt_reg_no)) = (
+            doc_type.matches_on(Field::RegistrationNo),
+            claim.registration_no_canonical.as_ref(),
+            idt.registration_no_canonical.as_ref(),
+        ) {
+            overall_significance += regno_sig
+                * registration::registration_credit(claim_reg_no, idt_reg_no, checksum, name_s);
         }
         overall_significance >= min_threshold
     }
 }
 
 impl MatchedIDt {
+    /// Retrieves the matches of the given claims
+    fn for_claims(claim_ids: &[i32]) -> Result<Vec<Self>, diesel::result::Error> {
+        use crate::diesel_cfg::schema::matched_identifications::dsl::{
+            claim_id, matched_identifications,
+        };
+
+        matched_identifications
+            .filter(claim_id.eq_any(claim_ids))
+            .load::<Self>(&connect_to_db())
+    }
+
     /// Inserts a new Identification/Claim match into the Matches
     /// table.
+    ///
+    /// An open claim becomes matched.
+    ///
+    /// # Returns
+    /// The number of inserted rows. `0` if the pair was matched before.
     pub async fn save(
         claim: &ClaimableIdentification,
         idt: &Identification,
-    ) -> Result<usize, diesel::result::Error> {
+    ) -> Result<usize, ResError> {
         use crate::diesel_cfg::schema::matched_identifications::dsl::*;
         use diesel::pg::upsert::on_constraint;
 
         // unique (claim_id, identification_id)
         // Ignore this, it's bound to happen
-        Ok(diesel::insert_into(matched_identifications)
+        let inserted = diesel::insert_into(matched_identifications)
             .values(&(claim_id.eq(claim.id), identification_id.eq(idt.id)))
             .on_conflict(on_constraint("matched_claim_id_unique"))
             .do_nothing()
-            .execute(&connect_to_db())?)
+            .execute(&connect_to_db())?;
+
+        if inserted > 0 {
+            lifecycle::mark_matched(claim.id)?;
+            Notification::notify(
+                &[claim.user_id],
+                EventKind::MatchRecorded,
+                "Your identification may have been found",
+                &format!("An identification of {} matches your claim", idt.name),
+                json!({"claim_id": claim.id, "identification_id": idt.id}),
+            );
+
+            WebhookEvent::ClaimMatched.publish(
+                idt.institution_id,
+                json!({"identification": idt.webhook_data(), "claim_id": claim.id}),
+            );
+        }
+        Ok(inserted)
     }
 }
 
@@ -812,11 +1567,13 @@ impl std::convert::From<&NewIdentification<'_>> for Identification {
     fn from(new_idt: &NewIdentification<'_>) -> Self {
         Identification {
             name: new_idt.name.as_ref().into(),
-            course: new_idt.course.as_ref().into(),
+            course: new_idt.course.as_ref().map(|c| c.to_string()),
             valid_from: new_idt.valid_from,
             valid_till: new_idt.valid_till,
             institution_id: new_idt.institution_id,
             registration_no: new_idt.registration_no.clone(),
+            registration_no_canonical: new_idt.registration_no_canonical.clone(),
+            document_type: new_idt.document_type,
 
             // Below fields should NOT be used on an Idt converted from a NewIdt
             id: 0,
@@ -830,6 +1587,7 @@ impl std::convert::From<&NewIdentification<'_>> for Identification {
             is_found: false,
             about: None,
             owner: None,
+            pickup_point_id: new_idt.pickup_point_id,
         }
     }
 }
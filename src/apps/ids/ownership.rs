@@ -0,0 +1,360 @@
+//! Ownership requests
+//!
+//! A claimant requests the ownership of an Identification matching
+//! their claim. The request is decided by whoever holds the
+//! Identification: the user who posted it, or a custodian or admin
+//! of its institution. Moderators stand in as custodians of every
+//! institution.
+//!
+//! Ownership is only transferred once a request is approved. The
+//! holder then hands the Identification over, fulfilling the claim.
+
+use super::{
+    lifecycle::ClaimStatus,
+    models::{ClaimableIdentification, Identification, MatchedIdtJson},
+};
+use crate::{
+    apps::{
+        events::models::EventKind,
+        institution::{
+            memberships::{InstitutionRole, Membership},
+            pickup::PickupPoint,
+        },
+        jobs::models::JobKind,
+        notifications::models::Notification,
+        user::models::User,
+        user::utils::from_timestamp,
+    },
+    diesel_cfg::{config::connect_to_db, schema::ownership_requests},
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{self, prelude::*, sql_types::Varchar, PgConnection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator_derive::Validate;
+
+use std::borrow::Cow;
+
+/// Status of an ownership request
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum OwnershipStatus {
+    /// Awaiting a decision
+    Pending,
+    /// The claimant owns the Identification, and awaits its handover
+    Approved,
+    Rejected,
+}
+
+varchar_enum!(OwnershipStatus {
+    Pending => "pending",
+    Approved => "approved",
+    Rejected => "rejected"
+});
+
+/// Queryable model of the ownership_requests table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "ownership_requests"]
+pub struct OwnershipRequest {
+    pub id: i32,
+    pub claim_id: i32,
+    pub identification_id: i32,
+
+    /// The claimant
+    pub requested_by: i32,
+    pub status: OwnershipStatus,
+
+    /// Why the request was decided as it was
+    pub reason: Option<String>,
+    pub decided_by: Option<i32>,
+
+    pub decided_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// Insertable ownership request
+#[derive(Insertable)]
+#[table_name = "ownership_requests"]
+struct NewOwnershipRequest {
+    claim_id: i32,
+    identification_id: i32,
+    requested_by: i32,
+}
+
+/// Decision on an ownership request
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct OwnershipDecision<'a> {
+    /// Required of rejections
+    #[validate(length(min = 1, max = 500, message = "should be 1 to 500 letters long"))]
+    pub reason: Option<Cow<'a, str>>,
+}
+
+/// Status filter of ownership request listings
+#[derive(Deserialize)]
+pub struct OwnershipRequestQuery {
+    pub status: Option<OwnershipStatus>,
+}
+
+impl OwnershipRequest {
+    /// Requests the ownership of the Identification matching a
+    /// claim of the user.
+    ///
+    /// The holder of the Identification is notified in the background.
+    pub fn create(data: &MatchedIdtJson, usr: &User) -> Result<Self, ResError> {
+        let (idt, claim) = Identification::search_matching_claim(data, usr)?;
+
+        let request = diesel::insert_into(ownership_requests::table)
+            .values(&NewOwnershipRequest {
+                claim_id: claim.id,
+                identification_id: idt.id,
+                requested_by: usr.id,
+            })
+            .on_conflict_do_nothing()
+            .get_result::<Self>(&connect_to_db())
+            .optional()?
+            .ok_or_else(|| {
+                ResError::new("You have already requested this identification".into(), 409)
+            })?;
+
+        if let Some(poster) = idt.posted_by {
+            Notification::notify(
+                &[poster],
+                EventKind::OwnershipRequested,
+                "Ownership requested",
+                &format!(
+                    "{} asked for the identification of {} you posted",
+                    usr.username, idt.name
+                ),
+                request.to_json(),
+            );
+        }
+        JobKind::SendOwnershipNotice {
+            request_id: request.id,
+        }
+        .enqueue()?;
+        Ok(request)
+    }
+
+    /// Finds an ownership request by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::ownership_requests::dsl::ownership_requests;
+
+        Ok(ownership_requests
+            .find(pk)
+            .first::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the requests on Identifications posted by the user,
+    /// newest first
+    pub fn received_by(
+        usr: &User,
+        request_status: Option<OwnershipStatus>,
+    ) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::identifications;
+
+        let mut query = ownership_requests::table
+            .inner_join(identifications::table)
+            .filter(identifications::posted_by.eq(usr.id))
+            .select(ownership_requests::all_columns)
+            .into_boxed();
+        if let Some(request_status) = request_status {
+            query = query.filter(ownership_requests::status.eq(request_status));
+        }
+
+        Ok(query
+            .order(ownership_requests::created_at.desc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Checks that the user may decide requests on the Identification
+    fn can_decide(usr: &User, idt: &Identification) -> Result<bool, ResError> {
+        Ok(idt.posted_by == Some(usr.id)
+            || Membership::holds(usr, idt.institution_id, InstitutionRole::Custodian)?)
+    }
+
+    /// Makes the claimant the owner of the Identification, verifying
+    /// their claim.
+    ///
+    /// Other pending requests on the Identification are rejected.
+    /// The decisions and the transfer are saved in one transaction,
+    /// so a transfer that fails leaves every request pending. Both
+    /// parties are notified in the background.
+    pub fn approve(&self, usr: &User, data: &OwnershipDecision) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::ownership_requests::dsl::{
+            decided_at, decided_by, id, identification_id, ownership_requests, reason, status,
+        };
+
+        let idt = Identification::find_by_id(self.identification_id)?;
+        if !Self::can_decide(usr, &idt)? {
+            return Err(ResError::unauthorized());
+        }
+        if self.requested_by == usr.id {
+            return Err(ResError::new(
+                "You can't decide your own request".into(),
+                403,
+            ));
+        }
+        let claim = ClaimableIdentification::find_by_id(self.claim_id)?;
+
+        let conn = connect_to_db();
+        let (approved, rejected, idt) = conn.transaction::<_, ResError, _>(|| {
+            let approved = self.decide_on(
+                &conn,
+                usr,
+                OwnershipStatus::Approved,
+                data.reason.as_deref(),
+            )?;
+            let idt = idt.transfer_on(&conn, &claim)?;
+
+            let rejected = diesel::update(
+                ownership_requests.filter(
+                    identification_id
+                        .eq(self.identification_id)
+                        .and(status.eq(OwnershipStatus::Pending))
+                        .and(id.ne(self.id)),
+                ),
+            )
+            .set((
+                status.eq(OwnershipStatus::Rejected),
+                reason.eq("The identification was handed over to another claimant"),
+                decided_by.eq(usr.id),
+                decided_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_results::<Self>(&conn)?;
+
+            for request in std::iter::once(&approved).chain(&rejected) {
+                JobKind::SendOwnershipNotice {
+                    request_id: request.id,
+                }
+                .enqueue_on(&conn)?;
+            }
+            Ok((approved, rejected, idt))
+        })?;
+
+        for request in std::iter::once(&approved).chain(&rejected) {
+            request.announce(usr, &idt);
+        }
+        Ok(approved)
+    }
+
+    /// Records the handover of the Identification of an approved
+    /// request to the claimant, fulfilling their claim.
+    ///
+    /// Both parties are notified.
+    pub fn hand_over(&self, usr: &User) -> Result<ClaimableIdentification, ResError> {
+        let idt = Identification::find_by_id(self.identification_id)?;
+        if !Self::can_decide(usr, &idt)? {
+            return Err(ResError::unauthorized());
+        }
+        if self.status != OwnershipStatus::Approved {
+            return Err(ResError::new(
+                format!("A {} request can't be handed over", self.status),
+                409,
+            ));
+        }
+
+        let claim = ClaimableIdentification::find_by_id(self.claim_id)?
+            .transition(ClaimStatus::Fulfilled)?;
+        idt.announce_transfer(&claim);
+        Ok(claim)
+    }
+
+    /// Turns the request down.
+    ///
+    /// Both parties are notified in the background.
+    pub fn reject(&self, usr: &User, data: &OwnershipDecision) -> Result<Self, ResError> {
+        let idt = Identification::find_by_id(self.identification_id)?;
+        if !Self::can_decide(usr, &idt)? {
+            return Err(ResError::unauthorized());
+        }
+        let reason = data
+            .reason
+            .as_deref()
+            .ok_or_else(|| ResError::new("Give a reason for the rejection".into(), 400))?;
+
+        let conn = connect_to_db();
+        let rejected = conn.transaction::<_, ResError, _>(|| {
+            let rejected = self.decide_on(&conn, usr, OwnershipStatus::Rejected, Some(reason))?;
+            JobKind::SendOwnershipNotice {
+                request_id: rejected.id,
+            }
+            .enqueue_on(&conn)?;
+            Ok(rejected)
+        })?;
+        rejected.announce(usr, &idt);
+        Ok(rejected)
+    }
+
+    /// Records the decision on a pending request
+    fn decide_on(
+        &self,
+        conn: &PgConnection,
+        usr: &User,
+        decision: OwnershipStatus,
+        why: Option<&str>,
+    ) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::ownership_requests::dsl::*;
+
+        diesel::update(
+            ownership_requests.filter(id.eq(self.id).and(status.eq(OwnershipStatus::Pending))),
+        )
+        .set((
+            status.eq(decision),
+            reason.eq(why),
+            decided_by.eq(usr.id),
+            decided_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result::<Self>(conn)
+        .optional()?
+        .ok_or_else(|| ResError::new("The request was already decided".into(), 409))
+    }
+
+    /// Tells the claimant of the decision, and the poster of
+    /// the Identification too if someone else decided.
+    ///
+    /// The decision is already saved, so a pickup point that can't
+    /// be described is logged and left out of the notification.
+    fn announce(&self, usr: &User, idt: &Identification) {
+        let mut body = format!(
+            "The request for the identification of {} was {}",
+            idt.name, self.status
+        );
+        if let (OwnershipStatus::Approved, Some(point_id)) = (self.status, idt.pickup_point_id) {
+            match PickupPoint::find_by_id(point_id).and_then(|point| point.directions_to()) {
+                Ok(directions) => body.push_str(&format!(". Collect it at {}", directions)),
+                Err(e) => error!(
+                    "Ownership request {}: Error describing pickup point {}: {}",
+                    self.id, point_id, e.msg
+                ),
+            }
+        }
+
+        let mut parties = vec![self.requested_by];
+        parties.extend(idt.posted_by.filter(|&poster| poster != usr.id));
+        Notification::notify(
+            &parties,
+            EventKind::OwnershipDecided,
+            &format!("Ownership request {}", self.status),
+            &body,
+            self.to_json(),
+        );
+    }
+
+    /// Ids of the records involved in the request
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "request_id": self.id,
+            "claim_id": self.claim_id,
+            "identification_id": self.identification_id,
+        })
+    }
+}
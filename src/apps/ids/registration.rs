@@ -0,0 +1,457 @@
+//! Registration number formats and normalization
+//!
+//! Registration numbers are written in many ways, e.g
+//! "SCT221-0123/2018" and "sct 221 0123 2018". Each is reduced to a
+//! canonical form on save, which matching and search compare instead.
+//!
+//! Institutions may define the formats of their registration numbers.
+//! Numbers not matching any format of the institution take the default
+//! canonical form: their uppercased alphanumerics.
+
+use super::models::{ClaimableIdentification, Identification};
+use crate::{
+    apps::jobs::models::JobKind,
+    apps::user::utils::from_timestamp,
+    diesel_cfg::{config::connect_to_db, schema::registration_formats},
+    errors::error::ResError,
+    similarity::levenshtein::levenshtn,
+    varchar_enum,
+};
+
+use chrono::NaiveDateTime;
+use diesel::{self, prelude::*, sql_types::Varchar};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+use validator_derive::Validate;
+
+use std::{borrow::Cow, collections::HashMap, sync::Mutex};
+
+/// Share of differing characters past which two registration
+/// numbers get no credit
+const MAX_REG_NO_DISTANCE: f64 = 0.5;
+
+/// Least name similarity for a near-miss registration
+/// number to get credit, where no check digit shows it misread.
+///
+/// A name this similar alone falls short of a same-institution
+/// match, so the credit decides it.
+const NAME_AGREEMENT: f64 = 0.8;
+
+/// Characters of a masked registration number left shown
+const UNMASKED_CHARS: usize = 3;
+
+lazy_static! {
+    /// Compiled format patterns, by pattern
+    static ref PATTERNS: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+}
+
+/// Check digit schemes of registration numbers
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum Checksum {
+    /// The last digit is a Luhn (mod 10) check digit
+    Luhn,
+}
+
+varchar_enum!(Checksum { Luhn => "luhn" });
+
+/// A registration number format of an institution
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "registration_formats"]
+pub struct RegistrationFormat {
+    pub id: i32,
+    pub institution_id: i32,
+
+    /// Regex matched against the trimmed, uppercased number
+    pub pattern: String,
+
+    /// Replacement template of the canonical form,
+    /// referring to groups of `pattern`
+    pub canonical: String,
+
+    /// Check digit scheme of numbers of this format
+    pub checksum: Option<Checksum>,
+    pub description: Option<String>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// Insertable registration number format
+#[derive(Insertable, Deserialize, Validate)]
+#[table_name = "registration_formats"]
+#[serde(deny_unknown_fields)]
+pub struct NewRegistrationFormat<'a> {
+    #[serde(skip_deserializing)]
+    pub institution_id: i32,
+
+    #[validate(custom = "validate_pattern")]
+    pub pattern: Cow<'a, str>,
+
+    #[validate(length(min = 1, message = "should not be empty"))]
+    pub canonical: Cow<'a, str>,
+
+    pub checksum: Option<Checksum>,
+    pub description: Option<Cow<'a, str>>,
+}
+
+/// Registration number search query
+#[derive(Deserialize)]
+pub struct RegistrationSearch {
+    pub registration_no: String,
+    pub institution_id: Option<i32>,
+}
+
+/// Ensures a format pattern is a valid regex
+fn validate_pattern(pattern: &str) -> Result<(), ValidationError> {
+    if Regex::new(pattern).is_err() {
+        return Err(ValidationError::new("should be a valid regular expression"));
+    }
+    Ok(())
+}
+
+impl<'a> NewRegistrationFormat<'a> {
+    /// Saves the format to the registration_formats table
+    pub fn save(&self) -> Result<RegistrationFormat, ResError> {
+        Ok(diesel::insert_into(registration_formats::table)
+            .values(self)
+            .get_result::<RegistrationFormat>(&connect_to_db())?)
+    }
+}
+
+impl RegistrationFormat {
+    /// Retrieves the registration number formats of an institution
+    pub fn for_institution(inst: i32) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::registration_formats::dsl::{
+            id, institution_id, registration_formats,
+        };
+
+        Ok(registration_formats
+            .filter(institution_id.eq(inst))
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Recomputes the canonical registration numbers of the
+    /// Identifications and Claims of an institution.
+    ///
+    /// Run in the background once its formats change. Records whose
+    /// canonical number changed are matched again.
+    ///
+    /// # Returns
+    /// The number of records checked
+    pub fn renormalize_institution(inst: i32) -> Result<usize, ResError> {
+        let formats = Self::for_institution(inst)?;
+        let mut count = 0;
+
+        let conn = connect_to_db();
+        for idt in Identification::retrieve_by_institution_id(inst, None)? {
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                let (idt, changed) = idt.normalize_registration_no_under(&conn, &formats)?;
+                if changed {
+                    idt.refresh_blocks_on(&conn)?;
+                    JobKind::MatchId {
+                        identification_id: idt.id,
+                    }
+                    .enqueue_on(&conn)?;
+                }
+                Ok(())
+            })?;
+            count += 1;
+        }
+        for claim in ClaimableIdentification::retrieve_by_institution_id(inst)? {
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                let (claim, changed) = claim.normalize_registration_no_under(&conn, &formats)?;
+                if changed {
+                    claim.refresh_blocks_on(&conn)?;
+                    JobKind::MatchClaim { claim_id: claim.id }.enqueue_on(&conn)?;
+                }
+                Ok(())
+            })?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Retrieves the formats of all institutions
+    pub fn retrieve_all() -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::registration_formats::dsl::registration_formats;
+
+        Ok(registration_formats.load::<Self>(&connect_to_db())?)
+    }
+
+    /// The canonical form of `reg_no` under this format.
+    ///
+    /// # Returns
+    /// None if the number isn't of this format
+    pub fn apply(&self, reg_no: &str) -> Option<String> {
+        let re = compiled(&self.pattern)?;
+        let reg_no = reg_no.trim().to_uppercase();
+
+        if re.is_match(&reg_no) {
+            Some(re.replace(&reg_no, self.canonical.as_str()).into_owned())
+        } else {
+            None
+        }
+    }
+
+    /// The check digit scheme of an institution's numbers, if any
+    pub fn checksum_of(inst: Option<i32>) -> Result<Option<Checksum>, ResError> {
+        match inst {
+            Some(inst) => Ok(Self::for_institution(inst)?
+                .into_iter()
+                .find_map(|f| f.checksum)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The regex of a format pattern, compiled once per pattern
+fn compiled(pattern: &str) -> Option<Regex> {
+    let mut patterns = PATTERNS.lock().unwrap();
+    if let Some(re) = patterns.get(pattern) {
+        return Some(re.clone());
+    }
+
+    let re = Regex::new(pattern).ok()?;
+    patterns.insert(pattern.to_string(), re.clone());
+    Some(re)
+}
+
+/// The default canonical form of a registration number
+///
+/// ```rust
+/// use got_ya_id::apps::ids::registration::default_canonical;
+///
+/// assert_eq!(default_canonical("sct 221 0123 2018"), "SCT22101232018");
+/// assert_eq!(default_canonical("SCT221-0123/2018"), "SCT22101232018");
+/// ```
+pub fn default_canonical(reg_no: &str) -> String {
+    reg_no
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Hides all but the last characters of a registration number.
+///
+/// Numbers too short to leave any shown are hidden whole.
+///
+/// ```rust
+/// use got_ya_id::apps::ids::registration::mask_registration_no;
+///
+/// assert_eq!(mask_registration_no("A1234567"), "*****567");
+/// assert_eq!(mask_registration_no("123"), "***");
+/// ```
+pub fn mask_registration_no(reg_no: &str) -> String {
+    let len = reg_no.chars().count();
+    let hidden = if len > UNMASKED_CHARS {
+        len - UNMASKED_CHARS
+    } else {
+        len
+    };
+
+    reg_no
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < hidden { '*' } else { c })
+        .collect()
+}
+
+/// Reduces a registration number to its canonical form under
+/// the first matching format.
+pub fn canonicalize(reg_no: &str, formats: &[RegistrationFormat]) -> String {
+    formats
+        .iter()
+        .find_map(|f| f.apply(reg_no))
+        .unwrap_or_else(|| default_canonical(reg_no))
+}
+
+/// The canonical form of a registration number of an institution
+///
+/// # Returns
+/// None for a missing or blank registration number
+pub fn canonical_registration_no(
+    reg_no: Option<&str>,
+    institution: Option<i32>,
+) -> Result<Option<String>, ResError> {
+    let reg_no = match reg_no {
+        Some(r) if !default_canonical(r).is_empty() => r,
+        _ => return Ok(None),
+    };
+
+    let formats = match institution {
+        Some(inst) => RegistrationFormat::for_institution(inst)?,
+        None => vec![],
+    };
+    Ok(canonical_under(Some(reg_no), &formats))
+}
+
+/// The canonical form of a registration number under the given
+/// formats of its institution
+///
+/// # Returns
+/// None for a missing or blank registration number
+pub fn canonical_under(reg_no: Option<&str>, formats: &[RegistrationFormat]) -> Option<String> {
+    match reg_no {
+        Some(r) if !default_canonical(r).is_empty() => Some(canonicalize(r, formats)),
+        _ => None,
+    }
+}
+
+/// The possible canonical forms of a searched registration number.
+///
+/// With no institution given, the number could be of any
+/// institution's format.
+pub fn search_forms(reg_no: &str, institution: Option<i32>) -> Result<Vec<String>, ResError> {
+    if institution.is_some() {
+        return Ok(canonical_registration_no(Some(reg_no), institution)?
+            .into_iter()
+            .collect());
+    }
+
+    let mut forms = vec![default_canonical(reg_no)];
+    for format in RegistrationFormat::retrieve_all()? {
+        if let Some(form) = format.apply(reg_no) {
+            if !forms.contains(&form) {
+                forms.push(form);
+            }
+        }
+    }
+    Ok(forms)
+}
+
+/// Checks the Luhn check digit of the digits of `reg_no`
+///
+/// ```rust
+/// use got_ya_id::apps::ids::registration::luhn_valid;
+///
+/// assert!(luhn_valid("79927398713"));
+/// assert!(!luhn_valid("79927398710"));
+/// ```
+pub fn luhn_valid(reg_no: &str) -> bool {
+    let digits = reg_no
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect::<Vec<u32>>();
+    if digits.len() < 2 {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Similarity of two canonical registration numbers, from 0 to 1.
+///
+/// Identical numbers score 1. Others are credited by their edit
+/// distance, nothing past `MAX_REG_NO_DISTANCE`, so a misread digit
+/// still counts towards a match.
+///
+/// Where the numbers carry a check digit and both are valid, they
+/// are taken to be two different numbers, rather than a misreading
+/// of one, and get no credit.
+///
+/// ```rust
+/// use got_ya_id::apps::ids::registration::{registration_similarity, Checksum};
+///
+/// assert_eq!(registration_similarity("SCT221-0123/2018", "SCT22101232018", None), 1.);
+///
+/// let misread = registration_similarity("SCT22101232018", "SCT22101282018", None);
+/// assert!(misread > 0.8 && misread < 1.);
+///
+/// assert_eq!(registration_similarity("SCT22101232018", "ENG10000002020", None), 0.);
+///
+/// // Both carry valid Luhn check digits
+/// assert_eq!(registration_similarity("79927398713", "79927398721", Some(Checksum::Luhn)), 0.);
+/// ```
+pub fn registration_similarity(a: &str, b: &str, checksum: Option<Checksum>) -> f64 {
+    let a = default_canonical(a);
+    let b = default_canonical(b);
+
+    if a.is_empty() || b.is_empty() {
+        return 0.;
+    }
+    if a == b {
+        return 1.;
+    }
+
+    if let Some(Checksum::Luhn) = checksum {
+        if luhn_valid(&a) && luhn_valid(&b) {
+            return 0.;
+        }
+    }
+
+    let len = a.chars().count().max(b.chars().count()) as f64;
+    let distance = levenshtn(&a, &b) as f64 / len;
+
+    ((MAX_REG_NO_DISTANCE - distance) / MAX_REG_NO_DISTANCE).max(0.)
+}
+
+/// Credit of two canonical registration numbers towards a match,
+/// from 0 to 1, given the similarity of the names they are under.
+///
+/// Identical numbers get full credit. A near-miss is credited where
+/// it is evidently a misreading of one number: under a check digit
+/// scheme, one number valid and the other not. Without such evidence
+/// it is as likely the number of a classmate, issued next to it, so
+/// only a single differing character is credited, and only where the
+/// names agree.
+///
+/// ```rust
+/// use got_ya_id::apps::ids::registration::{registration_credit, Checksum};
+///
+/// assert_eq!(registration_credit("SCT221-0123/2018", "SCT22101232018", None, 0.2), 1.);
+///
+/// // Classmates with adjacent numbers
+/// assert_eq!(registration_credit("SCT221-0123/2018", "SCT221-0124/2018", None, 0.4), 0.);
+///
+/// // A misread digit of the same owner's number, e.g "Wanjiru Kamau"
+/// // written as "W. Kamau"
+/// let misread = registration_credit("SCT221-0123/2018", "SCT221-0128/2018", None, 0.87);
+/// assert!(misread > 0.8 && misread < 1.);
+///
+/// // Only the first carries a valid Luhn check digit
+/// let misread = registration_credit("79927398713", "79927398718", Some(Checksum::Luhn), 0.4);
+/// assert!(misread > 0.8 && misread < 1.);
+/// ```
+pub fn registration_credit(
+    a: &str,
+    b: &str,
+    checksum: Option<Checksum>,
+    name_similarity: f64,
+) -> f64 {
+    let similarity = registration_similarity(a, b, checksum);
+    if similarity == 0. || similarity == 1. {
+        return similarity;
+    }
+
+    let (a, b) = (default_canonical(a), default_canonical(b));
+    let checked = checksum == Some(Checksum::Luhn) && luhn_valid(&a) != luhn_valid(&b);
+    if checked || (levenshtn(&a, &b) == 1 && name_similarity >= NAME_AGREEMENT) {
+        similarity
+    } else {
+        0.
+    }
+}
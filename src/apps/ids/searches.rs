@@ -0,0 +1,303 @@
+//! Saved searches
+//!
+//! Users who haven't filed a claim save a search by a fragment of the
+//! name on an Identification, its institution, or an area around a
+//! point. Each new Identification is checked against the saved
+//! searches, and their owners are alerted through notifications.
+//!
+//! An Identification alerts of a search only once, however often
+//! it's matched.
+
+use super::models::Identification;
+use crate::{
+    apps::{
+        events::models::EventKind, notifications::models::Notification, user::models::User,
+        user::utils::from_timestamp,
+    },
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{saved_search_alerts, saved_searches},
+    },
+    errors::error::ResError,
+};
+
+use chrono::NaiveDateTime;
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator_derive::Validate;
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Number of searches a user may save
+const SAVED_SEARCH_LIMIT: i64 = 10;
+
+/// Largest radius of a search area, in kilometres
+const MAX_RADIUS_KM: f64 = 100.0;
+
+/// Mean radius of the earth, in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Queryable model of the saved_searches table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "saved_searches"]
+pub struct SavedSearch {
+    pub id: i32,
+    pub user_id: i32,
+    pub label: String,
+
+    /// Part of the name on the Identification
+    pub name_fragment: Option<String>,
+    pub institution_id: Option<i32>,
+
+    /// Centre of the search area
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub radius_km: Option<f64>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// A search to save
+#[derive(Insertable, Deserialize, Validate)]
+#[table_name = "saved_searches"]
+#[serde(deny_unknown_fields)]
+pub struct NewSavedSearch<'a> {
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+
+    #[validate(length(min = 1, max = 64, message = "should be 1 to 64 letters long"))]
+    pub label: Cow<'a, str>,
+
+    #[validate(length(min = 2, max = 128, message = "should be 2 to 128 letters long"))]
+    pub name_fragment: Option<Cow<'a, str>>,
+    pub institution_id: Option<i32>,
+
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub radius_km: Option<f64>,
+}
+
+/// An Identification a search alerted of
+#[derive(Insertable)]
+#[table_name = "saved_search_alerts"]
+struct NewSavedSearchAlert {
+    saved_search_id: i32,
+    identification_id: i32,
+}
+
+impl<'a> NewSavedSearch<'a> {
+    /// Saves the search of the user
+    pub fn save(&mut self, usr: &User) -> Result<SavedSearch, ResError> {
+        use crate::diesel_cfg::schema::saved_searches::dsl::*;
+
+        self.check_criteria()?;
+
+        let conn = connect_to_db();
+        let saved = saved_searches
+            .filter(user_id.eq(usr.id))
+            .count()
+            .get_result::<i64>(&conn)?;
+        if saved >= SAVED_SEARCH_LIMIT {
+            return Err(ResError::new(
+                format!(
+                    "You can save up to {} searches. Remove one to add another",
+                    SAVED_SEARCH_LIMIT
+                ),
+                403,
+            ));
+        }
+
+        self.user_id = usr.id;
+        Ok(diesel::insert_into(saved_searches)
+            .values(&*self)
+            .get_result::<SavedSearch>(&conn)?)
+    }
+
+    /// Ensures the search has a criterion, and a whole,
+    /// valid area if any
+    fn check_criteria(&self) -> Result<(), ResError> {
+        let area = match (self.latitude, self.longitude, self.radius_km) {
+            (None, None, None) => false,
+            (Some(lat), Some(lon), Some(radius)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(ResError::new(
+                        "Give a latitude within ±90 and a longitude within ±180".into(),
+                        400,
+                    ));
+                }
+                if radius <= 0.0 || radius > MAX_RADIUS_KM {
+                    return Err(ResError::new(
+                        format!("Give a radius of up to {} km", MAX_RADIUS_KM),
+                        400,
+                    ));
+                }
+                true
+            }
+            _ => {
+                return Err(ResError::new(
+                    "Give the latitude, longitude and radius of the area together".into(),
+                    400,
+                ))
+            }
+        };
+
+        if self.name_fragment.is_none() && self.institution_id.is_none() && !area {
+            return Err(ResError::new(
+                "Search by a name, an institution or an area".into(),
+                400,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SavedSearch {
+    /// Retrieves the searches of the user
+    pub fn belonging_to_me(usr: &User) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::saved_searches::dsl::*;
+
+        Ok(saved_searches
+            .filter(user_id.eq(usr.id))
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Removes a search of the user
+    pub fn remove(pk: i32, usr: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::saved_searches::dsl::*;
+
+        diesel::delete(saved_searches.filter(id.eq(pk).and(user_id.eq(usr.id))))
+            .get_result::<Self>(&connect_to_db())
+            .optional()?
+            .ok_or_else(|| ResError::new("Saved search not found".into(), 404))
+    }
+
+    /// Whether the Identification meets every criterion of the search
+    pub fn matches(&self, idt: &Identification) -> bool {
+        if let Some(fragment) = &self.name_fragment {
+            if !normalize(&idt.name).contains(&normalize(fragment)) {
+                return false;
+            }
+        }
+        if self.institution_id.is_some() && self.institution_id != idt.institution_id {
+            return false;
+        }
+        if let (Some(lat), Some(lon), Some(radius)) =
+            (self.latitude, self.longitude, self.radius_km)
+        {
+            return match (idt.location_latitude, idt.location_longitude) {
+                (Some(idt_lat), Some(idt_lon)) => {
+                    distance_km((lat, lon), (idt_lat, idt_lon)) <= radius
+                }
+                // Identifications without a location point
+                // can't be placed within the area
+                _ => false,
+            };
+        }
+        true
+    }
+
+    /// Alerts the owners of the searches the Identification matches.
+    ///
+    /// The poster of the Identification isn't alerted. Searches that
+    /// alerted of the Identification before are skipped.
+    ///
+    /// # Returns
+    /// The number of users alerted
+    pub fn alert_for(idt: &Identification) -> Result<usize, ResError> {
+        use crate::diesel_cfg::schema::saved_searches::dsl::{id, institution_id, saved_searches};
+
+        let conn = connect_to_db();
+        let candidates = saved_searches
+            .filter(
+                institution_id
+                    .is_null()
+                    .or(institution_id.eq(idt.institution_id)),
+            )
+            .order(id.asc())
+            .load::<Self>(&conn)?;
+
+        let mut matched: BTreeMap<i32, Vec<&Self>> = BTreeMap::new();
+        for search in candidates
+            .iter()
+            .filter(|s| Some(s.user_id) != idt.posted_by && s.matches(idt))
+        {
+            matched.entry(search.user_id).or_default().push(search);
+        }
+
+        let mut alerted = 0;
+        for (user, searches) in matched {
+            let mut new = vec![];
+            for search in searches {
+                let inserted = diesel::insert_into(saved_search_alerts::table)
+                    .values(&NewSavedSearchAlert {
+                        saved_search_id: search.id,
+                        identification_id: idt.id,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(&conn)?;
+                if inserted > 0 {
+                    new.push(search);
+                }
+            }
+            if new.is_empty() {
+                continue;
+            }
+
+            let labels = new
+                .iter()
+                .map(|s| s.label.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Notification::notify(
+                &[user],
+                EventKind::SavedSearchMatched,
+                "An ID matching your search was posted",
+                &format!(
+                    "An ID of {} was posted, matching your search: {}",
+                    idt.name, labels
+                ),
+                json!({
+                    "identification_id": idt.id,
+                    "saved_search_ids": new.iter().map(|s| s.id).collect::<Vec<_>>(),
+                }),
+            );
+            alerted += 1;
+        }
+        Ok(alerted)
+    }
+}
+
+/// Lowercases a name, collapsing its whitespace
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Great-circle distance between two `(latitude, longitude)`
+/// points, in kilometres
+///
+/// ```rust
+/// use got_ya_id::apps::ids::searches::distance_km;
+///
+/// let nairobi = (-1.2921, 36.8219);
+/// let mombasa = (-4.0435, 39.6682);
+///
+/// let distance = distance_km(nairobi, mombasa);
+/// assert!(distance > 430.0 && distance < 450.0);
+/// assert_eq!(distance_km(nairobi, nairobi), 0.0);
+/// ```
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
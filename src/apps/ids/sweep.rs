@@ -0,0 +1,88 @@
+//! Full re-match of Claims against missing Identifications
+//!
+//! Matches are otherwise only computed when an Identification or
+//! a Claim is saved. The sweep picks up later changes, such as a
+//! claim moving institutions or changes to the matching rules.
+//!
+//! Blocking keys are recomputed first, so changes to the blocking
+//! rules also apply to existing records.
+
+use super::models::{ClaimableIdentification, Identification};
+use crate::{apps::jobs::models::JobKind, errors::error::ResError};
+
+use serde::{Deserialize, Serialize};
+
+/// Counts from a completed match sweep
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SweepReport {
+    /// Claims whose matches were recomputed
+    pub claims_scanned: usize,
+
+    /// Claims that could not be re-matched
+    pub claims_failed: usize,
+
+    /// Newly recorded Claim-Identification matches
+    pub matches_added: usize,
+
+    /// Recorded matches that no longer qualify, and were removed
+    pub matches_pruned: usize,
+}
+
+impl SweepReport {
+    /// Recomputes the matches of every active claim.
+    ///
+    /// Owners of claims with new matches are notified in the
+    /// background. A claim that fails to re-match, or whose owner
+    /// can't be notified, is logged and skipped.
+    pub async fn run() -> Result<SweepReport, ResError> {
+        let mut report = SweepReport::default();
+
+        for idt in Identification::retrieve_all("missing", None)? {
+            if let Err(e) = idt.refresh_blocks() {
+                error!(
+                    "Match sweep: Error indexing identification {}: {}",
+                    idt.id, e.msg
+                );
+            }
+        }
+
+        for claim in ClaimableIdentification::retrieve_active()? {
+            let rematched = match claim.refresh_blocks() {
+                Ok(_) => claim.rematch().await,
+                Err(e) => Err(e),
+            };
+
+            match rematched {
+                Ok((new_matches, pruned)) => {
+                    report.claims_scanned += 1;
+                    report.matches_added += new_matches.len();
+                    report.matches_pruned += pruned;
+
+                    for idt in new_matches {
+                        let queued = JobKind::SendNotification {
+                            claim_id: claim.id,
+                            identification_id: idt.id,
+                        }
+                        .enqueue();
+                        if let Err(e) = queued {
+                            error!(
+                                "Match sweep: Error queueing notification of claim {} matching identification {}: {}",
+                                claim.id, idt.id, e.msg
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    report.claims_failed += 1;
+                    error!(
+                        "Match sweep: Error re-matching claim {}: {}",
+                        claim.id, e.msg
+                    );
+                }
+            }
+        }
+
+        info!("Match sweep complete: {:?}", report);
+        Ok(report)
+    }
+}
@@ -0,0 +1,211 @@
+use super::{
+    lifecycle::ClaimStatus,
+    models::{ClaimableIdentification, Identification, NewIdentification},
+    ownership::{OwnershipDecision, OwnershipRequest, OwnershipStatus},
+};
+use crate::{
+    apps::user::{
+        models::{NewUser, User},
+        tests::DB_URL,
+    },
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{
+            claimed_identifications, identifications, institutions, matched_identifications,
+            ownership_requests,
+        },
+    },
+};
+
+use diesel::prelude::*;
+use std::borrow::Cow;
+
+/// Creates a user, or finds the one created by an earlier run
+fn user(username: &str) -> User {
+    let email = format!("{}@ownership.co", username);
+    let mut new_user = NewUser {
+        username: Cow::Borrowed(username),
+        password: Cow::Borrowed("password"),
+        access_level: Some(2),
+    };
+
+    new_user
+        .save(&email)
+        .or_else(|_| User::find_by_email(&email).map(|mut found| found.remove(0)))
+        .unwrap()
+}
+
+/// Posts an Identification
+fn posted_idt(poster: &User) -> Identification {
+    diesel::insert_into(identifications::table)
+        .values((
+            identifications::name.eq("Wanjiru Kamau"),
+            identifications::location_name.eq("Library"),
+            identifications::posted_by.eq(poster.id),
+        ))
+        .get_result::<Identification>(&connect_to_db())
+        .unwrap()
+}
+
+/// Creates a claim matching the Identification, and requests its ownership
+fn request(claimant: &User, idt: &Identification) -> OwnershipRequest {
+    let conn = connect_to_db();
+    let claim = diesel::insert_into(claimed_identifications::table)
+        .values((
+            claimed_identifications::user_id.eq(claimant.id),
+            claimed_identifications::name.eq("Wanjiru Kamau"),
+            claimed_identifications::status.eq(ClaimStatus::Matched),
+        ))
+        .get_result::<ClaimableIdentification>(&conn)
+        .unwrap();
+    diesel::insert_into(matched_identifications::table)
+        .values((
+            matched_identifications::claim_id.eq(claim.id),
+            matched_identifications::identification_id.eq(idt.id),
+        ))
+        .execute(&conn)
+        .unwrap();
+
+    diesel::insert_into(ownership_requests::table)
+        .values((
+            ownership_requests::claim_id.eq(claim.id),
+            ownership_requests::identification_id.eq(idt.id),
+            ownership_requests::requested_by.eq(claimant.id),
+        ))
+        .get_result::<OwnershipRequest>(&conn)
+        .unwrap()
+}
+
+fn no_reason() -> OwnershipDecision<'static> {
+    OwnershipDecision { reason: None }
+}
+
+#[test]
+fn approving_hands_identification_over() {
+    let _ = *DB_URL;
+    let poster = user("approvingposter");
+    let claimant = user("approvingclaimant");
+    let other = user("approvingother");
+
+    let idt = posted_idt(&poster);
+    let req = request(&claimant, &idt);
+    let other_req = request(&other, &idt);
+
+    let approved = req.approve(&poster, &no_reason()).unwrap();
+    assert_eq!(approved.status, OwnershipStatus::Approved);
+
+    let idt = Identification::find_by_id(idt.id).unwrap();
+    assert_eq!(idt.owner, Some(claimant.id));
+    let claim = ClaimableIdentification::find_by_id(req.claim_id).unwrap();
+    assert_eq!(claim.status, ClaimStatus::Verified);
+
+    // Only the holder records the handover
+    let err = approved.hand_over(&claimant).err().unwrap();
+    assert_eq!(err.status, 401);
+    let claim = approved.hand_over(&poster).unwrap();
+    assert_eq!(claim.status, ClaimStatus::Fulfilled);
+
+    let other_req = OwnershipRequest::find_by_id(other_req.id).unwrap();
+    assert_eq!(other_req.status, OwnershipStatus::Rejected);
+
+    // The rejected request can't be approved after
+    let err = other_req.approve(&poster, &no_reason()).err().unwrap();
+    assert_eq!(err.status, 409);
+}
+
+#[test]
+fn approving_after_a_handover_changes_nothing() {
+    let _ = *DB_URL;
+    let poster = user("raceposter");
+    let claimant = user("raceclaimant");
+    let owner = user("raceowner");
+
+    let idt = posted_idt(&poster);
+    let req = request(&claimant, &idt);
+
+    // Handed over elsewhere, after the request was loaded
+    diesel::update(identifications::table.find(idt.id))
+        .set(identifications::owner.eq(owner.id))
+        .execute(&connect_to_db())
+        .unwrap();
+
+    let err = req.approve(&poster, &no_reason()).err().unwrap();
+    assert_eq!(err.status, 409);
+
+    let req = OwnershipRequest::find_by_id(req.id).unwrap();
+    assert_eq!(req.status, OwnershipStatus::Pending);
+    let claim = ClaimableIdentification::find_by_id(req.claim_id).unwrap();
+    assert_eq!(claim.status, ClaimStatus::Matched);
+    let idt = Identification::find_by_id(idt.id).unwrap();
+    assert_eq!(idt.owner, Some(owner.id));
+}
+
+#[test]
+fn posters_cant_approve_their_own_requests() {
+    let _ = *DB_URL;
+    let poster = user("ownrequestposter");
+
+    let idt = posted_idt(&poster);
+    let req = request(&poster, &idt);
+
+    let err = req.approve(&poster, &no_reason()).err().unwrap();
+    assert_eq!(err.status, 403);
+    let idt = Identification::find_by_id(idt.id).unwrap();
+    assert_eq!(idt.owner, None);
+}
+
+/// Posts an Identification of "W. Kamau" at an institution
+fn posted_at(poster: &User, inst: i32, reg_no: Option<&str>) -> Identification {
+    diesel::insert_into(identifications::table)
+        .values((
+            identifications::name.eq("W. Kamau"),
+            identifications::location_name.eq("Library"),
+            identifications::posted_by.eq(poster.id),
+            identifications::institution_id.eq(inst),
+            identifications::registration_no.eq(reg_no),
+            identifications::registration_no_canonical.eq(reg_no),
+        ))
+        .get_result::<Identification>(&connect_to_db())
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn misread_registration_numbers_decide_near_name_matches() {
+    let _ = *DB_URL;
+    let conn = connect_to_db();
+    let claimant = user("misreadclaimant");
+    let poster = user("misreadposter");
+
+    let inst = diesel::insert_into(institutions::table)
+        .values((
+            institutions::name.eq("Misread University"),
+            institutions::town.eq("Nairobi"),
+            institutions::country.eq("Kenya"),
+        ))
+        .returning(institutions::id)
+        .get_result::<i32>(&conn)
+        .unwrap();
+
+    let claim = diesel::insert_into(claimed_identifications::table)
+        .values((
+            claimed_identifications::user_id.eq(claimant.id),
+            claimed_identifications::name.eq("Wanjiru Kamau"),
+            claimed_identifications::institution_id.eq(inst),
+            claimed_identifications::registration_no.eq("SCT22101232018"),
+            claimed_identifications::registration_no_canonical.eq("SCT22101232018"),
+        ))
+        .get_result::<ClaimableIdentification>(&conn)
+        .unwrap();
+
+    // The card shows an initial, and a digit was misread on posting
+    let misread = posted_at(&poster, inst, Some("SCT22101282018"));
+    assert!(NewIdentification::is_possible_match(&misread, &claim, None).await);
+
+    // The name alone falls short
+    let unnumbered = posted_at(&poster, inst, None);
+    assert!(!NewIdentification::is_possible_match(&unnumbered, &claim, None).await);
+
+    // As does the number of a classmate two characters off
+    let classmate = posted_at(&poster, inst, Some("SCT22101382018"));
+    assert!(!NewIdentification::is_possible_match(&classmate, &claim, None).await);
+}
@@ -1,3 +1,120 @@
+//! Identification helpers
+use super::{
+    models::{ClaimableIdentification, Identification},
+    ownership::{OwnershipRequest, OwnershipStatus},
+};
+use crate::{
+    apps::{
+        events::models::EventKind,
+        institution::pickup::{hours_summary, PickupPoint},
+        notifications::dispatcher::send_email,
+        user::{models::User, utils::get_notif_context},
+    },
+    errors::error::ResError,
+};
+
+use chrono::NaiveDate;
+use tera::Context;
+
+use std::env;
+
+/// Sends a notification email to the owner of a Claim
+/// matching the given Identification.
+pub async fn send_match_notification(
+    idt: &Identification,
+    claim: &ClaimableIdentification,
+) -> Result<(), ResError> {
+    let claim_rdct_link: String = env::var("CLAIM_REDIRECT_LINK").unwrap_or_else(|_| "".into());
+    let user_name = User::find_by_pk(claim.user_id, None)?.0.username;
+
+    let context = get_notif_context(&user_name, &claim_rdct_link, idt).await?;
+    send_email(
+        claim.user_id,
+        EventKind::MatchRecorded,
+        "claim_notification",
+        context,
+    )
+    .await
+}
+
+/// Reminds the owner of a Claim that it is about to expire.
+pub async fn send_expiry_reminder(
+    claim: &ClaimableIdentification,
+    expires_on: NaiveDate,
+) -> Result<(), ResError> {
+    let mut context = Context::new();
+    context.insert("claim_name", &claim.name);
+    context.insert("document_type", claim.document_type.label());
+    context.insert("expires_on", &expires_on.format("%e %B %Y").to_string());
+
+    send_email(
+        claim.user_id,
+        EventKind::ClaimExpiring,
+        "claim_expiry_reminder",
+        context,
+    )
+    .await
+}
+
+/// Notifies the parties to an ownership request of its status.
+///
+/// The poster of the Identification is told of new requests.
+/// Once decided, the claimant is told of the decision, as is the
+/// poster if someone else decided.
+pub async fn send_ownership_notice(request: &OwnershipRequest) -> Result<(), ResError> {
+    let idt = Identification::find_by_id(request.identification_id)?;
+    let claimant = User::find_by_pk(request.requested_by, None)?.0.username;
+
+    let mut context = Context::new();
+    context.insert("claimant", &claimant);
+    context.insert("id_name", &idt.name);
+    context.insert("document_type", idt.document_type.label());
+    context.insert("location_name", &idt.location_name);
+    if let Some(point_id) = idt.pickup_point_id {
+        let point = PickupPoint::find_by_id(point_id)?;
+        context.insert("location_name", &point.place()?);
+        context.insert("pickup_hours", &hours_summary(&point.hours()));
+        context.insert("pickup_directions", &point.directions);
+    }
+    context.insert("approved", &(request.status == OwnershipStatus::Approved));
+    context.insert("reason", &request.reason);
+
+    if request.status == OwnershipStatus::Pending {
+        if let Some(poster) = idt.posted_by {
+            send_email(
+                poster,
+                EventKind::OwnershipRequested,
+                "ownership_request",
+                context,
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+
+    let mut claimant_context = context.clone();
+    claimant_context.insert("to_claimant", &true);
+    send_email(
+        request.requested_by,
+        EventKind::OwnershipDecided,
+        "ownership_decision",
+        claimant_context,
+    )
+    .await?;
+
+    if let Some(poster) = idt.posted_by.filter(|p| Some(*p) != request.decided_by) {
+        context.insert("to_claimant", &false);
+        send_email(
+            poster,
+            EventKind::OwnershipDecided,
+            "ownership_decision",
+            context,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 /// (De)Serializer for Point type
 pub mod serde_pg_point {
     use diesel_geometry::data_types::PgPoint;
@@ -1,30 +1,40 @@
 //! Implementations of Http enpoints for the Identifications resource
 
+use actix_multipart::Multipart;
 use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
-
-use super::models::{
-    ClaimableIdentification, Identification, MatchedIdtJson, NewClaimableIdt, NewIdentification,
-    UpdatableClaimableIdt, UpdatableIdentification,
+use futures::{StreamExt, TryStreamExt};
+
+use super::{
+    bulk::FoundIdUpload,
+    documents::DocumentTypeQuery,
+    models::{
+        ClaimableIdentification, Identification, MatchedIdtJson, NewClaimableIdt,
+        NewIdentification, UpdatableClaimableIdt, UpdatableIdentification,
+    },
+    ownership::{OwnershipDecision, OwnershipRequest, OwnershipRequestQuery},
+    registration::RegistrationSearch,
+    searches::{NewSavedSearch, SavedSearch},
 };
 use crate::{
-    apps::user::{
-        models::User,
-        utils::{get_notif_context, TEMPLATE},
-    },
-    core::{
-        mail,
-        response::{err, respond},
+    apps::{
+        jobs::models::{Job, JobKind},
+        user::models::User,
     },
+    core::response::{err, respond},
     errors::error::ResError,
     hashmap,
 };
 
 use validator::Validate;
 
-use futures::future::try_join;
-use futures::future::TryFutureExt;
+/// Largest sheet of found IDs taken, in bytes
+const MAX_SHEET_BYTES: usize = 1024 * 1024;
+
+/// Largest ZIP of photos of found IDs taken, in bytes
+const MAX_PHOTOS_BYTES: usize = 64 * 1024 * 1024;
 
-use std::env;
+/// Number of recent match sweeps listed
+const SWEEPS_LISTED: i64 = 20;
 
 /// Receives a json NewIdentification data struct which is
 /// used to POST a new Identification
@@ -37,6 +47,7 @@ pub async fn create_new_identification(
     req: HttpRequest,
     mut new_idt: web::Json<NewIdentification<'_>>,
 ) -> Result<HttpResponse, Error> {
+    new_idt.resolve_pickup_point()?;
     if let Err(e) = new_idt.validate() {
         return err("400", e.to_string()).await;
     }
@@ -44,18 +55,14 @@ pub async fn create_new_identification(
     let this_user = User::from_token(&req)?;
     new_idt.0.posted_by = Some(this_user.id);
 
-    let idt_f = new_idt.save();
+    let idt = new_idt.save().await?;
 
-    // Identify possible existing claim on the ID
-    let idt_: Identification = Identification::from(&new_idt.0);
-    let match_f = idt_.match_claims();
-
-    let (idt, (is_matched, matched_claims)) = try_join(idt_f, match_f).await?;
-
-    // Send notification
-    if is_matched {
-        send_claim_notification(&idt, matched_claims).await?;
+    // Identify possible existing claims on the ID.
+    // Matching and notifications run in the background
+    JobKind::MatchId {
+        identification_id: idt.id,
     }
+    .enqueue()?;
 
     let res = hashmap!["status" => "201",
             "message" => "Success. Identification created"];
@@ -71,7 +78,7 @@ pub async fn create_new_identification(
 /// # Method
 ///  `GET`
 pub async fn get_idt(pk: web::Path<i32>) -> Result<HttpResponse, Error> {
-    let idt = Identification::find_by_id(*pk)?;
+    let idt = Identification::find_by_id(*pk)?.masked();
 
     let msg = hashmap!["status" => "200",
             "message" => "Success. Identification retrived"];
@@ -84,46 +91,165 @@ pub async fn get_idt(pk: web::Path<i32>) -> Result<HttpResponse, Error> {
 /// Stick to /ids/missing if unsure.
 ///
 /// # Url
-/// `/ids`
+/// `/ids?document_type={type}`
+///
+/// `document_type` is optional, e.g `passport`
 ///
 /// # Method
 /// `GET`
-pub async fn get_all_idts() -> Result<HttpResponse, Error> {
-    let data = Identification::retrieve_all("all")?;
+pub async fn get_all_idts(query: web::Query<DocumentTypeQuery>) -> Result<HttpResponse, Error> {
+    let data = Identification::retrieve_all("all", query.document_type)?
+        .into_iter()
+        .map(Identification::masked)
+        .collect::<Vec<_>>();
     let msg = hashmap!["status" => "200",
             "message" => "Success. All identifications retrieved"];
 
     respond(msg, Some(data), None).unwrap().await
 }
 
+/// Uploads a sheet of IDs handed in to an institution, as logged by
+/// its security desk, with an optional ZIP of their photos.
+///
+/// Each row is saved as a posted Identification is. The IDs created
+/// are matched against claims in one background job. The report
+/// lists the rows created, duplicate and invalid.
+///
+/// # Url
+/// `/ids/institution/{institution_id}/upload`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Custodians or admins of the institution, or moderators
+///
+/// ## multipart
+/// - `ids`: A CSV of the IDs, with a header row of the fields of a
+///   new Identification, e.g `name,course,registration_no,location_name`
+/// - `photos`: A ZIP of photos keyed by row, e.g `1.jpg` for the
+///   first row after the header
+pub async fn upload_found_ids(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    mut multipart: Multipart,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let mut sheet = None;
+    let mut photos = None;
+    while let Some(mut field) = multipart.try_next().await? {
+        let (name, limit) = match field
+            .content_disposition()
+            .and_then(|d| d.get_name().map(String::from))
+        {
+            Some(name) if name == "ids" => (name, MAX_SHEET_BYTES),
+            Some(name) if name == "photos" => (name, MAX_PHOTOS_BYTES),
+            _ => {
+                return err(
+                    "400",
+                    "Upload the sheet as `ids`, and the photos as `photos`".to_string(),
+                )
+                .await
+            }
+        };
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            data.extend_from_slice(&chunk?);
+            if data.len() > limit {
+                return err("413", format!("The {} upload is too large", name)).await;
+            }
+        }
+        if name == "ids" {
+            sheet = Some(data);
+        } else {
+            photos = Some(data);
+        }
+    }
+
+    let sheet = match sheet {
+        Some(sheet) => sheet,
+        None => return err("400", "Upload a CSV sheet of the IDs as `ids`".to_string()).await,
+    };
+    let report = FoundIdUpload { sheet, photos }
+        .save(pk.into_inner(), &user)
+        .await?;
+
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Identifications uploaded"];
+    respond(msg, Some(report), None).unwrap().await
+}
+
 /// Retrieves all existings Identifications belonging to a
 /// given institution.
 ///
 /// # Url
-/// `/ids/institution/{institution_id}`
+/// `/ids/institution/{institution_id}?document_type={type}`
+///
+/// `document_type` is optional
 ///
 /// # Method
 /// `GET`
 pub async fn get_ids_by_institution_pk(
     institution_id: web::Path<i32>,
+    query: web::Query<DocumentTypeQuery>,
 ) -> Result<HttpResponse, Error> {
-    let data = Identification::retrieve_by_institution_id(*institution_id)?;
+    let data = Identification::retrieve_by_institution_id(*institution_id, query.document_type)?
+        .into_iter()
+        .map(Identification::masked)
+        .collect::<Vec<_>>();
     let msg = hashmap!["status" => "200",
             "message" => "Success. All identifications retrieved"];
 
     respond(msg, Some(data), None).unwrap().await
 }
 
+/// Finds Identifications by registration number
+///
+/// The number is compared in its canonical form, so
+/// "SCT221-0123/2018" finds "sct 221 0123 2018".
+///
+/// # Url
+/// `/ids/search/registration?registration_no={reg_no}&institution_id={id}`
+///
+/// `institution_id` is optional.
+///
+/// Custodians of the institution and moderators may search any
+/// number. Other users only the numbers of their own claims.
+///
+/// # Method
+/// `GET`
+pub async fn search_by_registration_no(
+    req: HttpRequest,
+    query: web::Query<RegistrationSearch>,
+) -> Result<HttpResponse, Error> {
+    let data = Identification::search_by_registration_no(
+        &query.registration_no,
+        query.institution_id,
+        &User::from_token(&req)?,
+    )?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Identifications retrieved"];
+
+    respond(msg, Some(data), None).unwrap().await
+}
+
 /// Retrieves missing Identifications. These are identifications
 /// which have not been marked `is_found` as True yet.
 ///
 /// # Url
-/// `/ids/missing`
+/// `/ids/missing?document_type={type}`
+///
+/// `document_type` is optional
 ///
 /// # Method
 /// `GET`
-pub async fn get_missing_idts() -> Result<HttpResponse, Error> {
-    let data = Identification::retrieve_all("missing")?;
+pub async fn get_missing_idts(query: web::Query<DocumentTypeQuery>) -> Result<HttpResponse, Error> {
+    let data = Identification::retrieve_all("missing", query.document_type)?
+        .into_iter()
+        .map(Identification::masked)
+        .collect::<Vec<_>>();
     let msg = hashmap!["status" => "200",
             "message" => "Success. Missing identifications retrieved"];
 
@@ -133,12 +259,17 @@ pub async fn get_missing_idts() -> Result<HttpResponse, Error> {
 /// which have an `is_found` marked True by the owner.
 ///
 /// # Url
-/// `/ids/found`
+/// `/ids/found?document_type={type}`
+///
+/// `document_type` is optional
 ///
 /// # Method
 /// `GET`
-pub async fn get_found_idts() -> Result<HttpResponse, Error> {
-    let data = Identification::retrieve_all("found")?;
+pub async fn get_found_idts(query: web::Query<DocumentTypeQuery>) -> Result<HttpResponse, Error> {
+    let data = Identification::retrieve_all("found", query.document_type)?
+        .into_iter()
+        .map(Identification::masked)
+        .collect::<Vec<_>>();
     let msg = hashmap!["status" => "200",
             "message" => "Success. Found identifications retrieved"];
 
@@ -214,24 +345,23 @@ pub async fn lose_idt(pk: web::Path<i32>) -> Result<HttpResponse, Error> {
 /// `PUT`
 pub async fn update_idt(
     pk: web::Path<i32>,
-    new_data: web::Json<UpdatableIdentification<'_>>,
+    mut new_data: web::Json<UpdatableIdentification<'_>>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
+    let idt = Identification::find_by_id(pk.into_inner())?;
+    new_data.resolve_pickup_point(&idt)?;
     if let Err(e) = new_data.validate() {
         return err("400", e.to_string()).await;
     };
-    let idt = Identification::find_by_id(pk.into_inner())?;
     let saved = idt.update(&req, &new_data)?;
 
     let msg = hashmap!["status" => "200",
             "message" => "Success. Identification updated"];
 
-    let (newly_matched, matched_claims) = idt.match_claims().await?;
-
-    // Send Notification
-    if newly_matched {
-        send_claim_notification(&saved, matched_claims).await?;
+    JobKind::MatchId {
+        identification_id: saved.id,
     }
+    .enqueue()?;
 
     respond(msg, Some(saved), None).unwrap().await
 }
@@ -276,6 +406,9 @@ pub async fn get_user_posted_idts(req: HttpRequest) -> Result<HttpResponse, Erro
 
 /// Allows a user to claim an Identification as belonging to them
 ///
+/// This requests the ownership of the Identification. It is
+/// transferred once the user who posted it, or a custodian, approves.
+///
 /// # Url
 /// `/ids/claim/mine`
 ///
@@ -302,10 +435,119 @@ pub async fn claim_idt(
     req: HttpRequest,
     data: web::Json<MatchedIdtJson>,
 ) -> Result<HttpResponse, Error> {
-    let owned_idt = Identification::search_matching_claim(&data, &User::from_token(&req)?)?;
+    let request = OwnershipRequest::create(&data, &User::from_token(&req)?)?;
+    let msg = hashmap!["status" => "201",
+            "message" => "Success. Ownership requested"];
+    respond(msg, Some(request), None).unwrap().await
+}
+
+/// Retrieves the ownership requests on Identifications
+/// posted by the user
+///
+/// # Url
+/// `/ids/posted/me/requests?status=pending`
+///
+/// # Method
+/// GET
+///
+/// ## Authorization required
+pub async fn get_posted_requests(
+    req: HttpRequest,
+    query: web::Query<OwnershipRequestQuery>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let requests = OwnershipRequest::received_by(&user, query.status)?;
+
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Ownership requests retrieved"];
+
+    respond(msg, Some(requests), None).unwrap().await
+}
+
+/// Approves an ownership request, making the claimant the
+/// owner of the Identification
+///
+/// # Url
+/// `/ids/requests/{pk}/approve`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The poster of the Identification, or a custodian
+///
+/// ## Example
+/// ```json
+/// {
+///     "reason": "Described the photo on it"
+/// }
+/// ```
+pub async fn approve_ownership_request(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<OwnershipDecision<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let approved = OwnershipRequest::find_by_id(*pk)?.approve(&user, &data)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Ownership request approved"];
+
+    respond(msg, Some(approved), None).unwrap().await
+}
+
+/// Records the handover of the Identification of an approved
+/// ownership request, fulfilling the claim
+///
+/// # Url
+/// `/ids/requests/{pk}/handover`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The poster of the Identification, or a custodian
+pub async fn hand_over_identification(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let claim = OwnershipRequest::find_by_id(*pk)?.hand_over(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Identification handed over"];
+
+    respond(msg, Some(claim), None).unwrap().await
+}
+
+/// Rejects an ownership request. A reason is required.
+///
+/// # Url
+/// `/ids/requests/{pk}/reject`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The poster of the Identification, or a custodian
+pub async fn reject_ownership_request(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<OwnershipDecision<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let rejected = OwnershipRequest::find_by_id(*pk)?.reject(&user, &data)?;
     let msg = hashmap!["status" => "200",
-            "message" => "Success. Identification claimed"];
-    respond(msg, Some(owned_idt), None).unwrap().await
+            "message" => "Success. Ownership request rejected"];
+
+    respond(msg, Some(rejected), None).unwrap().await
 }
 
 /// Created a claim to an identification
@@ -330,19 +572,15 @@ pub async fn create_idt_claim(
 
     let user = User::from_token(&req)?;
     let new_claim = new_idt.save(&user).await?;
-    let match_f = new_claim.match_idt().map_err(|e| e.into());
+
+    JobKind::MatchClaim {
+        claim_id: new_claim.id,
+    }
+    .enqueue()?;
 
     let msg = hashmap!["status" => "201",
             "message" => "Success. Claim saved"];
-    let resp_f = respond(msg, Some(new_claim.clone()), None).unwrap();
-
-    let ((is_matched, matched_idts), res) = try_join(match_f, resp_f).await?;
-
-    // send Notification
-    if is_matched {
-        send_claimed_notification(&user, matched_idts).await?;
-    }
-    Ok(res)
+    respond(msg, Some(new_claim), None).unwrap().await
 }
 
 /// Updates existing Claims
@@ -367,11 +605,10 @@ pub async fn update_idt_claim(
     let msg = hashmap!["status" => "200",
             "message" => "Success. Claimupdated"];
 
-    let (newly_matched, matched_idts) = claimed_idt.match_idt().await?;
-
-    if newly_matched {
-        send_claimed_notification(&user, matched_idts).await?;
+    JobKind::MatchClaim {
+        claim_id: updated.id,
     }
+    .enqueue()?;
 
     respond(msg, Some(updated), None).unwrap().await
 }
@@ -392,9 +629,12 @@ pub async fn retrieve_claim(req: HttpRequest, pk: web::Path<i32>) -> Result<Http
 
     respond(msg, Some(idt_claim), None).unwrap().await
 }
-/// Retrieves a Claimable Identification belonging to a
+/// Retrieves the Claimable Identifications belonging to a
 /// given user.
 ///
+/// Each claim carries its match status: `is_matched`, and the
+/// ids of the `matched_identifications`.
+///
 /// # Url
 /// `/ids/claim/user`
 ///
@@ -403,66 +643,187 @@ pub async fn retrieve_claim(req: HttpRequest, pk: web::Path<i32>) -> Result<Http
 pub async fn retrieve_user_claim(req: HttpRequest) -> Result<HttpResponse, Error> {
     let user = User::from_token(&req)?;
 
-    let idt_claim = ClaimableIdentification::belonging_to_me(&user)?;
+    let idt_claims = ClaimableIdentification::belonging_to_me_with_matches(&user)?;
     let msg = hashmap!["status" => "200",
-            "message" => "Success. Claim  retrieved"];
+            "message" => "Success. Claims retrieved"];
 
-    respond(msg, Some(idt_claim), None).unwrap().await
+    respond(msg, Some(idt_claims), None).unwrap().await
 }
 
-/// Sends a notification email to Users of the passed Identification
-/// claims.
+/// Closes a Claim on its owner's request.
+///
+/// A withdrawn claim is no longer matched.
 ///
-/// A notification is sent to every email attached to the User
-async fn send_claim_notification(
-    idt: &Identification,
-    claims: Vec<ClaimableIdentification>,
-) -> Result<(), ResError> {
-    let claim_rdct_link: String = env::var("CLAIM_REDIRECT_LINK").unwrap_or_else(|_| "".into());
+/// # Url
+/// `/ids/claim/{pk}/withdraw`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The owner of the claim, or admins of its institution
+pub async fn withdraw_claim(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
 
-    for claim in claims {
-        let user_emails = User::all_emails(claim.user_id).await?;
-        let user_name = User::find_by_pk(claim.user_id, None)?.0.username;
+    let withdrawn = ClaimableIdentification::find_by_id(*pk)?.withdraw(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Claim withdrawn"];
 
-        let context = get_notif_context(&user_name, &claim_rdct_link, idt).await?;
+    respond(msg, Some(withdrawn), None).unwrap().await
+}
 
-        let s = TEMPLATE.render("claim_notification.html", &context)?;
+/// Keeps a Claim active.
+///
+/// An expired claim is reopened and matched again. The expiry of an
+/// active claim is postponed.
+///
+/// # Url
+/// `/ids/claim/{pk}/renew`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+pub async fn renew_claim(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
 
-        for user_email in user_emails {
-            let mut mail = mail::Mail::new(&user_email, &user_name, "Pick up your ID", &s)
-                .await
-                .map_err(|e| ResError::new(e, 500))?;
+    let renewed = ClaimableIdentification::find_by_id(*pk)?.renew(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Claim renewed"];
 
-            mail.send().await?;
-        }
-    }
-    Ok(())
+    respond(msg, Some(renewed), None).unwrap().await
 }
 
-/// Sends a notification email to a single claim owner whose Identification
-/// claim matches a set of Identifications.
+/// Queues a recompute of the matches of all Claims.
+///
+/// New matches are recorded, and matches that no longer qualify
+/// are removed. The sweep runs in the background, as it also
+/// does periodically. The queued job is returned.
+///
+/// # Url
+/// `/ids/matches/sweep`
+///
+/// # Method
+/// `POST`
 ///
-/// The send email has the details of only one of the Identifications
-/// in the matched set. The notification content should redirect the user
-/// to the rest of the matches
-async fn send_claimed_notification(
-    clm_owner: &User,
-    idts: Vec<Identification>,
-) -> Result<(), ResError> {
-    let claim_rdct_link: String = env::var("CLAIM_REDIRECT_LINK").unwrap_or_else(|_| "".into());
+/// #### Authentication required
+/// Moderators only
+pub async fn sweep_matches(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    if !user.is_moderator() {
+        return Err(ResError::unauthorized().into());
+    }
+
+    let job = JobKind::RematchSweep.enqueue()?;
+    let msg = hashmap!["status" => "202",
+            "message" => "Accepted. Claim matches will be recomputed"];
 
-    let emails_f = clm_owner.emails().map_err(|e| e.into());
-    let context_f = get_notif_context(&clm_owner.username, &claim_rdct_link, &idts[0]);
+    respond(msg, Some(job), None).unwrap().await
+}
+
+/// Retrieves the latest match sweeps, newest first.
+///
+/// The `result` of a completed sweep holds its counts of claims
+/// scanned and failed, and of matches added and pruned.
+///
+/// # Url
+/// `/ids/matches/sweep`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+/// Moderators only
+pub async fn get_match_sweeps(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    if !user.is_moderator() {
+        return Err(ResError::unauthorized().into());
+    }
 
-    let (emails, context) = try_join(emails_f, context_f).await?;
-    let s = TEMPLATE.render("claim_notification.html", &context)?;
+    let sweeps = Job::latest_of(JobKind::RematchSweep.name(), SWEEPS_LISTED)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Match sweeps retrieved"];
 
-    for user_email in emails {
-        let mut mail = mail::Mail::new(&user_email, &clm_owner.username, "Pick up your ID", &s)
-            .await
-            .map_err(|e| ResError::new(e, 500))?;
+    respond(msg, Some(sweeps), None).unwrap().await
+}
 
-        mail.send().await?;
+/// Saves a search to be alerted of Identifications matching it,
+/// as they are posted
+///
+/// # Url
+/// `/ids/searches`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   label: "My student ID",
+///   name_fragment: "Mugo",
+///   institution_id: 2,
+///   latitude: -1.2921,
+///   longitude: 36.8219,
+///   radius_km: 5
+///   }
+/// ```
+/// Give at least one of the name fragment, institution
+/// or area. The area takes all three of its fields.
+pub async fn create_saved_search(
+    req: HttpRequest,
+    mut data: web::Json<NewSavedSearch<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
     }
-    Ok(())
+    let user = User::from_token(&req)?;
+
+    let search = data.save(&user)?;
+    let msg = hashmap!["status" => "201",
+            "message" => "Success. Search saved"];
+
+    respond(msg, Some(search), None).unwrap().await
+}
+
+/// Retrieves the saved searches of the user
+///
+/// # Url
+/// `/ids/searches`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_saved_searches(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let searches = SavedSearch::belonging_to_me(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Saved searches retrieved"];
+
+    respond(msg, Some(searches), None).unwrap().await
+}
+
+/// Removes a saved search of the user
+///
+/// # Url
+/// `/ids/searches/{pk}`
+///
+/// # Method
+/// `DELETE`
+///
+/// #### Authentication required
+pub async fn remove_saved_search(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let removed = SavedSearch::remove(*pk, &user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Saved search removed"];
+
+    respond(msg, Some(removed), None).unwrap().await
 }
@@ -0,0 +1,258 @@
+//! Institution digests
+//!
+//! Staff and students subscribe to a daily or weekly email of the
+//! IDs newly posted at an institution, and not yet found by their
+//! owners.
+//!
+//! Each subscription keeps the newest identification it was sent, so
+//! no identification is sent twice. The watermark moves once the
+//! dispatcher took the email, which keeps to the notification
+//! preferences, quiet hours and unsubscribe links of the user.
+//! Digests are sent by the `SendInstitutionDigests` job, enqueued by
+//! the scheduler.
+
+use crate::{
+    apps::{
+        events::models::EventKind,
+        ids::documents::DocumentType,
+        notifications::{dispatcher::send_email, preferences::Digest},
+        user::{models::User, utils::from_timestamp},
+    },
+    diesel_cfg::{config::connect_to_db, schema::institution_digests},
+    errors::error::ResError,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tera::Context;
+
+/// Most identifications listed in a digest.
+/// The rest are only counted.
+const DIGEST_ITEMS_LIMIT: usize = 50;
+
+/// Queryable model of the institution_digests table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "institution_digests"]
+pub struct InstitutionDigest {
+    pub id: i32,
+    pub user_id: i32,
+    pub institution_id: i32,
+    pub frequency: Digest,
+
+    /// Newest identification sent to the subscriber
+    pub last_identification_id: i32,
+    pub last_sent_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// A subscription to save
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestSubscription {
+    /// `daily` or `weekly`
+    pub frequency: Digest,
+}
+
+/// Numbers of digests sent, and of subscribers skipped
+#[derive(Serialize, Debug, Default)]
+pub struct InstitutionDigestReport {
+    pub sent: usize,
+    /// Nothing new was posted, or the subscriber can't be mailed
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// An identification listed in a digest
+#[derive(Queryable)]
+struct DigestItem {
+    id: i32,
+    name: String,
+    document_type: DocumentType,
+    location_name: String,
+    created_at: NaiveDateTime,
+}
+
+impl InstitutionDigest {
+    /// Subscribes the user to digests of the institution,
+    /// or changes the frequency of their subscription.
+    ///
+    /// A new subscription starts from the identifications
+    /// posted after it.
+    pub fn subscribe(usr: &User, inst_id: i32, digest: Digest) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::{
+            id as idt_id, identifications, institution_id as idt_institution,
+        };
+        use crate::diesel_cfg::schema::institution_digests::dsl::*;
+        use diesel::{dsl::max, pg::upsert::on_constraint};
+
+        if digest.period().is_none() {
+            return Err(ResError::new(
+                "Pick a daily or weekly digest. Unsubscribe to stop them".into(),
+                400,
+            ));
+        }
+
+        let conn = connect_to_db();
+        let newest = identifications
+            .filter(idt_institution.eq(inst_id))
+            .select(max(idt_id))
+            .first::<Option<i32>>(&conn)?
+            .unwrap_or(0);
+
+        Ok(diesel::insert_into(institution_digests)
+            .values((
+                user_id.eq(usr.id),
+                institution_id.eq(inst_id),
+                frequency.eq(digest),
+                last_identification_id.eq(newest),
+            ))
+            .on_conflict(on_constraint("institution_digests_unique"))
+            .do_update()
+            .set(frequency.eq(digest))
+            .get_result::<Self>(&conn)?)
+    }
+
+    /// Stops the digests of the institution to the user
+    pub fn unsubscribe(usr: &User, inst_id: i32) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::institution_digests::dsl::*;
+
+        let removed = diesel::delete(
+            institution_digests.filter(user_id.eq(usr.id).and(institution_id.eq(inst_id))),
+        )
+        .execute(&connect_to_db())?;
+
+        if removed == 0 {
+            return Err(ResError::new(
+                "You are not subscribed to digests of this institution".into(),
+                404,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Retrieves the subscriptions of the user
+    pub fn belonging_to_user(usr: &User) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institution_digests::dsl::*;
+
+        Ok(institution_digests
+            .filter(user_id.eq(usr.id))
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Sends the digests that are due
+    pub async fn send_due() -> Result<InstitutionDigestReport, ResError> {
+        use crate::diesel_cfg::schema::institution_digests::dsl::{id, institution_digests};
+
+        let mut report = InstitutionDigestReport::default();
+        let now = Utc::now().naive_utc();
+
+        let subscriptions = institution_digests
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?;
+
+        for subscription in subscriptions.iter().filter(|s| s.is_due(now)) {
+            match subscription.send(now).await {
+                Ok(true) => report.sent += 1,
+                Ok(false) => report.skipped += 1,
+                Err(e) => {
+                    error!(
+                        "Institution digests: Error sending digest {}: {}",
+                        subscription.id, e.msg
+                    );
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Whether a digest may be sent at the given UTC time
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        match (self.frequency.period(), self.last_sent_at) {
+            (Some(period), Some(last)) => last + period <= now,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Emails a digest of the identifications posted since the
+    /// last one, moving the watermark past them.
+    ///
+    /// # Returns
+    /// Whether a digest was sent
+    async fn send(&self, now: NaiveDateTime) -> Result<bool, ResError> {
+        use crate::diesel_cfg::schema::identifications::dsl::{
+            created_at, document_type, id, identifications, institution_id, is_found,
+            location_name, name,
+        };
+        use crate::diesel_cfg::schema::institution_digests::dsl::{
+            last_identification_id, last_sent_at,
+        };
+        use crate::diesel_cfg::schema::institutions::dsl::{
+            institutions, name as institution_name,
+        };
+
+        let conn = connect_to_db();
+        let items = identifications
+            .filter(
+                institution_id
+                    .eq(self.institution_id)
+                    .and(id.gt(self.last_identification_id))
+                    .and(is_found.eq(false)),
+            )
+            .select((id, name, document_type, location_name, created_at))
+            .order(id.asc())
+            .load::<DigestItem>(&conn)?;
+        let newest = match items.last() {
+            Some(item) => item.id,
+            None => return Ok(false),
+        };
+
+        let user = User::find_by_pk(self.user_id, None)?.0;
+        if user.deliverable_email()?.is_none() {
+            return Ok(false);
+        }
+        let institution = institutions
+            .find(self.institution_id)
+            .select(institution_name)
+            .first::<String>(&conn)?;
+
+        let listed = items
+            .iter()
+            .take(DIGEST_ITEMS_LIMIT)
+            .map(|item| {
+                json!({
+                    "name": item.name,
+                    "document_type": item.document_type.label(),
+                    "location_name": item.location_name,
+                    "posted_on": item.created_at.format("%d %b %Y").to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut context = Context::new();
+        context.insert("institution", &institution);
+        context.insert("items", &listed);
+        context.insert("total", &items.len());
+        context.insert("more", &(items.len() - listed.len()));
+        send_email(
+            self.user_id,
+            EventKind::InstitutionDigest,
+            "institution_digest",
+            context,
+        )
+        .await?;
+
+        diesel::update(self)
+            .set((last_identification_id.eq(newest), last_sent_at.eq(now)))
+            .execute(&conn)?;
+        Ok(true)
+    }
+}
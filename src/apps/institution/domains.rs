@@ -0,0 +1,241 @@
+//! Verified institution email domains
+//!
+//! Users join an institution by verifying an email on one of its
+//! domains. A domain matches emails on it exactly, and, unless
+//! turned off, emails on its subdomains.
+//!
+//! Domains are proposed by the admins of the institution, and only
+//! let users join once a moderator verifies them. Institutions may
+//! propose the same domain, but only one can have it verified, so a
+//! lookalike institution can't take the domain of the real one.
+//! Existing institutions have no domains until theirs are proposed
+//! and verified. Until then, users can't join them.
+
+use super::models::Institution;
+use crate::{
+    apps::user::{models::User, utils::from_timestamp},
+    diesel_cfg::{config::connect_to_db, schema::institution_domains},
+    errors::error::ResError,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    self,
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+    PgConnection,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use validator::ValidationError;
+use validator_derive::Validate;
+
+use std::borrow::Cow;
+
+/// Queryable model of the institution_domains table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "institution_domains"]
+pub struct InstitutionDomain {
+    pub id: i32,
+    pub institution_id: i32,
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub added_by: Option<i32>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+
+    /// When a moderator verified the domain belongs
+    /// to the institution. Unverified domains let no
+    /// one join
+    pub verified_at: Option<NaiveDateTime>,
+    pub verified_by: Option<i32>,
+}
+
+/// A domain to add to an institution
+#[derive(Insertable, Deserialize, Validate)]
+#[table_name = "institution_domains"]
+#[serde(deny_unknown_fields)]
+pub struct NewInstitutionDomain<'a> {
+    #[serde(skip_deserializing)]
+    pub institution_id: i32,
+
+    #[validate(custom = "validate_domain")]
+    pub domain: Cow<'a, str>,
+
+    #[serde(default = "subdomains_included")]
+    pub include_subdomains: bool,
+
+    #[serde(skip_deserializing)]
+    pub added_by: Option<i32>,
+}
+
+impl<'a> NewInstitutionDomain<'a> {
+    /// Proposes the domain for the institution.
+    ///
+    /// It lets users join once a moderator verifies it.
+    pub fn save(
+        &mut self,
+        institution: &Institution,
+        usr: &User,
+    ) -> Result<InstitutionDomain, ResError> {
+        self.institution_id = institution.id;
+        self.added_by = Some(usr.id);
+        self.domain = Cow::Owned(self.domain.trim().to_lowercase());
+
+        let conn = connect_to_db();
+        if InstitutionDomain::verified_owner(&conn, &self.domain)?.is_some() {
+            return Err(ResError::new(
+                format!("{} is already a domain of an institution", self.domain),
+                409,
+            ));
+        }
+
+        diesel::insert_into(institution_domains::table)
+            .values(&*self)
+            .on_conflict_do_nothing()
+            .get_result::<InstitutionDomain>(&conn)
+            .optional()?
+            .ok_or_else(|| {
+                ResError::new(
+                    format!("{} is already a domain of the institution", self.domain),
+                    409,
+                )
+            })
+    }
+}
+
+impl InstitutionDomain {
+    /// Retrieves the domains of the institution
+    pub fn for_institution(inst_id: i32) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institution_domains::dsl::*;
+
+        Ok(institution_domains
+            .filter(institution_id.eq(inst_id))
+            .order(domain.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the verified domains of the institution
+    pub fn verified_for(inst_id: i32) -> Result<Vec<Self>, ResError> {
+        Ok(Self::for_institution(inst_id)?
+            .into_iter()
+            .filter(|d| d.verified_at.is_some())
+            .collect())
+    }
+
+    /// Verifies that a proposed domain belongs to the institution.
+    ///
+    /// Takes a moderator. A domain verified for another
+    /// institution can't be verified again.
+    pub fn verify(inst_id: i32, pk: i32, moderator: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::institution_domains::dsl::*;
+
+        if !moderator.is_moderator() {
+            return Err(ResError::unauthorized());
+        }
+        diesel::update(institution_domains.filter(id.eq(pk).and(institution_id.eq(inst_id))))
+            .set((
+                verified_at.eq(Utc::now().naive_utc()),
+                verified_by.eq(moderator.id),
+            ))
+            .get_result::<Self>(&connect_to_db())
+            .optional()
+            .map_err(|e| match e {
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => ResError::new(
+                    "The domain is already verified for another institution".into(),
+                    409,
+                ),
+                e => e.into(),
+            })?
+            .ok_or_else(|| ResError::new("Domain not found".into(), 404))
+    }
+
+    /// The institution the domain is verified for, if any
+    fn verified_owner(conn: &PgConnection, name: &str) -> QueryResult<Option<i32>> {
+        use crate::diesel_cfg::schema::institution_domains::dsl::*;
+
+        institution_domains
+            .filter(domain.eq(name).and(verified_at.is_not_null()))
+            .select(institution_id)
+            .first::<i32>(conn)
+            .optional()
+    }
+
+    /// Removes a domain of the institution
+    pub fn remove(inst_id: i32, pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::institution_domains::dsl::*;
+
+        diesel::delete(institution_domains.filter(id.eq(pk).and(institution_id.eq(inst_id))))
+            .get_result::<Self>(&connect_to_db())
+            .optional()?
+            .ok_or_else(|| ResError::new("Domain not found".into(), 404))
+    }
+
+    /// Whether the email is on the domain
+    pub fn covers(&self, email: &str) -> bool {
+        domain_matches(&self.domain, self.include_subdomains, email)
+    }
+
+    /// Finds a verified email of the user on any of the domains
+    pub fn verified_email_on(usr: &User, domains: &[Self]) -> Result<Option<String>, ResError> {
+        use crate::diesel_cfg::schema::emails::dsl::{email, emails, removed, user_id, verified};
+
+        let verified_emails = emails
+            .filter(
+                user_id
+                    .eq(usr.id)
+                    .and(verified.eq(true))
+                    .and(removed.eq(false)),
+            )
+            .select(email)
+            .load::<String>(&connect_to_db())?;
+
+        Ok(verified_emails
+            .into_iter()
+            .find(|address| domains.iter().any(|d| d.covers(address))))
+    }
+}
+
+/// Whether an email is on a domain, or on one of its
+/// subdomains if they are included
+///
+/// ```rust
+/// use got_ya_id::apps::institution::domains::domain_matches;
+///
+/// assert!(domain_matches("uonbi.ac.ke", false, "jane@uonbi.ac.ke"));
+/// assert!(domain_matches("uonbi.ac.ke", true, "Jane@Students.UoNBi.ac.ke"));
+/// assert!(!domain_matches("uonbi.ac.ke", false, "jane@students.uonbi.ac.ke"));
+///
+/// // Lookalikes don't match
+/// assert!(!domain_matches("uonbi.ac.ke", true, "jane@fakeuonbi.ac.ke"));
+/// assert!(!domain_matches("uonbi.ac.ke", true, "jane@uonbi.ac.ke.evil.com"));
+/// ```
+pub fn domain_matches(domain: &str, include_subdomains: bool, email: &str) -> bool {
+    let host = match email.rfind('@') {
+        Some(at) => email[at + 1..].trim().to_lowercase(),
+        None => return false,
+    };
+
+    host == domain || (include_subdomains && host.ends_with(&format!(".{}", domain)))
+}
+
+/// Subdomains match unless said otherwise
+fn subdomains_included() -> bool {
+    true
+}
+
+/// Ensures a domain is a hostname with a top level domain,
+/// e.g `uonbi.ac.ke`
+fn validate_domain(domain: &str) -> Result<(), ValidationError> {
+    lazy_static! {
+        static ref DOMAIN_PATTERN: Regex =
+            Regex::new(r"(?i)^([a-z0-9]([a-z0-9-]*[a-z0-9])?\.)+[a-z]{2,}$").unwrap();
+    }
+    if !DOMAIN_PATTERN.is_match(domain.trim()) {
+        return Err(ValidationError::new("should be a domain, e.g uonbi.ac.ke"));
+    }
+    Ok(())
+}
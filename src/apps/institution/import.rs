@@ -0,0 +1,381 @@
+//! Bulk import of institutions
+//!
+//! Institutions are seeded from national registries of universities
+//! and colleges, given as CSV with a header row of
+//! `name,town,country,description,postal_address`, or as a JSON array
+//! of objects of the same fields. An institution of the same name,
+//! town and country is updated. The rest are created.
+//!
+//! An import runs in one transaction, and reports on each row. A bad
+//! row undoes the whole import, unless a partial import is asked for:
+//! then only the bad rows are left out. A dry run reports what the
+//! import would do, and undoes it.
+//!
+//! Imports are run by admins, through `/institutions/import` or the
+//! `import-institutions` subcommand.
+
+use crate::{
+    diesel_cfg::{config::connect_to_db, schema::institutions},
+    errors::error::ResError,
+};
+
+use csv::{ReaderBuilder, Trim};
+use diesel::{self, prelude::*, PgConnection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::Validate;
+use validator_derive::Validate;
+
+use std::{fs, path::Path};
+
+/// Most rows taken in one import
+const MAX_IMPORT_ROWS: usize = 5000;
+
+/// Name of the CLI subcommand
+pub const CLI_COMMAND: &str = "import-institutions";
+
+/// Usage of the CLI subcommand
+const CLI_USAGE: &str =
+    "Usage: got-ya-id import-institutions <FILE> [--format=csv|json] [--dry-run] [--partial]";
+
+/// Formats institutions are imported from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Json,
+}
+
+impl ImportFormat {
+    /// Finds the format from a content type or a file name
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::institution::import::ImportFormat;
+    ///
+    /// assert_eq!(ImportFormat::detect("text/csv; charset=utf-8"), Some(ImportFormat::Csv));
+    /// assert_eq!(ImportFormat::detect("registry.JSON"), Some(ImportFormat::Json));
+    /// assert_eq!(ImportFormat::detect("registry.xlsx"), None);
+    /// ```
+    pub fn detect(hint: &str) -> Option<Self> {
+        let hint = hint.to_lowercase();
+        if hint.contains("json") {
+            Some(ImportFormat::Json)
+        } else if hint.contains("csv") {
+            Some(ImportFormat::Csv)
+        } else {
+            None
+        }
+    }
+}
+
+/// How to run an import
+#[derive(Deserialize, Default)]
+pub struct ImportOptions {
+    /// Report what the import would do, saving nothing
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Save the good rows if some are bad
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// An institution to import
+#[derive(Deserialize, Validate, Insertable, AsChangeset)]
+#[table_name = "institutions"]
+#[serde(deny_unknown_fields)]
+pub struct InstitutionRow {
+    #[validate(length(min = 5, max = 255, message = "Try making the name at least 5 letters"))]
+    pub name: String,
+    #[validate(length(
+        min = 3,
+        max = 255,
+        message = "Try to make the town at least 3 letters long"
+    ))]
+    pub town: String,
+    #[validate(length(
+        min = 3,
+        max = 255,
+        message = "Try to make country at least 3 letters long"
+    ))]
+    pub country: String,
+    pub description: Option<String>,
+    pub postal_address: Option<String>,
+}
+
+/// What became of a row
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Created,
+    Updated,
+    /// The row couldn't be read, or has invalid fields
+    Invalid,
+    /// The row was refused by the database
+    Failed,
+}
+
+/// Report on a row of an import
+#[derive(Serialize, Deserialize)]
+pub struct RowReport {
+    /// Number of the row, from 1, not counting a CSV header
+    pub row: usize,
+    pub status: RowStatus,
+    pub institution_id: Option<i32>,
+    pub name: Option<String>,
+    pub errors: Option<String>,
+}
+
+/// Report on an import
+#[derive(Serialize, Deserialize, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Whether the import was saved
+    pub committed: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub invalid: usize,
+    pub failed: usize,
+    pub rows: Vec<RowReport>,
+}
+
+/// Ends the import transaction, undoing it
+enum Rollback {
+    Undo(ImportReport),
+    Error(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for Rollback {
+    fn from(e: diesel::result::Error) -> Self {
+        Rollback::Error(e)
+    }
+}
+
+impl ImportReport {
+    /// Whether any row was left out
+    pub fn has_errors(&self) -> bool {
+        self.invalid + self.failed > 0
+    }
+
+    fn add(&mut self, row: RowReport) {
+        match row.status {
+            RowStatus::Created => self.created += 1,
+            RowStatus::Updated => self.updated += 1,
+            RowStatus::Invalid => self.invalid += 1,
+            RowStatus::Failed => self.failed += 1,
+        }
+        self.rows.push(row);
+    }
+}
+
+impl InstitutionRow {
+    /// Reads the rows of an import, with the errors of the
+    /// rows that can't be read or are invalid
+    pub fn parse(data: &[u8], format: ImportFormat) -> Result<Vec<Result<Self, String>>, ResError> {
+        let rows = match format {
+            ImportFormat::Csv => ReaderBuilder::new()
+                .trim(Trim::All)
+                .from_reader(data)
+                .deserialize::<Self>()
+                .map(|row| row.map_err(|e| e.to_string()))
+                .collect::<Vec<_>>(),
+            ImportFormat::Json => serde_json::from_slice::<Vec<Value>>(data)
+                .map_err(|e| {
+                    ResError::new(format!("Give a JSON array of institutions: {}", e), 400)
+                })?
+                .into_iter()
+                .map(|row| serde_json::from_value::<Self>(row).map_err(|e| e.to_string()))
+                .collect::<Vec<_>>(),
+        };
+
+        if rows.is_empty() {
+            return Err(ResError::new(
+                "There are no institutions to import".into(),
+                400,
+            ));
+        }
+        if rows.len() > MAX_IMPORT_ROWS {
+            return Err(ResError::new(
+                format!("Import up to {} institutions at a time", MAX_IMPORT_ROWS),
+                400,
+            ));
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let row = row?.trimmed();
+                row.validate().map_err(|e| e.to_string())?;
+                Ok(row)
+            })
+            .collect())
+    }
+
+    /// Imports the institutions in one transaction
+    pub fn import(
+        data: &[u8],
+        format: ImportFormat,
+        options: &ImportOptions,
+    ) -> Result<ImportReport, ResError> {
+        let rows = Self::parse(data, format)?;
+
+        let conn = connect_to_db();
+        let result = conn.transaction::<_, Rollback, _>(|| {
+            let mut report = ImportReport {
+                dry_run: options.dry_run,
+                ..Default::default()
+            };
+
+            for (i, row) in rows.iter().enumerate() {
+                let row_no = i + 1;
+                let row = match row {
+                    Ok(row) => row,
+                    Err(errors) => {
+                        report.add(RowReport {
+                            row: row_no,
+                            status: RowStatus::Invalid,
+                            institution_id: None,
+                            name: None,
+                            errors: Some(errors.clone()),
+                        });
+                        continue;
+                    }
+                };
+
+                // Each row runs in a savepoint, so a refused row
+                // is undone alone and the rest are still checked
+                let saved = conn.transaction::<_, diesel::result::Error, _>(|| row.upsert(&conn));
+                report.add(match saved {
+                    Ok((pk, created)) => RowReport {
+                        row: row_no,
+                        status: if created {
+                            RowStatus::Created
+                        } else {
+                            RowStatus::Updated
+                        },
+                        institution_id: Some(pk),
+                        name: Some(row.name.clone()),
+                        errors: None,
+                    },
+                    Err(e) => RowReport {
+                        row: row_no,
+                        status: RowStatus::Failed,
+                        institution_id: None,
+                        name: Some(row.name.clone()),
+                        errors: Some(e.to_string()),
+                    },
+                });
+            }
+
+            if options.dry_run || (report.has_errors() && !options.partial) {
+                return Err(Rollback::Undo(report));
+            }
+            report.committed = true;
+            Ok(report)
+        });
+
+        match result {
+            Ok(report) | Err(Rollback::Undo(report)) => {
+                info!(
+                    "Institution import: {} created, {} updated, {} invalid, {} failed. Committed: {}",
+                    report.created, report.updated, report.invalid, report.failed, report.committed
+                );
+                Ok(report)
+            }
+            Err(Rollback::Error(e)) => Err(e.into()),
+        }
+    }
+
+    /// Runs the `import-institutions` subcommand
+    /// on the arguments following it
+    pub fn import_from_cli(args: &[String]) -> Result<ImportReport, ResError> {
+        let usage = || ResError::new(CLI_USAGE.into(), 400);
+
+        let mut options = ImportOptions::default();
+        let mut format = None;
+        let mut file = None;
+        for arg in args {
+            match arg.as_str() {
+                "--dry-run" => options.dry_run = true,
+                "--partial" => options.partial = true,
+                arg if arg.starts_with("--format=") => {
+                    format =
+                        Some(ImportFormat::detect(&arg["--format=".len()..]).ok_or_else(usage)?)
+                }
+                arg if !arg.starts_with("--") && file.is_none() => file = Some(arg),
+                _ => return Err(usage()),
+            }
+        }
+
+        let file = file.ok_or_else(usage)?;
+        let format = match format {
+            Some(format) => format,
+            None => ImportFormat::detect(
+                &Path::new(file)
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            )
+            .ok_or_else(|| {
+                ResError::new(
+                    format!("Can't tell the format of {}. Give it with --format", file),
+                    400,
+                )
+            })?,
+        };
+        let data = fs::read(file)
+            .map_err(|e| ResError::new(format!("Can't read {}: {}", file, e), 400))?;
+
+        Self::import(&data, format, &options)
+    }
+
+    /// The row, without surrounding whitespace and empty fields
+    fn trimmed(self) -> Self {
+        let optional = |field: Option<String>| {
+            field
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            name: self.name.trim().into(),
+            town: self.town.trim().into(),
+            country: self.country.trim().into(),
+            description: optional(self.description),
+            postal_address: optional(self.postal_address),
+        }
+    }
+
+    /// Updates the institution of the same name, town and country,
+    /// or creates it.
+    ///
+    /// # Returns
+    /// The id of the institution, and whether it was created
+    fn upsert(&self, conn: &PgConnection) -> QueryResult<(i32, bool)> {
+        use crate::diesel_cfg::schema::institutions::dsl::*;
+
+        let existing = institutions
+            .filter(
+                name.eq(&self.name)
+                    .and(town.eq(&self.town))
+                    .and(country.eq(&self.country)),
+            )
+            .select(id)
+            .first::<i32>(conn)
+            .optional()?;
+
+        match existing {
+            Some(pk) => {
+                diesel::update(institutions.find(pk))
+                    .set(self)
+                    .execute(conn)?;
+                Ok((pk, false))
+            }
+            None => Ok((
+                diesel::insert_into(institutions)
+                    .values(self)
+                    .returning(id)
+                    .get_result::<i32>(conn)?,
+                true,
+            )),
+        }
+    }
+}
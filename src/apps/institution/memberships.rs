@@ -0,0 +1,312 @@
+//! Institution memberships
+//!
+//! Users hold a role in each institution they belong to. Admins
+//! manage the institution, its staff, registration formats and email
+//! domains, and moderate its IDs and claims. Custodians hold the IDs
+//! handed in at the institution, and decide who gets them. Members
+//! joined on a verified email domain.
+//!
+//! Roles only reach within their institution. Moderators, whose
+//! access level is global, may act on every institution.
+
+use crate::{
+    apps::{
+        email::models::Email,
+        events::models::EventKind,
+        notifications::models::Notification,
+        user::{models::User, utils::from_timestamp},
+    },
+    diesel_cfg::{config::connect_to_db, schema::institution_memberships},
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::NaiveDateTime;
+use diesel::{self, pg::upsert::on_constraint, prelude::*, sql_types::Varchar, PgConnection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator_derive::Validate;
+
+use std::borrow::Cow;
+
+/// Roles of a user in an institution
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum InstitutionRole {
+    Admin,
+    Custodian,
+    Member,
+}
+
+varchar_enum!(InstitutionRole {
+    Admin => "admin",
+    Custodian => "custodian",
+    Member => "member"
+});
+
+impl InstitutionRole {
+    /// Whether the role may do what the given role does.
+    ///
+    /// Admins may do what custodians do, and custodians what
+    /// members do.
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::institution::memberships::InstitutionRole;
+    ///
+    /// assert!(InstitutionRole::Admin.includes(InstitutionRole::Custodian));
+    /// assert!(InstitutionRole::Custodian.includes(InstitutionRole::Custodian));
+    /// assert!(!InstitutionRole::Member.includes(InstitutionRole::Custodian));
+    /// ```
+    pub fn includes(self, role: InstitutionRole) -> bool {
+        self.rank() <= role.rank()
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            InstitutionRole::Admin => 0,
+            InstitutionRole::Custodian => 1,
+            InstitutionRole::Member => 2,
+        }
+    }
+}
+
+/// Queryable model of the institution_memberships table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "institution_memberships"]
+pub struct Membership {
+    pub id: i32,
+    pub institution_id: i32,
+    pub user_id: i32,
+    pub role: InstitutionRole,
+    pub invited_by: Option<i32>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// Staff to add to an institution
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct NewStaff<'a> {
+    /// An email of the user
+    #[validate(email(message = "Email format not invented yet"))]
+    pub email: Cow<'a, str>,
+    pub role: InstitutionRole,
+}
+
+impl Membership {
+    /// The role of the user in the institution, if any
+    pub fn role_of(usr_id: i32, inst_id: i32) -> Result<Option<InstitutionRole>, ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        Ok(institution_memberships
+            .filter(user_id.eq(usr_id).and(institution_id.eq(inst_id)))
+            .select(role)
+            .first::<InstitutionRole>(&connect_to_db())
+            .optional()?)
+    }
+
+    /// Whether the user may act with the role in the institution.
+    ///
+    /// Moderators may act on every institution. Only moderators
+    /// may act on what belongs to no institution.
+    pub fn holds(
+        usr: &User,
+        inst_id: Option<i32>,
+        required: InstitutionRole,
+    ) -> Result<bool, ResError> {
+        if usr.is_moderator() {
+            return Ok(true);
+        }
+        match inst_id {
+            Some(inst_id) => {
+                Ok(Self::role_of(usr.id, inst_id)?.map_or(false, |held| held.includes(required)))
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Ensures the user may act with the role in the institution
+    pub fn require(
+        usr: &User,
+        inst_id: Option<i32>,
+        required: InstitutionRole,
+    ) -> Result<(), ResError> {
+        if !Self::holds(usr, inst_id, required)? {
+            return Err(ResError::unauthorized());
+        }
+        Ok(())
+    }
+
+    /// Makes a moderator the first admin of an institution they created
+    pub fn found_on(conn: &PgConnection, inst_id: i32, usr: &User) -> QueryResult<Self> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        diesel::insert_into(institution_memberships)
+            .values((
+                institution_id.eq(inst_id),
+                user_id.eq(usr.id),
+                role.eq(InstitutionRole::Admin),
+            ))
+            .get_result::<Self>(conn)
+    }
+
+    /// Records the user as a member of the institution they joined.
+    ///
+    /// Their memberships of other institutions are dropped. Staff
+    /// roles are kept.
+    pub fn join(usr: &User, inst_id: i32) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        let conn = connect_to_db();
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(
+                institution_memberships.filter(
+                    user_id
+                        .eq(usr.id)
+                        .and(role.eq(InstitutionRole::Member))
+                        .and(institution_id.ne(inst_id)),
+                ),
+            )
+            .execute(&conn)?;
+            diesel::insert_into(institution_memberships)
+                .values((institution_id.eq(inst_id), user_id.eq(usr.id)))
+                .on_conflict_do_nothing()
+                .execute(&conn)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Retrieves the staff of the institution, admins first
+    pub fn staff_of(inst_id: i32) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        let mut staff = institution_memberships
+            .filter(
+                institution_id
+                    .eq(inst_id)
+                    .and(role.ne(InstitutionRole::Member)),
+            )
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?;
+        staff.sort_by_key(|m| m.role.rank());
+        Ok(staff)
+    }
+
+    /// Adds a user to the staff of the institution, or changes
+    /// their role. The user is notified.
+    pub fn invite(inst_id: i32, data: &NewStaff, by: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        if data.role == InstitutionRole::Member {
+            return Err(ResError::new(
+                "Invite staff as an admin or a custodian".into(),
+                400,
+            ));
+        }
+        let invitee = Email::u_id(&data.email).map_err(|_| {
+            ResError::new(format!("User of email {} non-existent", data.email), 404)
+        })?;
+        if invitee == by.id {
+            return Err(ResError::new("You can't change your own role".into(), 403));
+        }
+
+        let conn = connect_to_db();
+        let membership = conn.transaction::<_, ResError, _>(|| {
+            let previous = institution_memberships
+                .filter(user_id.eq(invitee).and(institution_id.eq(inst_id)))
+                .select(role)
+                .first::<InstitutionRole>(&conn)
+                .optional()?;
+            let membership = diesel::insert_into(institution_memberships)
+                .values((
+                    institution_id.eq(inst_id),
+                    user_id.eq(invitee),
+                    role.eq(data.role),
+                    invited_by.eq(by.id),
+                ))
+                .on_conflict(on_constraint("institution_memberships_unique"))
+                .do_update()
+                .set((role.eq(data.role), invited_by.eq(by.id)))
+                .get_result::<Self>(&conn)?;
+
+            if previous == Some(InstitutionRole::Admin) && data.role != InstitutionRole::Admin {
+                Self::keep_an_admin(&conn, inst_id)?;
+            }
+            Ok(membership)
+        })?;
+
+        Notification::notify(
+            &[invitee],
+            EventKind::InstitutionRoleChanged,
+            "You were added to an institution's staff",
+            &format!(
+                "{} made you {} of their institution",
+                by.username,
+                if data.role == InstitutionRole::Admin {
+                    "an admin"
+                } else {
+                    "a custodian"
+                }
+            ),
+            json!({ "institution_id": inst_id, "role": data.role }),
+        );
+        Ok(membership)
+    }
+
+    /// Removes a user from the staff of the institution.
+    ///
+    /// The last admin of an institution can't be removed.
+    pub fn remove(inst_id: i32, usr_id: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        let conn = connect_to_db();
+        conn.transaction::<_, ResError, _>(|| {
+            let removed = diesel::delete(
+                institution_memberships.filter(
+                    user_id
+                        .eq(usr_id)
+                        .and(institution_id.eq(inst_id))
+                        .and(role.ne(InstitutionRole::Member)),
+                ),
+            )
+            .get_result::<Self>(&conn)
+            .optional()?
+            .ok_or_else(|| ResError::new("Staff member not found".into(), 404))?;
+
+            if removed.role == InstitutionRole::Admin {
+                Self::keep_an_admin(&conn, inst_id)?;
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Fails if the institution would be left without an admin.
+    ///
+    /// Runs after the change, within its transaction, so a
+    /// failure undoes it.
+    fn keep_an_admin(conn: &PgConnection, inst_id: i32) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::*;
+
+        let admins = institution_memberships
+            .filter(
+                institution_id
+                    .eq(inst_id)
+                    .and(role.eq(InstitutionRole::Admin)),
+            )
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if admins == 0 {
+            return Err(ResError::new(
+                "An institution needs at least one admin".into(),
+                409,
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+//! Merging duplicate institutions
+//!
+//! A duplicate institution is folded into the one that survives: its
+//! identifications, claims and profiles move over, as do its staff,
+//! digests, domains, campuses, pickup points, registration formats,
+//! saved searches and webhooks. The duplicate is then removed. The
+//! whole merge runs in one transaction.
+//!
+//! Where the surviving institution already has the same member,
+//! subscriber or campus, its own is kept.
+
+use super::{
+    memberships::{InstitutionRole, Membership},
+    models::Institution,
+};
+use crate::{
+    apps::{jobs::models::JobKind, user::models::User},
+    diesel_cfg::config::connect_to_db,
+    errors::error::ResError,
+};
+
+use diesel::{self, prelude::*, PgConnection};
+use serde::{Deserialize, Serialize};
+
+/// The institution to merge a duplicate into
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstitutionMerge {
+    /// Id of the surviving institution
+    pub into: i32,
+}
+
+/// What a merge moved to the surviving institution
+#[derive(Serialize, Deserialize)]
+pub struct InstitutionMergeReport {
+    pub institution: Institution,
+    pub removed_institution_id: i32,
+    pub identifications: usize,
+    pub claims: usize,
+    pub profiles: usize,
+}
+
+impl Institution {
+    /// Merges the institution into another, and removes it.
+    ///
+    /// Takes an admin of both institutions, or a moderator.
+    /// The moved claims are matched again in the background.
+    pub async fn merge_into(
+        &self,
+        survivor_id: i32,
+        usr: &User,
+    ) -> Result<InstitutionMergeReport, ResError> {
+        if survivor_id == self.id {
+            return Err(ResError::new(
+                "An institution can't be merged into itself".into(),
+                400,
+            ));
+        }
+        let survivor = Self::find_by_pk(survivor_id).await?;
+        Membership::require(usr, Some(self.id), InstitutionRole::Admin)?;
+        Membership::require(usr, Some(survivor.id), InstitutionRole::Admin)?;
+
+        let conn = connect_to_db();
+        let (identifications, claims, profiles) =
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                let moved = move_records(&conn, self.id, survivor.id)?;
+                for claim_id in &moved.1 {
+                    JobKind::MatchClaim {
+                        claim_id: *claim_id,
+                    }
+                    .enqueue_on(&conn)?;
+                }
+                diesel::delete(self).execute(&conn)?;
+                Ok(moved)
+            })?;
+
+        info!(
+            "Institutions: {} merged into {} by user {}",
+            self.id, survivor.id, usr.id
+        );
+        Ok(InstitutionMergeReport {
+            institution: survivor,
+            removed_institution_id: self.id,
+            identifications,
+            claims: claims.len(),
+            profiles,
+        })
+    }
+}
+
+/// Moves everything of the duplicate to the surviving institution.
+///
+/// # Returns
+/// The number of identifications moved, the ids of the
+/// claims moved, and the number of profiles moved
+fn move_records(conn: &PgConnection, from: i32, to: i32) -> QueryResult<(usize, Vec<i32>, usize)> {
+    use crate::diesel_cfg::schema::{
+        campuses, claimed_identifications, identifications, institution_digests,
+        institution_domains, institution_memberships, pickup_points, profiles,
+        registration_formats, saved_searches, webhook_subscriptions,
+    };
+
+    let moved_ids =
+        diesel::update(identifications::table.filter(identifications::institution_id.eq(from)))
+            .set(identifications::institution_id.eq(to))
+            .execute(conn)?;
+    let moved_claims = diesel::update(
+        claimed_identifications::table.filter(claimed_identifications::institution_id.eq(from)),
+    )
+    .set(claimed_identifications::institution_id.eq(to))
+    .returning(claimed_identifications::id)
+    .get_results::<i32>(conn)?;
+    let moved_profiles = diesel::update(profiles::table.filter(profiles::institution_id.eq(from)))
+        .set(profiles::institution_id.eq(to))
+        .execute(conn)?;
+
+    // Members and subscribers of both keep what they
+    // hold at the surviving institution
+    let survivor_members = institution_memberships::table
+        .filter(institution_memberships::institution_id.eq(to))
+        .select(institution_memberships::user_id);
+    diesel::delete(
+        institution_memberships::table.filter(
+            institution_memberships::institution_id
+                .eq(from)
+                .and(institution_memberships::user_id.eq_any(survivor_members)),
+        ),
+    )
+    .execute(conn)?;
+    diesel::update(
+        institution_memberships::table.filter(institution_memberships::institution_id.eq(from)),
+    )
+    .set(institution_memberships::institution_id.eq(to))
+    .execute(conn)?;
+
+    let survivor_subscribers = institution_digests::table
+        .filter(institution_digests::institution_id.eq(to))
+        .select(institution_digests::user_id);
+    diesel::delete(
+        institution_digests::table.filter(
+            institution_digests::institution_id
+                .eq(from)
+                .and(institution_digests::user_id.eq_any(survivor_subscribers)),
+        ),
+    )
+    .execute(conn)?;
+    diesel::update(institution_digests::table.filter(institution_digests::institution_id.eq(from)))
+        .set(institution_digests::institution_id.eq(to))
+        .execute(conn)?;
+
+    // Pickup points of a campus both have move to the
+    // campus of the surviving institution
+    let duplicate_campuses = campuses::table
+        .filter(campuses::institution_id.eq(from))
+        .select((campuses::id, campuses::name))
+        .load::<(i32, String)>(conn)?;
+    for (campus_id, campus_name) in duplicate_campuses {
+        let same_campus = campuses::table
+            .filter(
+                campuses::institution_id
+                    .eq(to)
+                    .and(campuses::name.eq(&campus_name)),
+            )
+            .select(campuses::id)
+            .first::<i32>(conn)
+            .optional()?;
+        match same_campus {
+            Some(same_campus) => {
+                diesel::update(pickup_points::table.filter(pickup_points::campus_id.eq(campus_id)))
+                    .set(pickup_points::campus_id.eq(same_campus))
+                    .execute(conn)?;
+                diesel::delete(campuses::table.find(campus_id)).execute(conn)?;
+            }
+            None => {
+                diesel::update(campuses::table.find(campus_id))
+                    .set(campuses::institution_id.eq(to))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    diesel::update(pickup_points::table.filter(pickup_points::institution_id.eq(from)))
+        .set(pickup_points::institution_id.eq(to))
+        .execute(conn)?;
+
+    // Of a domain both proposed, the verified one is kept
+    let duplicate_verified = institution_domains::table
+        .filter(
+            institution_domains::institution_id
+                .eq(from)
+                .and(institution_domains::verified_at.is_not_null()),
+        )
+        .select(institution_domains::domain);
+    diesel::delete(
+        institution_domains::table.filter(
+            institution_domains::institution_id
+                .eq(to)
+                .and(institution_domains::domain.eq_any(duplicate_verified)),
+        ),
+    )
+    .execute(conn)?;
+    let survivor_domains = institution_domains::table
+        .filter(institution_domains::institution_id.eq(to))
+        .select(institution_domains::domain);
+    diesel::delete(
+        institution_domains::table.filter(
+            institution_domains::institution_id
+                .eq(from)
+                .and(institution_domains::domain.eq_any(survivor_domains)),
+        ),
+    )
+    .execute(conn)?;
+    diesel::update(institution_domains::table.filter(institution_domains::institution_id.eq(from)))
+        .set(institution_domains::institution_id.eq(to))
+        .execute(conn)?;
+    diesel::update(
+        registration_formats::table.filter(registration_formats::institution_id.eq(from)),
+    )
+    .set(registration_formats::institution_id.eq(to))
+    .execute(conn)?;
+    diesel::update(saved_searches::table.filter(saved_searches::institution_id.eq(from)))
+        .set(saved_searches::institution_id.eq(to))
+        .execute(conn)?;
+    diesel::update(
+        webhook_subscriptions::table.filter(webhook_subscriptions::institution_id.eq(from)),
+    )
+    .set(webhook_subscriptions::institution_id.eq(to))
+    .execute(conn)?;
+
+    Ok((moved_ids, moved_claims, moved_profiles))
+}
@@ -1,2 +1,9 @@
+pub mod digests;
+pub mod domains;
+pub mod import;
+pub mod memberships;
+pub mod merge;
 pub mod models;
+pub mod pickup;
+pub mod search;
 pub mod views;
@@ -14,13 +14,16 @@ use std::borrow::Cow;
 use crate::{
     apps::{
         ids::models::ClaimableIdentification, ids::validators::validate_str_len,
-        profiles::models::Profile, user::models::User, user::utils::from_timestamp,
+        jobs::models::JobKind, profiles::models::Profile, user::models::User,
+        user::utils::from_timestamp,
     },
     diesel_cfg::{config::connect_to_db, schema::institutions},
     errors::error::ResError,
     similarity::cosine::cosine_similarity,
 };
 
+use super::{domains::InstitutionDomain, memberships::Membership};
+
 /// Insertable institution model
 #[derive(Validate, Deserialize, Insertable)]
 #[table_name = "institutions"]
@@ -48,7 +51,7 @@ pub struct NewInstitution<'a> {
 #[derive(Queryable, Identifiable, AsChangeset, Serialize, Deserialize)]
 #[table_name = "institutions"]
 pub struct Institution {
-    id: i32,
+    pub id: i32,
     pub name: String,
     pub town: String,
     pub country: String,
@@ -75,13 +78,14 @@ pub struct UpdatableInstitution<'a> {
     pub postal_address: Option<Cow<'a, str>>,
 }
 
-/// Comparison object for changing
+/// Comparison object for suggesting
 /// a User's institution.
 ///
-/// The name and email fields are used
-/// by the function match_institution to
-/// verify if the User's email is a close
-/// match to the institution they wish to change to.
+/// The name and email fields are used by
+/// `email_institution_sim` to guess whether the
+/// User's email is a close match to an institution.
+/// The guess is only a suggestion. Joining an institution
+/// takes a verified email on one of its domains.
 #[derive(Deserialize, Validate)]
 #[serde(deny_unknown_fields)]
 pub struct ChangeableInst<'a> {
@@ -92,6 +96,28 @@ pub struct ChangeableInst<'a> {
     pub email: Cow<'a, str>,
 }
 
+/// An institution the emails of a User suggest
+#[derive(Serialize, Deserialize)]
+pub struct InstitutionSuggestion {
+    pub institution: Institution,
+    /// The email that suggests the institution
+    pub email: String,
+    /// Whether the email is on a verified domain of the
+    /// institution, so the User can join it
+    pub verified_domain: bool,
+}
+
+/// A created institution, with the existing institutions
+/// it may duplicate
+#[derive(Serialize, Deserialize)]
+pub struct CreatedInstitution {
+    #[serde(flatten)]
+    pub institution: Institution,
+
+    /// Institutions of the country with near-identical names
+    pub similar_institutions: Vec<Institution>,
+}
+
 /// Parsable JSON object for changing
 /// the User's institution's requests.
 #[derive(Deserialize, Validate)]
@@ -104,7 +130,12 @@ pub struct UpdatableJsonUserInsitution {
 
 impl<'a> NewInstitution<'a> {
     /// Saves new Institution to the insitutions table.
-    pub async fn save(&self) -> Result<Institution, ResError> {
+    ///
+    /// A moderator creating it becomes its first admin. Institutions
+    /// created by other users get their first admin from a moderator,
+    /// so no one takes over an institution, and its email domains,
+    /// by creating it first.
+    pub async fn save(&self, creator: &User) -> Result<Institution, ResError> {
         use crate::diesel_cfg::schema::institutions::dsl::{
             country, institutions as _institutions, name, town,
         };
@@ -120,13 +151,36 @@ impl<'a> NewInstitution<'a> {
             return Err(ResError::new("Institution already exists".into(), 409));
         }
 
-        let created_institution = diesel::insert_into(institutions::table)
-            .values(self)
-            .get_result::<Institution>(&connect_to_db())?;
+        let conn = connect_to_db();
+        let created_institution = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let institution = diesel::insert_into(institutions::table)
+                .values(self)
+                .get_result::<Institution>(&conn)?;
+            if creator.is_moderator() {
+                Membership::found_on(&conn, institution.id, creator)?;
+            }
+            Ok(institution)
+        })?;
         Ok(created_institution)
     }
 }
 
+impl CreatedInstitution {
+    /// Finds the institutions the created one may duplicate
+    pub async fn with_similar(institution: Institution) -> Result<Self, ResError> {
+        let similar_institutions =
+            Institution::near_duplicates(&institution.name, &institution.country)
+                .await?
+                .into_iter()
+                .filter(|similar| similar.id != institution.id)
+                .collect();
+        Ok(Self {
+            institution,
+            similar_institutions,
+        })
+    }
+}
+
 impl Institution {
     /// Retrives all Insitutions the database.
     pub fn get_all() -> Result<Vec<Institution>, ResError> {
@@ -150,6 +204,44 @@ impl Institution {
             .get_result::<Institution>(&connect_to_db())?)
     }
 
+    /// Suggests institutions to the User from their verified emails.
+    ///
+    /// Institutions with a verified domain matching an email come
+    /// first, then those whose names resemble the email domains.
+    pub async fn suggest_for(usr: &User) -> Result<Vec<InstitutionSuggestion>, ResError> {
+        let emails = User::all_emails(usr.id).await?;
+        let mut verified = vec![];
+        let mut resembling = vec![];
+
+        for institution in Self::get_all()? {
+            let domains = InstitutionDomain::verified_for(institution.id)?;
+            if let Some(email) = emails
+                .iter()
+                .find(|email| domains.iter().any(|d| d.covers(email)))
+            {
+                verified.push(InstitutionSuggestion {
+                    email: email.clone(),
+                    institution,
+                    verified_domain: true,
+                });
+                continue;
+            }
+
+            for email in &emails {
+                if ChangeableInst::email_institution_sim(&institution.name, email, false).await {
+                    resembling.push(InstitutionSuggestion {
+                        email: email.clone(),
+                        institution,
+                        verified_domain: false,
+                    });
+                    break;
+                }
+            }
+        }
+        verified.append(&mut resembling);
+        Ok(verified)
+    }
+
     /// Changes the insitution of a User whose Id
     /// is passed in.
     pub async fn change_user_institution(
@@ -176,75 +268,73 @@ impl Institution {
             }
         }
 
-        let all_user_emails: Vec<String> = User::all_emails(user.id).await?;
-
         let new_insitution: Institution = Self::find_by_pk(updatable_inst.institution_id).await?;
 
-        let mut matched_institution = false;
-        for email in all_user_emails {
-            let changeable_inst: ChangeableInst<'_> = ChangeableInst {
-                name: Cow::from(&new_insitution.name),
-                email: Cow::from(&email),
-            };
-            if changeable_inst.is_match().await? {
-                matched_institution = true;
-                break;
-            }
+        // Membership takes a verified email on a verified domain
+        // of the institution
+        let domains = InstitutionDomain::verified_for(new_insitution.id)?;
+        if domains.is_empty() {
+            return Err(ResError::new(
+                format!(
+                    "Institution {} has no verified email domains yet. Its admins can propose one for a moderator to verify",
+                    new_insitution.name
+                ),
+                403,
+            ));
         }
-
-        if !matched_institution {
+        if InstitutionDomain::verified_email_on(&user, &domains)?.is_none() {
             return Err(ResError::new(
                 format!(
-                    "User {} does not have an email matching institution {}",
-                    user.username, new_insitution.name
+                    "Verify an email on {} to join institution {}",
+                    domains
+                        .iter()
+                        .map(|d| d.domain.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    new_insitution.name
                 ),
-                400,
+                403,
             ));
         }
 
-        match ClaimableIdentification::belonging_to_me(&user) {
-            Err(_) => Err(ResError::new(
+        let claims = ClaimableIdentification::belonging_to_me(&user)?;
+        if claims.is_empty() {
+            return Err(ResError::new(
                 "User has no identification claim. Create claim first to change institution".into(),
                 400,
-            )),
-
-            Ok(mut claim) => {
-                // Alter db institution refs then:
-                // Update user profile, claims, and Ids
-                user_profile.institution_id = Some(updatable_inst.institution_id);
-                user_profile.save_changes::<Profile>(&connect_to_db())?;
+            ));
+        }
 
-                claim.institution_id = Some(updatable_inst.institution_id);
-                claim.save_changes::<ClaimableIdentification>(&connect_to_db())?;
-                Ok(new_insitution)
-            }
+        // Alter db institution refs then:
+        // Update user profile, claims, and Ids
+        user_profile.institution_id = Some(updatable_inst.institution_id);
+        user_profile.save_changes::<Profile>(&connect_to_db())?;
+        Membership::join(&user, updatable_inst.institution_id)?;
+
+        // Only active claims of institution-issued documents move along
+        for mut claim in claims
+            .into_iter()
+            .filter(|c| c.document_type.is_institutional() && c.status.is_active())
+        {
+            // Registration numbers follow the formats of the new institution
+            claim.institution_id = Some(updatable_inst.institution_id);
+            let conn = connect_to_db();
+            conn.transaction::<_, ResError, _>(|| {
+                let claim = claim
+                    .save_changes::<ClaimableIdentification>(&conn)?
+                    .normalize_registration_no(&conn)?;
+                claim.refresh_blocks_on(&conn)?;
+
+                // Drops the matches from the old institution
+                JobKind::MatchClaim { claim_id: claim.id }.enqueue_on(&conn)?;
+                Ok(())
+            })?;
         }
+        Ok(new_insitution)
     }
 }
 
 impl<'a> ChangeableInst<'a> {
-    /// Attempts to acertain whether the email belongs to the
-    /// institution.
-    ///
-    /// This is experimental, and relies on character similarity metrics,
-    /// which check if alphaneumerics or words on the email domain
-    /// having letters(Initials) or words identical to those of the
-    /// institution's name to a certain extent.
-    ///
-    /// Cosine Similarity threshold - .75 (For initials)
-    async fn is_match(&self) -> Result<bool, ResError> {
-        let match_ = Self::email_institution_sim(&self.name, &self.email, false).await;
-
-        if !match_ {
-            Err(ResError {
-                msg: "Email doesn't seem to belong to this institution".into(),
-                status: 403,
-            })
-        } else {
-            Ok(match_)
-        }
-    }
-
     /// Checks if an institution email belongs to the institution
     /// of a given name.
     ///
@@ -0,0 +1,450 @@
+//! Campuses and pickup points
+//!
+//! Institutions have campuses, and official pickup points such as a
+//! security desk or a library, where found IDs are handed in and
+//! collected. A pickup point has opening hours, so owners know when
+//! to collect their IDs.
+//!
+//! IDs posted at a pickup point take its name as their location.
+
+use super::memberships::{InstitutionRole, Membership};
+use crate::{
+    apps::{
+        ids::validators::regexes,
+        user::{models::User, utils::from_timestamp},
+    },
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{campuses, pickup_points},
+    },
+    errors::error::ResError,
+};
+
+use chrono::{NaiveDateTime, NaiveTime};
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::{ValidationError, ValidationErrors};
+use validator_derive::Validate;
+
+use std::borrow::Cow;
+
+/// Days of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Day {
+    fn label(self) -> &'static str {
+        match self {
+            Day::Mon => "Mon",
+            Day::Tue => "Tue",
+            Day::Wed => "Wed",
+            Day::Thu => "Thu",
+            Day::Fri => "Fri",
+            Day::Sat => "Sat",
+            Day::Sun => "Sun",
+        }
+    }
+}
+
+/// Hours a pickup point is open on a day.
+///
+/// Times are `HH:MM`, in the local time of the institution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpeningPeriod {
+    pub day: Day,
+    pub opens: String,
+    pub closes: String,
+}
+
+impl OpeningPeriod {
+    /// Ensures the period opens before it closes
+    fn check(&self) -> Result<(), ValidationError> {
+        let parse = |time: &str| NaiveTime::parse_from_str(time, "%H:%M");
+
+        match (parse(&self.opens), parse(&self.closes)) {
+            (Ok(opens), Ok(closes)) if opens < closes => Ok(()),
+            (Ok(_), Ok(_)) => Err(ValidationError::new("should open before closing")),
+            _ => Err(ValidationError::new("should be times of the form HH:MM")),
+        }
+    }
+}
+
+/// Queryable model of the campuses table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "campuses"]
+pub struct Campus {
+    pub id: i32,
+    pub institution_id: i32,
+    pub name: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// A campus to add to an institution
+#[derive(Insertable, Deserialize, Validate)]
+#[table_name = "campuses"]
+#[serde(deny_unknown_fields)]
+pub struct NewCampus<'a> {
+    #[serde(skip_deserializing)]
+    pub institution_id: i32,
+
+    #[validate(regex(
+        path = "regexes::LOCATION_REGEX",
+        message = "should have letters, digits or -_`"
+    ))]
+    pub name: Cow<'a, str>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Queryable model of the pickup_points table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "pickup_points"]
+pub struct PickupPoint {
+    pub id: i32,
+    pub institution_id: i32,
+    pub campus_id: Option<i32>,
+
+    /// Becomes the location name of the IDs posted at the point
+    pub name: String,
+    pub directions: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+
+    /// A list of `OpeningPeriod`s
+    pub opening_hours: Value,
+    pub is_active: bool,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+}
+
+/// A pickup point to add to an institution
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct NewPickupPoint<'a> {
+    #[validate(regex(
+        path = "regexes::LOCATION_REGEX",
+        message = "should have letters, digits or -_`"
+    ))]
+    pub name: Cow<'a, str>,
+    pub campus_id: Option<i32>,
+
+    #[validate(length(max = 500, message = "should be up to 500 letters long"))]
+    pub directions: Option<Cow<'a, str>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+
+    #[serde(default)]
+    pub opening_hours: Vec<OpeningPeriod>,
+}
+
+/// Changes to a pickup point
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdatablePickupPoint<'a> {
+    #[validate(length(max = 500, message = "should be up to 500 letters long"))]
+    pub directions: Option<Cow<'a, str>>,
+    pub opening_hours: Option<Vec<OpeningPeriod>>,
+
+    /// Inactive points take no new IDs
+    pub is_active: Option<bool>,
+}
+
+/// Filter of pickup point listings
+#[derive(Deserialize)]
+pub struct PickupPointQuery {
+    /// List points that no longer take IDs too
+    #[serde(default)]
+    pub include_inactive: bool,
+}
+
+/// Changeset of the updatable fields of a pickup point
+#[derive(AsChangeset)]
+#[table_name = "pickup_points"]
+struct PickupPointChangeset<'a> {
+    directions: Option<&'a str>,
+    opening_hours: Option<Value>,
+    is_active: Option<bool>,
+}
+
+/// Ensures coordinates are given together, and are within range
+fn check_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> Result<(), ResError> {
+    match (latitude, longitude) {
+        (None, None) => Ok(()),
+        (Some(lat), Some(lon)) => {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                return Err(ResError::new(
+                    "Give a latitude within ±90 and a longitude within ±180".into(),
+                    400,
+                ));
+            }
+            Ok(())
+        }
+        _ => Err(ResError::new(
+            "Give the latitude and longitude together".into(),
+            400,
+        )),
+    }
+}
+
+/// Validates every opening period
+fn check_hours(periods: &[OpeningPeriod]) -> Result<(), ResError> {
+    for period in periods {
+        if let Err(e) = period.check() {
+            let mut errors = ValidationErrors::new();
+            errors.add("opening_hours", e);
+            return Err(ResError::new(errors.to_string(), 400));
+        }
+    }
+    Ok(())
+}
+
+impl<'a> NewCampus<'a> {
+    /// Adds the campus to the institution
+    pub fn save(&mut self, inst_id: i32) -> Result<Campus, ResError> {
+        check_coordinates(self.latitude, self.longitude)?;
+        self.institution_id = inst_id;
+
+        diesel::insert_into(campuses::table)
+            .values(&*self)
+            .on_conflict_do_nothing()
+            .get_result::<Campus>(&connect_to_db())
+            .optional()?
+            .ok_or_else(|| ResError::new(format!("Campus {} already exists", self.name), 409))
+    }
+}
+
+impl Campus {
+    /// Retrieves the campuses of the institution
+    pub fn for_institution(inst_id: i32) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::campuses::dsl::*;
+
+        Ok(campuses
+            .filter(institution_id.eq(inst_id))
+            .order(name.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+}
+
+impl<'a> NewPickupPoint<'a> {
+    /// Adds the pickup point to the institution
+    pub fn save(&self, inst_id: i32) -> Result<PickupPoint, ResError> {
+        use crate::diesel_cfg::schema::pickup_points::dsl::*;
+
+        check_hours(&self.opening_hours)?;
+        check_coordinates(self.latitude, self.longitude)?;
+        if let Some(campus) = self.campus_id {
+            let campus_institution = campuses::table
+                .find(campus)
+                .select(campuses::institution_id)
+                .first::<i32>(&connect_to_db())
+                .optional()?;
+            if campus_institution != Some(inst_id) {
+                return Err(ResError::new(
+                    "The campus is not of this institution".into(),
+                    400,
+                ));
+            }
+        }
+
+        Ok(diesel::insert_into(pickup_points)
+            .values((
+                institution_id.eq(inst_id),
+                campus_id.eq(self.campus_id),
+                name.eq(&self.name),
+                directions.eq(&self.directions),
+                latitude.eq(self.latitude),
+                longitude.eq(self.longitude),
+                // A plain struct of strings always serializes
+                opening_hours.eq(serde_json::to_value(&self.opening_hours).unwrap()),
+            ))
+            .get_result::<PickupPoint>(&connect_to_db())?)
+    }
+}
+
+impl PickupPoint {
+    /// Finds a pickup point by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::pickup_points::dsl::pickup_points;
+
+        Ok(pickup_points.find(pk).first::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the pickup points of the institution.
+    /// Inactive points are left out unless asked for.
+    pub fn for_institution(inst_id: i32, include_inactive: bool) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::pickup_points::dsl::*;
+
+        let mut query = pickup_points
+            .filter(institution_id.eq(inst_id))
+            .into_boxed();
+        if !include_inactive {
+            query = query.filter(is_active.eq(true));
+        }
+        Ok(query.order(name.asc()).load::<Self>(&connect_to_db())?)
+    }
+
+    /// Updates the pickup point, if the user is an admin
+    /// of its institution
+    pub fn update(&self, usr: &User, data: &UpdatablePickupPoint) -> Result<Self, ResError> {
+        Membership::require(usr, Some(self.institution_id), InstitutionRole::Admin)?;
+        if let Some(hours) = &data.opening_hours {
+            check_hours(hours)?;
+        }
+
+        Ok(diesel::update(self)
+            .set(&PickupPointChangeset {
+                directions: data.directions.as_deref(),
+                // A plain struct of strings always serializes
+                opening_hours: data
+                    .opening_hours
+                    .as_ref()
+                    .map(|hours| serde_json::to_value(hours).unwrap()),
+                is_active: data.is_active,
+            })
+            .get_result::<Self>(&connect_to_db())?)
+    }
+
+    /// Ensures an ID of the institution can be posted at the point.
+    ///
+    /// IDs of no institution can be posted at any active point,
+    /// and take its institution.
+    pub fn takes_ids_of(&self, institution: Option<i32>) -> Result<(), ResError> {
+        if !self.is_active {
+            return Err(ResError::new(
+                format!("{} no longer takes IDs", self.name),
+                400,
+            ));
+        }
+        if institution.map_or(false, |inst_id| inst_id != self.institution_id) {
+            return Err(ResError::new(
+                "The pickup point is not of the institution of the ID".into(),
+                400,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The opening periods of the point
+    pub fn hours(&self) -> Vec<OpeningPeriod> {
+        serde_json::from_value(self.opening_hours.clone()).unwrap_or_default()
+    }
+
+    /// Where the point is: its name, and campus if any
+    pub fn place(&self) -> Result<String, ResError> {
+        let campus = match self.campus_id {
+            Some(campus) => campuses::table
+                .find(campus)
+                .select(campuses::name)
+                .first::<String>(&connect_to_db())
+                .optional()?,
+            None => None,
+        };
+
+        Ok(match campus {
+            Some(campus) => format!("{}, {}", self.name, campus),
+            None => self.name.clone(),
+        })
+    }
+
+    /// Where and when IDs can be collected from the point
+    pub fn directions_to(&self) -> Result<String, ResError> {
+        let mut text = self.place()?;
+        let hours = hours_summary(&self.hours());
+
+        if !hours.is_empty() {
+            text.push_str(&format!(". Open {}", hours));
+        }
+        if let Some(directions) = &self.directions {
+            text.push_str(&format!(". {}", directions));
+        }
+        Ok(text)
+    }
+}
+
+/// Summarizes opening hours, joining consecutive days
+/// of the same hours
+///
+/// ```rust
+/// use got_ya_id::apps::institution::pickup::{hours_summary, Day, OpeningPeriod};
+///
+/// let period = |day, opens: &str, closes: &str| OpeningPeriod {
+///     day,
+///     opens: opens.into(),
+///     closes: closes.into(),
+/// };
+/// let hours = vec![
+///     period(Day::Sat, "09:00", "12:00"),
+///     period(Day::Mon, "08:00", "17:00"),
+///     period(Day::Tue, "08:00", "17:00"),
+///     period(Day::Wed, "08:00", "17:00"),
+///     period(Day::Thu, "08:00", "13:00"),
+///     period(Day::Thu, "14:00", "17:00"),
+/// ];
+///
+/// assert_eq!(
+///     hours_summary(&hours),
+///     "Mon-Wed 08:00-17:00; Thu 08:00-13:00, 14:00-17:00; Sat 09:00-12:00"
+/// );
+/// assert_eq!(hours_summary(&[]), "");
+/// ```
+pub fn hours_summary(periods: &[OpeningPeriod]) -> String {
+    let mut periods = periods.to_vec();
+    periods.sort_by(|a, b| (a.day, &a.opens).cmp(&(b.day, &b.opens)));
+
+    // The hours of each open day
+    let mut days: Vec<(Day, String)> = vec![];
+    for period in &periods {
+        let span = format!("{}-{}", period.opens, period.closes);
+        match days.last_mut() {
+            Some((day, hours)) if *day == period.day => {
+                hours.push_str(", ");
+                hours.push_str(&span);
+            }
+            _ => days.push((period.day, span)),
+        }
+    }
+
+    // Runs of consecutive days open the same hours
+    let mut runs: Vec<(Day, Day, &str)> = vec![];
+    for (day, hours) in &days {
+        match runs.last_mut() {
+            Some((_, last, run_hours))
+                if *run_hours == hours.as_str() && *last as u8 + 1 == *day as u8 =>
+            {
+                *last = *day
+            }
+            _ => runs.push((*day, *day, hours.as_str())),
+        }
+    }
+
+    runs.iter()
+        .map(|(first, last, hours)| {
+            if first == last {
+                format!("{} {}", first.label(), hours)
+            } else {
+                format!("{}-{} {}", first.label(), last.label(), hours)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
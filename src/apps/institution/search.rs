@@ -0,0 +1,303 @@
+//! Institution search
+//!
+//! Institutions are found by a prefix of their names, as typed in an
+//! autocomplete, or by a loose resemblance, as when misspelt or
+//! abbreviated. Names are compared in a normal form: lowercase, with
+//! common abbreviations spelt out and filler words dropped, so
+//! `Univ. of Nairobi` reads the same as `University of Nairobi`.
+//!
+//! Postgres picks the candidates, by a trigram index of the names:
+//! names with words starting with those of the query, and failing
+//! enough of them, the names closest to the query. Only those are
+//! ranked here.
+//!
+//! The same comparison warns of near-duplicates of new institutions.
+
+use super::models::Institution;
+use crate::{
+    diesel_cfg::config::connect_to_db, errors::error::ResError,
+    similarity::cosine::cosine_similarity,
+};
+
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{Bool, Double, Text},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Number of results given if no limit is asked for
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// Most results given for a search
+const MAX_SEARCH_LIMIT: usize = 50;
+
+/// Most institutions ranked for a search, of those
+/// matched by prefix and of those matched loosely each
+const MAX_CANDIDATES: i64 = 200;
+
+/// Least resemblance of a misspelt query to a name it finds
+const FUZZY_THRESHOLD: f64 = 0.8;
+
+/// Least resemblance of two names of the same country
+/// for the institutions to be taken as duplicates
+const DUPLICATE_THRESHOLD: f64 = 0.9;
+
+/// Words left out of compared names
+const FILLER_WORDS: &[&str] = &["of", "the", "and", "for", "at"];
+
+/// Abbreviations spelt out in compared names
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("univ", "university"),
+    ("uni", "university"),
+    ("coll", "college"),
+    ("inst", "institute"),
+    ("tech", "technology"),
+    ("poly", "polytechnic"),
+    ("intl", "international"),
+    ("natl", "national"),
+    ("sch", "school"),
+    ("st", "saint"),
+];
+
+/// Search of institutions
+#[derive(Deserialize)]
+pub struct InstitutionSearchQuery {
+    /// The name, or the start of it
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+/// An institution found by a search, and how well it matched
+#[derive(Serialize, Deserialize)]
+pub struct InstitutionHit {
+    pub institution: Institution,
+
+    /// Up to 1. Prefix matches rank above fuzzy ones
+    pub score: f64,
+}
+
+impl Institution {
+    /// Finds institutions by name, best matches first.
+    ///
+    /// Names starting with the query rank first, then names with
+    /// words starting with each word of the query, then names that
+    /// resemble it.
+    pub async fn search(query: &InstitutionSearchQuery) -> Result<Vec<InstitutionHit>, ResError> {
+        // Abbreviations are matched as typed too, as the start
+        // of a longer word, e.g `inst` of `institution`
+        let typed = name_words(&query.q, false);
+        let words = name_words(&query.q, true);
+        if words.is_empty() {
+            return Err(ResError::new(
+                "Give a name, or the start of it, to search".into(),
+                400,
+            ));
+        }
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .min(MAX_SEARCH_LIMIT)
+            .max(1);
+
+        let mut hits = vec![];
+        for institution in Self::prefix_candidates(&typed, &words)? {
+            let name = name_words(&institution.name, true);
+            if let Some(score) = rank(&typed, &words, &name).await {
+                hits.push(InstitutionHit { institution, score });
+            }
+        }
+        // Loose matches rank below every prefix match
+        if hits.len() < limit {
+            let found = hits
+                .iter()
+                .map(|hit| hit.institution.id)
+                .collect::<Vec<_>>();
+            for institution in Self::fuzzy_candidates(&words, found)? {
+                let name = name_words(&institution.name, true);
+                if let Some(score) = rank(&typed, &words, &name).await {
+                    hits.push(InstitutionHit { institution, score });
+                }
+            }
+        }
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.institution.name.cmp(&b.institution.name))
+        });
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// Institutions with a word starting with each word of the
+    /// query, as typed or spelt out. Names starting with the
+    /// query come first.
+    fn prefix_candidates(typed: &[String], words: &[String]) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institutions::dsl::institutions;
+
+        let mut query = institutions.into_boxed();
+        for (typed_word, word) in typed.iter().zip(words) {
+            query = query.filter(
+                sql::<Bool>("lower(name) ~ ")
+                    .bind::<Text, _>(format!(r"\m({})", word_forms(typed_word, word))),
+            );
+        }
+        let starts = format!(
+            r"^\W*(({})\W+)*({})",
+            FILLER_WORDS.join("|"),
+            word_forms(&typed[0], &words[0])
+        );
+
+        Ok(query
+            .order((
+                sql::<Bool>("lower(name) ~ ").bind::<Text, _>(starts).desc(),
+                sql::<Text>("lower(name)").asc(),
+            ))
+            .limit(MAX_CANDIDATES)
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Institutions other than those found, whose names are
+    /// closest to the query
+    fn fuzzy_candidates(words: &[String], found: Vec<i32>) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institutions::dsl::{id, institutions};
+
+        Ok(institutions
+            .filter(id.ne_all(found))
+            .order(sql::<Double>("lower(name) <->> ").bind::<Text, _>(words.join(" ")))
+            .limit(MAX_CANDIDATES)
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Finds institutions of the country whose names are
+    /// near-duplicates of the given name
+    pub async fn near_duplicates(name: &str, country: &str) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institutions::dsl::institutions;
+
+        let candidates = institutions
+            .filter(sql::<Text>("lower(trim(country))").eq(country.trim().to_lowercase()))
+            .load::<Self>(&connect_to_db())?;
+
+        let words = name_words(name, true);
+        let mut duplicates = vec![];
+        for institution in candidates {
+            let other = name_words(&institution.name, true);
+            if name_similarity(&words, &other).await >= DUPLICATE_THRESHOLD {
+                duplicates.push(institution);
+            }
+        }
+        Ok(duplicates)
+    }
+}
+
+/// Puts an institution name in the form names are compared in
+///
+/// ```rust
+/// use got_ya_id::apps::institution::search::normalize_name;
+///
+/// assert_eq!(normalize_name("Univ. of Nairobi"), "university nairobi");
+/// assert_eq!(normalize_name("  The University of  NAIROBI "), "university nairobi");
+/// assert_eq!(normalize_name("Kenya Poly"), "kenya polytechnic");
+/// ```
+pub fn normalize_name(name: &str) -> String {
+    name_words(name, true).join(" ")
+}
+
+/// The words of a name in normal form, with
+/// abbreviations spelt out if asked to
+fn name_words(name: &str, expand: bool) -> Vec<String> {
+    lazy_static! {
+        static ref WORD: Regex = Regex::new(r"\w+").unwrap();
+    }
+    let name = name.to_lowercase();
+
+    WORD.find_iter(&name)
+        .map(|word| word.as_str())
+        .filter(|word| !FILLER_WORDS.contains(word))
+        .map(|word| {
+            ABBREVIATIONS
+                .iter()
+                .find(|(short, _)| expand && *short == word)
+                .map_or(word, |(_, long)| *long)
+                .to_string()
+        })
+        .collect()
+}
+
+/// The forms a word of the query may take in a name, as
+/// alternatives of a Postgres regular expression: as typed, spelt
+/// out, and as the abbreviations of words it starts
+///
+/// ```rust
+/// use got_ya_id::apps::institution::search::word_forms;
+///
+/// assert_eq!(word_forms("nai", "nai"), "nai");
+/// assert_eq!(word_forms("univ", "university"), "univ|university|uni\\M");
+/// ```
+pub fn word_forms(typed: &str, word: &str) -> String {
+    let mut forms = vec![typed.to_string()];
+    if word != typed {
+        forms.push(word.to_string());
+    }
+    for (short, long) in ABBREVIATIONS {
+        if *short != typed && (long.starts_with(typed) || long.starts_with(word)) {
+            forms.push(format!(r"{}\M", short));
+        }
+    }
+    forms.join("|")
+}
+
+/// How well a query matches the words of a name, if at all.
+///
+/// The query is given as typed, and with its abbreviations
+/// spelt out.
+async fn rank(typed: &[String], query: &[String], name: &[String]) -> Option<f64> {
+    let joined = name.join(" ");
+    if [typed, query]
+        .iter()
+        .any(|words| joined.starts_with(&words.join(" ")))
+    {
+        return Some(1.0);
+    }
+    if [typed, query].iter().any(|words| {
+        words
+            .iter()
+            .all(|q_word| name.iter().any(|word| word.starts_with(q_word.as_str())))
+    }) {
+        return Some(0.9);
+    }
+
+    let resemblance = resemblance(query, name).await;
+    if resemblance >= FUZZY_THRESHOLD {
+        // Below every prefix match
+        Some(0.8 * resemblance)
+    } else {
+        None
+    }
+}
+
+/// How much two names resemble each other, either way
+async fn name_similarity(a: &[String], b: &[String]) -> f64 {
+    resemblance(a, b).await.min(resemblance(b, a).await)
+}
+
+/// How much each of the words resembles its closest word
+/// of the name, on average
+async fn resemblance(words: &[String], name: &[String]) -> f64 {
+    if words.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+    for word in words {
+        let mut closest: f64 = 0.0;
+        for other in name {
+            closest = closest.max(cosine_similarity(word, other).await);
+        }
+        total += closest;
+    }
+    total / words.len() as f64
+}
@@ -1,14 +1,31 @@
 use crate::{
-    apps::user::models::User,
+    apps::{
+        ids::registration::{NewRegistrationFormat, RegistrationFormat},
+        jobs::models::JobKind,
+        user::models::User,
+    },
     core::response::{err, respond2 as respond},
+    errors::error::ResError,
     hashmap,
 };
 
-use super::models::{
-    Institution, NewInstitution, UpdatableInstitution, UpdatableJsonUserInsitution,
+use super::{
+    digests::{DigestSubscription, InstitutionDigest},
+    domains::{InstitutionDomain, NewInstitutionDomain},
+    import::{ImportFormat, ImportOptions, InstitutionRow},
+    memberships::{InstitutionRole, Membership, NewStaff},
+    merge::InstitutionMerge,
+    models::{
+        CreatedInstitution, Institution, NewInstitution, UpdatableInstitution,
+        UpdatableJsonUserInsitution,
+    },
+    pickup::{
+        Campus, NewCampus, NewPickupPoint, PickupPoint, PickupPointQuery, UpdatablePickupPoint,
+    },
+    search::InstitutionSearchQuery,
 };
 
-use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
+use actix_web::{http::header::CONTENT_TYPE, web, Error, HttpRequest, HttpResponse, Result};
 
 use validator::Validate;
 
@@ -29,9 +46,10 @@ use validator::Validate;
 /// ```
 ///
 /// To verify if a user belongs to the institution,
-/// we verify if any of the user's emails belong to the institution.
+/// we verify if any of the user's emails is on a verified
+/// email domain of the institution.
 ///
-/// So a user making this request should have a verirified
+/// So a user making this request should have a verified
 /// institutional email for use in identification and
 /// verification of institution membership.
 pub async fn change_institution(
@@ -54,6 +72,11 @@ pub async fn change_institution(
 
 /// Creates new Insitution
 ///
+/// A moderator creating it becomes its first admin. Otherwise a
+/// moderator appoints its first admin at `/institutions/{id}/staff`.
+/// Institutions of the country with near-identical names are listed
+/// in `similar_institutions`, to warn of duplicates.
+///
 /// # url:
 /// `/institutions`
 ///
@@ -79,10 +102,18 @@ pub async fn create_institution(
     if let Err(e) = new_insitution.validate() {
         return err("400", e).await;
     }
-    User::from_token(&req)?;
-    let insititution: Institution = new_insitution.save().await?;
-    let msg = hashmap!["status" => "201", "message" => "Success. Institution created"];
-    respond(msg, Some(insititution)).await
+    let user = User::from_token(&req)?;
+    let insititution: Institution = new_insitution.save(&user).await?;
+    let created = CreatedInstitution::with_similar(insititution).await?;
+
+    let msg = match (created.similar_institutions.is_empty(), user.is_moderator()) {
+        (true, true) => hashmap!["status" => "201", "message" => "Success. Institution created"],
+        (true, false) => hashmap!["status" => "201",
+            "message" => "Success. Institution created. A moderator will appoint its admins"],
+        (false, _) => hashmap!["status" => "201",
+            "message" => "Success. Institution created. Similar institutions exist, it may be a duplicate"],
+    };
+    respond(msg, Some(created)).await
 }
 
 /// Gets all institutions
@@ -101,6 +132,90 @@ pub async fn get_all_institutions(req: HttpRequest) -> Result<HttpResponse, Erro
     respond(msg, Some(institutions)).await
 }
 
+/// Imports institutions in bulk, from a registry in CSV or JSON.
+///
+/// Institutions of the same name, town and country are updated,
+/// the rest created. Each row is reported on. A bad row undoes
+/// the whole import unless `partial` is set. A `dry_run` saves
+/// nothing.
+///
+/// # url:
+/// `/institutions/import?dry_run=false&partial=false`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins
+///
+/// ## Example
+/// ```none
+/// curl -X POST -H "Authorization: Bearer $TOKEN" -H "Content-Type: text/csv" \
+///     --data-binary @universities.csv "localhost:8888/api/institutions/import?dry_run=true"
+/// ```
+///
+/// The CSV has a header row of
+/// `name,town,country,description,postal_address`.
+pub async fn import_institutions(
+    req: HttpRequest,
+    options: web::Query<ImportOptions>,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    if !user.is_admin() {
+        return Err(ResError::unauthorized().into());
+    }
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let format = match ImportFormat::detect(content_type) {
+        Some(format) => format,
+        None => {
+            return err(
+                "415",
+                "Post the institutions as text/csv or application/json".to_string(),
+            )
+            .await
+        }
+    };
+
+    let report = InstitutionRow::import(&body, format, &options)?;
+    let msg = if report.committed {
+        hashmap!["status" => "200", "message" => "Success. Institutions imported"]
+    } else if report.dry_run {
+        hashmap!["status" => "200", "message" => "Success. Dry run, nothing was saved"]
+    } else {
+        hashmap!["status" => "422",
+            "message" => "Some rows were left out, so nothing was saved. Fix them, or import partially"]
+    };
+    respond(msg, Some(report)).await
+}
+
+/// Searches institutions by name, best matches first.
+///
+/// Suits autocompletion: names starting with the query come first,
+/// then names resembling it, as when misspelt or abbreviated.
+///
+/// # url:
+/// `/institutions/search?q=univ of nai&limit=10`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn search_institutions(
+    req: HttpRequest,
+    query: web::Query<InstitutionSearchQuery>,
+) -> Result<HttpResponse, Error> {
+    User::from_token(&req)?;
+    let hits = Institution::search(&query).await?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Institutions found"];
+    respond(msg, Some(hits)).await
+}
+
 /// Retrives an Institution's detail.
 ///
 /// # url:
@@ -129,6 +244,7 @@ pub async fn get_institution_detail(
 /// `PUT`
 ///
 /// #### Authorization Required
+/// Admins of the institution, or moderators
 /// ## Request Data Example
 /// ```json
 /// {
@@ -147,10 +263,535 @@ pub async fn update_institution(
     if let Err(e) = new_data.validate() {
         return err("400", e).await;
     }
-    User::from_token(&req)?;
+    let user = User::from_token(&req)?;
 
     let mut insitution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(insitution.id), InstitutionRole::Admin)?;
     insitution = insitution.update(&new_data).await?;
     let msg = hashmap!["status" => "200", "message" => "Success. Institution updated"];
     respond(msg, Some(insitution)).await
 }
+
+/// Adds a registration number format to an Institution
+///
+/// Registration numbers of the institution matching `pattern` are
+/// saved in the `canonical` form. Existing numbers are normalized anew
+/// in the background, and matched again if their canonical form changed.
+///
+/// # url:
+/// `/institutions/{id}/registration_formats`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   pattern: "^([A-Z]{3})\\s*(\\d{3})[\\s-]*(\\d{4})[\\s/]*(\\d{4})$",
+///   canonical: "$1$2-$3/$4",
+///   checksum: null,
+///   description: "Undergraduate registration numbers"
+///   }
+/// ```
+pub async fn add_registration_format(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    mut data: web::Json<NewRegistrationFormat<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(institution.id), InstitutionRole::Admin)?;
+    data.institution_id = institution.id;
+    let format = data.save()?;
+    JobKind::RenormalizeRegistrationNos {
+        institution_id: institution.id,
+    }
+    .enqueue()?;
+
+    let msg = hashmap!["status" => "201", "message" => "Success. Registration format added"];
+    respond(msg, Some(format)).await
+}
+
+/// Retrieves the registration number formats of an Institution
+///
+/// # url:
+/// `/institutions/{id}/registration_formats`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_registration_formats(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    User::from_token(&req)?;
+    let formats = RegistrationFormat::for_institution(pk.into_inner())?;
+    let msg = hashmap!["status" => "200", "message" => "Success. Registration formats retrieved"];
+    respond(msg, Some(formats)).await
+}
+
+/// Subscribes to periodic emails of the IDs newly posted at an Institution.
+/// Changes the frequency of an existing subscription.
+///
+/// # url:
+/// `/institutions/{id}/digest`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   frequency: "daily | weekly"
+///   }
+/// ```
+pub async fn subscribe_to_digest(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<DigestSubscription>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    let digest = InstitutionDigest::subscribe(&user, institution.id, data.frequency)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Subscribed to institution digest"];
+    respond(msg, Some(digest)).await
+}
+
+/// Unsubscribes from the digests of an Institution
+///
+/// # url:
+/// `/institutions/{id}/digest`
+///
+/// # Method
+/// `DELETE`
+///
+/// #### Authorization Required
+pub async fn unsubscribe_from_digest(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    InstitutionDigest::unsubscribe(&user, pk.into_inner())?;
+
+    let msg =
+        hashmap!["status" => "200", "message" => "Success. Unsubscribed from institution digest"];
+    respond(msg, None::<()>).await
+}
+
+/// Retrieves the institution digests the user is subscribed to
+///
+/// # url:
+/// `/institutions/digests`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_digest_subscriptions(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let digests = InstitutionDigest::belonging_to_user(&user)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Institution digests retrieved"];
+    respond(msg, Some(digests)).await
+}
+
+/// Suggests institutions for the user to join, from their
+/// verified emails.
+///
+/// Suggestions on a verified domain of the institution
+/// can be joined. The rest only resemble the institution.
+///
+/// # url:
+/// `/institutions/suggestions`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_institution_suggestions(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let suggestions = Institution::suggest_for(&user).await?;
+
+    let msg =
+        hashmap!["status" => "200", "message" => "Success. Institution suggestions retrieved"];
+    respond(msg, Some(suggestions)).await
+}
+
+/// Proposes an email domain of an Institution
+///
+/// Once a moderator verifies the domain, users with a verified
+/// email on it can join the institution.
+///
+/// # url:
+/// `/institutions/{id}/domains`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   domain: "uonbi.ac.ke",
+///   include_subdomains: true
+///   }
+/// ```
+pub async fn add_institution_domain(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    mut data: web::Json<NewInstitutionDomain<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(institution.id), InstitutionRole::Admin)?;
+    let domain = data.save(&institution, &user)?;
+
+    let msg = hashmap!["status" => "201",
+        "message" => "Success. Domain added. A moderator will verify it"];
+    respond(msg, Some(domain)).await
+}
+
+/// Verifies that a proposed email domain belongs to an Institution
+///
+/// # url:
+/// `/institutions/{id}/domains/{domain_id}/verify`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Moderators
+pub async fn verify_institution_domain(
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let (institution_id, domain_id) = path.into_inner();
+    let domain = InstitutionDomain::verify(institution_id, domain_id, &user)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Domain verified"];
+    respond(msg, Some(domain)).await
+}
+
+/// Retrieves the email domains of an Institution, verified or not
+///
+/// # url:
+/// `/institutions/{id}/domains`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_institution_domains(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    User::from_token(&req)?;
+    let domains = InstitutionDomain::for_institution(pk.into_inner())?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Domains retrieved"];
+    respond(msg, Some(domains)).await
+}
+
+/// Removes an email domain of an Institution.
+/// Members who joined on it are kept.
+///
+/// # url:
+/// `/institutions/{id}/domains/{domain_id}`
+///
+/// # Method
+/// `DELETE`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+pub async fn remove_institution_domain(
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let (institution_id, domain_id) = path.into_inner();
+    Membership::require(&user, Some(institution_id), InstitutionRole::Admin)?;
+    let domain = InstitutionDomain::remove(institution_id, domain_id)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Domain removed"];
+    respond(msg, Some(domain)).await
+}
+
+/// Retrieves the staff of an Institution, admins first
+///
+/// # url:
+/// `/institutions/{id}/staff`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+/// Staff of the institution, or moderators
+pub async fn get_institution_staff(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let institution_id = pk.into_inner();
+    Membership::require(&user, Some(institution_id), InstitutionRole::Custodian)?;
+
+    let staff = Membership::staff_of(institution_id)?;
+    let msg = hashmap!["status" => "200", "message" => "Success. Staff retrieved"];
+    respond(msg, Some(staff)).await
+}
+
+/// Adds a user to the staff of an Institution, or changes their role.
+///
+/// # url:
+/// `/institutions/{id}/staff`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   email: "custodian@uonbi.ac.ke",
+///   role: "admin | custodian"
+///   }
+/// ```
+pub async fn invite_institution_staff(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<NewStaff<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(institution.id), InstitutionRole::Admin)?;
+    let membership = Membership::invite(institution.id, &data, &user)?;
+
+    let msg = hashmap!["status" => "201", "message" => "Success. Staff added"];
+    respond(msg, Some(membership)).await
+}
+
+/// Removes a user from the staff of an Institution
+///
+/// # url:
+/// `/institutions/{id}/staff/{user_id}`
+///
+/// # Method
+/// `DELETE`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+pub async fn remove_institution_staff(
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let (institution_id, user_id) = path.into_inner();
+    Membership::require(&user, Some(institution_id), InstitutionRole::Admin)?;
+
+    let membership = Membership::remove(institution_id, user_id)?;
+    let msg = hashmap!["status" => "200", "message" => "Success. Staff removed"];
+    respond(msg, Some(membership)).await
+}
+
+/// Adds a campus to an Institution
+///
+/// # url:
+/// `/institutions/{id}/campuses`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   name: "Chiromo",
+///   latitude: -1.2735,
+///   longitude: 36.8065
+///   }
+/// ```
+pub async fn add_campus(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    mut data: web::Json<NewCampus<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(institution.id), InstitutionRole::Admin)?;
+    let campus = data.save(institution.id)?;
+
+    let msg = hashmap!["status" => "201", "message" => "Success. Campus added"];
+    respond(msg, Some(campus)).await
+}
+
+/// Retrieves the campuses of an Institution
+///
+/// # url:
+/// `/institutions/{id}/campuses`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_campuses(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    User::from_token(&req)?;
+    let campuses = Campus::for_institution(pk.into_inner())?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Campuses retrieved"];
+    respond(msg, Some(campuses)).await
+}
+
+/// Adds a pickup point to an Institution.
+///
+/// IDs posted at the point take its name as their location.
+///
+/// # url:
+/// `/institutions/{id}/pickup_points`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   name: "Security Office",
+///   campus_id: 1,
+///   directions: "Ground floor, next to the main gate",
+///   opening_hours: [{day: "mon", opens: "08:00", closes: "17:00"}]
+///   }
+/// ```
+pub async fn add_pickup_point(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<NewPickupPoint<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let institution = Institution::find_by_pk(pk.into_inner()).await?;
+    Membership::require(&user, Some(institution.id), InstitutionRole::Admin)?;
+    let point = data.save(institution.id)?;
+
+    let msg = hashmap!["status" => "201", "message" => "Success. Pickup point added"];
+    respond(msg, Some(point)).await
+}
+
+/// Retrieves the pickup points of an Institution
+///
+/// # url:
+/// `/institutions/{id}/pickup_points?include_inactive=false`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authorization Required
+pub async fn get_pickup_points(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    query: web::Query<PickupPointQuery>,
+) -> Result<HttpResponse, Error> {
+    User::from_token(&req)?;
+    let points = PickupPoint::for_institution(pk.into_inner(), query.include_inactive)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Pickup points retrieved"];
+    respond(msg, Some(points)).await
+}
+
+/// Updates the directions or opening hours of a pickup point,
+/// or stops it taking new IDs
+///
+/// # url:
+/// `/institutions/{id}/pickup_points/{point_id}`
+///
+/// # Method
+/// `PUT`
+///
+/// #### Authorization Required
+/// Admins of the institution, or moderators
+pub async fn update_pickup_point(
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+    data: web::Json<UpdatablePickupPoint<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e).await;
+    }
+    let user = User::from_token(&req)?;
+    let (institution_id, point_id) = path.into_inner();
+
+    let point = PickupPoint::find_by_id(point_id)?;
+    if point.institution_id != institution_id {
+        return err("404", "Pickup point not found".to_string()).await;
+    }
+    let point = point.update(&user, &data)?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Pickup point updated"];
+    respond(msg, Some(point)).await
+}
+
+/// Merges a duplicate Institution into another, and removes it.
+///
+/// Its identifications, claims, profiles, staff and the rest
+/// move to the surviving institution, in one transaction.
+///
+/// # url:
+/// `/institutions/{id}/merge`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authorization Required
+/// Admins of both institutions, or moderators
+///
+/// ## Request Data Example
+/// ```json
+/// {
+///   into: 1
+///   }
+/// ```
+pub async fn merge_institution(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<InstitutionMerge>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    let duplicate = Institution::find_by_pk(pk.into_inner()).await?;
+    let report = duplicate.merge_into(data.into, &user).await?;
+
+    let msg = hashmap!["status" => "200", "message" => "Success. Institutions merged"];
+    respond(msg, Some(report)).await
+}
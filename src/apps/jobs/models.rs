@@ -0,0 +1,598 @@
+//! Background job queue models
+//!
+//! Jobs are persisted in the `jobs` table and picked by the
+//! workers in `jobs::worker`. Claiming uses `FOR UPDATE SKIP LOCKED`,
+//! so several workers (or server instances) can share the queue.
+
+use crate::{
+    apps::{
+        email::outbox::OutboxEmail,
+        ids::{
+            lifecycle::ExpiryReport,
+            models::{ClaimableIdentification, Identification},
+            ownership::OwnershipRequest,
+            registration::RegistrationFormat,
+            searches::SavedSearch,
+            sweep::SweepReport,
+            utils::{send_match_notification, send_ownership_notice},
+        },
+        institution::digests::InstitutionDigest,
+        messaging::{models::Message, utils::send_message_notice},
+        notifications::{
+            dispatcher::{deliver, send_digests},
+            models::Notification,
+            preferences::Channel,
+        },
+        user::utils::from_timestamp,
+        webhooks::models::WebhookDelivery,
+    },
+    core::mail::MailTransport,
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{job_schedules, jobs},
+    },
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{self, prelude::*, sql_types::Varchar, PgConnection};
+use serde::{Deserialize, Serialize};
+
+/// How long a claimed job is leased to a worker.
+///
+/// A `running` job whose lease has run out is assumed
+/// abandoned (e.g the worker died) and is picked again.
+const LEASE_SECONDS: i64 = 5 * 60;
+
+/// How long jobs going over every claim, user or upload
+/// are leased. See `JobKind::lease`
+const LONG_LEASE_SECONDS: i64 = 60 * 60;
+
+/// Base delay of the retry backoff, in seconds
+const BACKOFF_BASE_SECONDS: i64 = 30;
+
+/// Upper bound of the retry backoff, in seconds
+const BACKOFF_MAX_SECONDS: i64 = 60 * 60;
+
+/// Status of a queued job
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting to be picked once `run_at` is due
+    Queued,
+    /// Leased to a worker until `run_at`
+    Running,
+    /// Completed successfully
+    Done,
+    /// Gave up after `max_attempts`
+    Failed,
+}
+
+varchar_enum!(JobStatus {
+    Queued => "queued",
+    Running => "running",
+    Done => "done",
+    Failed => "failed"
+});
+
+/// The work a job carries out.
+///
+/// This is stored as the job `payload`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Matches a Claim against missing Identifications,
+    /// pruning its matches that no longer qualify
+    MatchClaim { claim_id: i32 },
+
+    /// Matches an Identification against existing Claims and saved searches
+    MatchId { identification_id: i32 },
+
+    /// Matches a batch of Identifications, as uploaded together
+    MatchIds { identification_ids: Vec<i32> },
+
+    /// Alerts the owner of a Claim to a matching Identification
+    SendNotification {
+        claim_id: i32,
+        identification_id: i32,
+    },
+
+    /// Recomputes the matches of all claims
+    RematchSweep,
+
+    /// Reminds owners of claims close to expiry,
+    /// and expires inactive claims
+    ExpireClaims,
+
+    /// Tells the parties to an ownership request of its status
+    SendOwnershipNotice { request_id: i32 },
+
+    /// Emails the recipient of a message
+    SendMessageNotice { message_id: i32 },
+
+    /// Delivers a notification over a webhook, or as a text
+    DeliverNotification {
+        notification_id: i32,
+        channel: Channel,
+    },
+
+    /// Sends emails held back for digests, or during quiet hours
+    SendDigests,
+
+    /// Posts an event to a webhook subscription
+    DeliverWebhook { delivery_id: i32 },
+
+    /// Sends an email from the outbox
+    SendEmail { email_id: i32 },
+
+    /// Emails the IDs newly posted at institutions to their subscribers
+    SendInstitutionDigests,
+
+    /// Recomputes the canonical registration numbers of an
+    /// institution, once its formats change
+    RenormalizeRegistrationNos { institution_id: i32 },
+}
+
+/// Queryable model of the jobs table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "jobs"]
+pub struct Job {
+    pub id: i64,
+    /// Name of the job type. Same as the payload `type`
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+
+    /// When the job is next due
+    #[serde(deserialize_with = "from_timestamp")]
+    pub run_at: NaiveDateTime,
+    pub last_error: Option<String>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+
+    /// Report of the completed job, for jobs that give one
+    pub result: Option<serde_json::Value>,
+}
+
+/// Last run of a periodic job
+///
+/// The row is locked while the scheduler of one server
+/// instance enqueues the job, so a due job is only
+/// enqueued once.
+#[derive(Queryable, Identifiable)]
+#[table_name = "job_schedules"]
+#[primary_key(name)]
+pub struct JobSchedule {
+    pub name: String,
+    pub last_run_at: Option<NaiveDateTime>,
+}
+
+/// Insertable job model
+#[derive(Insertable)]
+#[table_name = "jobs"]
+pub struct NewJob<'a> {
+    kind: &'a str,
+    payload: serde_json::Value,
+}
+
+impl JobKind {
+    /// Name of the job type as saved in the `kind` column
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::MatchClaim { .. } => "match_claim",
+            JobKind::MatchId { .. } => "match_id",
+            JobKind::MatchIds { .. } => "match_ids",
+            JobKind::SendNotification { .. } => "send_notification",
+            JobKind::RematchSweep => "rematch_sweep",
+            JobKind::ExpireClaims => "expire_claims",
+            JobKind::SendOwnershipNotice { .. } => "send_ownership_notice",
+            JobKind::SendMessageNotice { .. } => "send_message_notice",
+            JobKind::DeliverNotification { .. } => "deliver_notification",
+            JobKind::SendDigests => "send_digests",
+            JobKind::DeliverWebhook { .. } => "deliver_webhook",
+            JobKind::SendEmail { .. } => "send_email",
+            JobKind::SendInstitutionDigests => "send_institution_digests",
+            JobKind::RenormalizeRegistrationNos { .. } => "renormalize_registration_nos",
+        }
+    }
+
+    /// How long a worker running the job holds it.
+    ///
+    /// Sweeps over all claims or users, and batches of uploaded
+    /// IDs, can run for longer than a single job's lease, and
+    /// would be picked again by another worker while running.
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::jobs::models::JobKind;
+    /// use chrono::Duration;
+    ///
+    /// assert_eq!(JobKind::MatchClaim { claim_id: 1 }.lease(), Duration::minutes(5));
+    /// assert_eq!(JobKind::RematchSweep.lease(), Duration::hours(1));
+    /// ```
+    pub fn lease(&self) -> Duration {
+        match self {
+            JobKind::MatchIds { .. }
+            | JobKind::RematchSweep
+            | JobKind::ExpireClaims
+            | JobKind::SendDigests
+            | JobKind::SendInstitutionDigests
+            | JobKind::RenormalizeRegistrationNos { .. } => Duration::seconds(LONG_LEASE_SECONDS),
+            _ => Duration::seconds(LEASE_SECONDS),
+        }
+    }
+
+    /// Adds the job to the queue.
+    ///
+    /// The job becomes runnable immediately.
+    pub fn enqueue(&self) -> Result<Job, ResError> {
+        NewJob::from(self).save()
+    }
+
+    /// Adds the job to the queue on the given connection,
+    /// so it is only queued if a transaction running on
+    /// the connection commits
+    pub fn enqueue_on(&self, conn: &PgConnection) -> QueryResult<Job> {
+        diesel::insert_into(jobs::table)
+            .values(&NewJob::from(self))
+            .get_result::<Job>(conn)
+    }
+
+    /// Carries out the work of the job. Emails are handed
+    /// to the mail transport.
+    ///
+    /// # Returns
+    /// The report of sweeps and other periodic jobs, to be
+    /// kept with the completed job
+    pub async fn run(
+        &self,
+        mailer: &dyn MailTransport,
+    ) -> Result<Option<serde_json::Value>, ResError> {
+        match self {
+            JobKind::MatchClaim { claim_id } => {
+                let claim = ClaimableIdentification::find_by_id(*claim_id)?;
+                let (matched_idts, _) = claim.rematch().await?;
+
+                for idt in matched_idts {
+                    JobKind::SendNotification {
+                        claim_id: claim.id,
+                        identification_id: idt.id,
+                    }
+                    .enqueue()?;
+                }
+            }
+            JobKind::MatchId { identification_id } => {
+                match_identification(*identification_id).await?;
+            }
+            JobKind::MatchIds { identification_ids } => {
+                // A retry would alert the IDs matched before a failure
+                // twice, so failures are logged and the rest matched
+                for identification_id in identification_ids {
+                    match match_identification(*identification_id).await {
+                        Ok(()) => {}
+                        Err(e) if e.status == 404 => {
+                            warn!("MatchIds: Identification {} is gone", identification_id)
+                        }
+                        Err(e) => error!(
+                            "MatchIds: Error matching Identification {}: {}",
+                            identification_id, e.msg
+                        ),
+                    }
+                }
+            }
+            JobKind::SendNotification {
+                claim_id,
+                identification_id,
+            } => {
+                let claim = ClaimableIdentification::find_by_id(*claim_id)?;
+                let idt = Identification::find_by_id(*identification_id)?;
+
+                send_match_notification(&idt, &claim).await?;
+            }
+            JobKind::RematchSweep => {
+                let report = SweepReport::run().await?;
+                debug!("Match sweep: {:?}", report);
+                return Ok(job_result(&report));
+            }
+            JobKind::ExpireClaims => {
+                let report = ExpiryReport::run().await?;
+                debug!("Claim expiry: {:?}", report);
+                return Ok(job_result(&report));
+            }
+            JobKind::SendOwnershipNotice { request_id } => {
+                let request = OwnershipRequest::find_by_id(*request_id)?;
+
+                send_ownership_notice(&request).await?;
+            }
+            JobKind::SendMessageNotice { message_id } => {
+                let message = Message::find_by_id(*message_id)?;
+
+                send_message_notice(&message).await?;
+            }
+            JobKind::DeliverNotification {
+                notification_id,
+                channel,
+            } => {
+                let notification = Notification::find_by_id(*notification_id)?;
+
+                deliver(&notification, *channel).await?;
+            }
+            JobKind::SendDigests => {
+                let report = send_digests().await?;
+                debug!("Notification digests: {:?}", report);
+                return Ok(job_result(&report));
+            }
+            JobKind::DeliverWebhook { delivery_id } => {
+                WebhookDelivery::find_by_id(*delivery_id)?.attempt().await?;
+            }
+            JobKind::SendEmail { email_id } => {
+                OutboxEmail::find_by_id(*email_id)?.attempt(mailer)?;
+            }
+            JobKind::SendInstitutionDigests => {
+                let report = InstitutionDigest::send_due().await?;
+                debug!("Institution digests: {:?}", report);
+                return Ok(job_result(&report));
+            }
+            JobKind::RenormalizeRegistrationNos { institution_id } => {
+                let checked = RegistrationFormat::renormalize_institution(*institution_id)?;
+                debug!(
+                    "Registration numbers: Checked {} records of institution {}",
+                    checked, institution_id
+                );
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Serializes the report of a job
+fn job_result<T: Serialize>(report: &T) -> Option<serde_json::Value> {
+    // Reports are plain structs of counts, which always serialize
+    serde_json::to_value(report).ok()
+}
+
+/// Alerts the saved searches an Identification matches, and the
+/// owners of the Claims it matches
+async fn match_identification(identification_id: i32) -> Result<(), ResError> {
+    let idt = Identification::find_by_id(identification_id)?;
+    SavedSearch::alert_for(&idt)?;
+    let (_, matched_claims) = idt.match_claims().await?;
+
+    for claim in matched_claims {
+        JobKind::SendNotification {
+            claim_id: claim.id,
+            identification_id: idt.id,
+        }
+        .enqueue()?;
+    }
+    Ok(())
+}
+
+impl<'a> From<&'a JobKind> for NewJob<'a> {
+    fn from(kind: &'a JobKind) -> Self {
+        NewJob {
+            kind: kind.name(),
+            // A plain enum of integer and unit enum fields always serializes
+            payload: serde_json::to_value(kind).unwrap(),
+        }
+    }
+}
+
+impl<'a> NewJob<'a> {
+    /// Saves the job to the jobs table
+    pub fn save(&self) -> Result<Job, ResError> {
+        Ok(diesel::insert_into(jobs::table)
+            .values(self)
+            .get_result::<Job>(&connect_to_db())?)
+    }
+}
+
+impl Job {
+    /// Leases the next due job to the calling worker, on its
+    /// connection, for the lease of its kind.
+    ///
+    /// Rows locked by other workers are skipped, so no two
+    /// workers hold the same job.
+    ///
+    /// Abandoned jobs that were already attempted `max_attempts`
+    /// times are failed instead of picked again.
+    ///
+    /// # Returns
+    /// None if no job is due
+    pub fn claim_next(conn: &PgConnection) -> Result<Option<Job>, ResError> {
+        use crate::diesel_cfg::schema::jobs::dsl::{
+            attempts, id, jobs, last_error, max_attempts, run_at, status,
+        };
+
+        let now = Utc::now().naive_utc();
+
+        let job = conn.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::update(
+                jobs.filter(
+                    run_at
+                        .le(now)
+                        .and(status.eq(JobStatus::Running))
+                        .and(attempts.ge(max_attempts)),
+                ),
+            )
+            .set((
+                status.eq(JobStatus::Failed),
+                last_error.eq("The lease of the last attempt ran out"),
+            ))
+            .execute(conn)?;
+
+            let due = jobs
+                .filter(
+                    run_at
+                        .le(now)
+                        .and(
+                            status
+                                .eq(JobStatus::Queued)
+                                .or(status.eq(JobStatus::Running)),
+                        )
+                        .and(attempts.lt(max_attempts)),
+                )
+                .order(run_at.asc())
+                .limit(1)
+                .for_update()
+                .skip_locked()
+                .first::<Job>(conn)
+                .optional()?;
+
+            match due {
+                Some(job) => diesel::update(jobs.filter(id.eq(job.id)))
+                    .set((
+                        status.eq(JobStatus::Running),
+                        attempts.eq(attempts + 1),
+                        run_at.eq(now + job.lease()),
+                    ))
+                    .get_result::<Job>(conn)
+                    .map(Some),
+                None => Ok(None),
+            }
+        })?;
+
+        Ok(job)
+    }
+
+    /// How long the job is leased. Jobs of an unreadable
+    /// payload fail when run, so get the shortest lease
+    fn lease(&self) -> Duration {
+        self.job_kind()
+            .map(|kind| kind.lease())
+            .unwrap_or_else(|_| Duration::seconds(LEASE_SECONDS))
+    }
+
+    /// Deserializes the job payload
+    pub fn job_kind(&self) -> Result<JobKind, ResError> {
+        serde_json::from_value::<JobKind>(self.payload.clone())
+            .map_err(|e| ResError::new(format!("Invalid payload on job {}: {}", self.id, e), 500))
+    }
+
+    /// Marks the job as successfully completed, keeping its report
+    pub fn complete(&self, report: Option<&serde_json::Value>) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::jobs::dsl::{last_error, result, status};
+
+        diesel::update(self)
+            .set((
+                status.eq(JobStatus::Done),
+                last_error.eq(None::<String>),
+                result.eq(report),
+            ))
+            .execute(&connect_to_db())?;
+        Ok(())
+    }
+
+    /// Retrieves the latest jobs of a kind, newest first
+    pub fn latest_of(job_kind: &str, count: i64) -> Result<Vec<Job>, ResError> {
+        use crate::diesel_cfg::schema::jobs::dsl::{created_at, id, jobs, kind};
+
+        Ok(jobs
+            .filter(kind.eq(job_kind))
+            .order((created_at.desc(), id.desc()))
+            .limit(count)
+            .load::<Job>(&connect_to_db())?)
+    }
+
+    /// Records a failed run of the job.
+    ///
+    /// The job is retried with an exponential backoff until
+    /// it has been attempted `max_attempts` times.
+    pub fn fail(&self, error: &str) -> Result<JobStatus, ResError> {
+        use crate::diesel_cfg::schema::jobs::dsl::{last_error, run_at, status};
+
+        let new_status = if self.attempts >= self.max_attempts {
+            JobStatus::Failed
+        } else {
+            JobStatus::Queued
+        };
+        let retry_at = Utc::now().naive_utc() + Self::backoff(self.attempts);
+
+        diesel::update(self)
+            .set((
+                status.eq(new_status),
+                last_error.eq(error),
+                run_at.eq(retry_at),
+            ))
+            .execute(&connect_to_db())?;
+        Ok(new_status)
+    }
+
+    /// Delay before the next attempt of a job that has
+    /// been attempted `attempts` times.
+    ///
+    /// ```rust
+    /// use got_ya_id::apps::jobs::models::Job;
+    /// use chrono::Duration;
+    ///
+    /// assert_eq!(Job::backoff(1), Duration::seconds(30));
+    /// assert_eq!(Job::backoff(3), Duration::seconds(120));
+    /// assert_eq!(Job::backoff(30), Duration::seconds(3600));
+    /// ```
+    pub fn backoff(attempts: i32) -> Duration {
+        let exp = (attempts.max(1) - 1).min(16) as u32;
+        let secs = BACKOFF_BASE_SECONDS.saturating_mul(2_i64.pow(exp));
+
+        Duration::seconds(secs.min(BACKOFF_MAX_SECONDS))
+    }
+}
+
+impl JobSchedule {
+    /// Enqueues the job of the named schedule if `interval` has passed
+    /// since the schedule last ran.
+    ///
+    /// # Returns
+    /// true: If the job was enqueued
+    pub fn enqueue_if_due(
+        schedule: &str,
+        interval: Duration,
+        job: &JobKind,
+    ) -> Result<bool, ResError> {
+        use crate::diesel_cfg::schema::job_schedules::dsl::{job_schedules, last_run_at, name};
+
+        let conn = connect_to_db();
+        let now = Utc::now().naive_utc();
+
+        diesel::insert_into(job_schedules)
+            .values(name.eq(schedule))
+            .on_conflict_do_nothing()
+            .execute(&conn)?;
+
+        let enqueued = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let this_schedule = job_schedules
+                .find(schedule)
+                .for_update()
+                .skip_locked()
+                .first::<JobSchedule>(&conn)
+                .optional()?;
+
+            match this_schedule {
+                // Held by another scheduler
+                None => return Ok(false),
+                Some(s) => {
+                    if let Some(last) = s.last_run_at {
+                        if last + interval > now {
+                            return Ok(false);
+                        }
+                    }
+                }
+            }
+
+            diesel::insert_into(jobs::table)
+                .values(&NewJob::from(job))
+                .execute(&conn)?;
+            diesel::update(job_schedules.find(schedule))
+                .set(last_run_at.eq(now))
+                .execute(&conn)?;
+            Ok(true)
+        })?;
+
+        Ok(enqueued)
+    }
+}
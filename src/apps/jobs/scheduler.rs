@@ -0,0 +1,89 @@
+//! Enqueues periodic jobs
+//!
+//! Each schedule runs its job once every interval, across all
+//! server instances. Intervals are given in minutes through
+//! ENV variables.
+
+use super::models::{JobKind, JobSchedule};
+
+use actix_rt::time::delay_for;
+use chrono::Duration;
+
+use std::{env, time::Duration as StdDuration};
+
+/// Pause between checks for due schedules
+const TICK: StdDuration = StdDuration::from_secs(60);
+
+/// A periodic job
+struct Schedule {
+    /// Unique name of the schedule
+    name: &'static str,
+
+    /// ENV variable holding the interval in minutes
+    interval_var: &'static str,
+
+    /// Interval in minutes used if `interval_var` is missing
+    default_minutes: i64,
+
+    job: JobKind,
+}
+
+impl Schedule {
+    /// The configured interval of the schedule
+    fn interval(&self) -> Duration {
+        let minutes = env::var(self.interval_var)
+            .ok()
+            .and_then(|m| m.parse::<i64>().ok())
+            .unwrap_or(self.default_minutes);
+        Duration::minutes(minutes)
+    }
+}
+
+/// All periodic jobs
+fn schedules() -> Vec<Schedule> {
+    vec![
+        Schedule {
+            name: "rematch_sweep",
+            interval_var: "REMATCH_SWEEP_INTERVAL",
+            default_minutes: 24 * 60,
+            job: JobKind::RematchSweep,
+        },
+        Schedule {
+            name: "expire_claims",
+            interval_var: "CLAIM_EXPIRY_CHECK_INTERVAL",
+            default_minutes: 60,
+            job: JobKind::ExpireClaims,
+        },
+        Schedule {
+            name: "send_digests",
+            interval_var: "NOTIFICATION_DIGEST_CHECK_INTERVAL",
+            default_minutes: 30,
+            job: JobKind::SendDigests,
+        },
+        Schedule {
+            name: "send_institution_digests",
+            interval_var: "INSTITUTION_DIGEST_CHECK_INTERVAL",
+            default_minutes: 60,
+            job: JobKind::SendInstitutionDigests,
+        },
+    ]
+}
+
+/// Starts the scheduler on the current actix runtime
+pub fn spawn_scheduler() {
+    actix_rt::spawn(run_scheduler());
+}
+
+/// Enqueues the jobs of due schedules, every `TICK`
+async fn run_scheduler() {
+    loop {
+        for schedule in schedules() {
+            match JobSchedule::enqueue_if_due(schedule.name, schedule.interval(), &schedule.job) {
+                Ok(true) => debug!("Scheduler: Enqueued {}", schedule.name),
+                Ok(false) => (),
+                Err(e) => error!("Scheduler: Error enqueuing {}: {}", schedule.name, e.msg),
+            }
+        }
+        delay_for(TICK).await;
+    }
+}
@@ -0,0 +1,105 @@
+//! Runs queued jobs in the background
+//!
+//! Workers live on the server's actix runtime and poll the
+//! `jobs` table. Their number is set by the `JOB_WORKERS`
+//! ENV variable, `0` disabling them for an instance.
+
+use super::models::{Job, JobStatus};
+use crate::{core::mail::MailTransport, diesel_cfg::config::try_connect_to_db};
+
+use actix_rt::time::delay_for;
+
+use std::{env, sync::Arc, time::Duration};
+
+/// Pause between polls of an empty queue
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest pause between polls while the database is unreachable
+const MAX_OUTAGE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Number of workers started if `JOB_WORKERS` is missing
+const DEFAULT_WORKERS: usize = 2;
+
+/// Starts the configured number of job workers on the
+/// current actix runtime, sending emails through the
+/// given transport.
+pub fn spawn_workers(mailer: Arc<dyn MailTransport>) {
+    let count = env::var("JOB_WORKERS")
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_WORKERS);
+
+    for worker in 0..count {
+        actix_rt::spawn(run_worker(worker, mailer.clone()));
+    }
+    info!("Started {} job worker(s)", count);
+}
+
+/// Picks and runs due jobs, waiting on the queue when
+/// there's none.
+///
+/// While the database can't be reached, the worker polls
+/// less often, up to `MAX_OUTAGE_BACKOFF` apart.
+async fn run_worker(worker: usize, mailer: Arc<dyn MailTransport>) {
+    let mut backoff = POLL_INTERVAL;
+
+    loop {
+        let claimed = match try_connect_to_db() {
+            Ok(conn) => Job::claim_next(&conn),
+            Err(e) => {
+                error!("Worker {}: Error connecting to the database: {}", worker, e);
+                delay_for(backoff).await;
+                backoff = (backoff * 2).min(MAX_OUTAGE_BACKOFF);
+                continue;
+            }
+        };
+
+        match claimed {
+            Ok(Some(job)) => {
+                backoff = POLL_INTERVAL;
+                run_job(worker, job, &*mailer).await
+            }
+            Ok(None) => {
+                backoff = POLL_INTERVAL;
+                delay_for(POLL_INTERVAL).await
+            }
+            Err(e) => {
+                error!("Worker {}: Error claiming job: {}", worker, e.msg);
+                delay_for(backoff).await;
+                backoff = (backoff * 2).min(MAX_OUTAGE_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Runs a single job, recording its outcome
+async fn run_job(worker: usize, job: Job, mailer: &dyn MailTransport) {
+    let outcome = match job.job_kind() {
+        Ok(kind) => kind.run(mailer).await,
+        Err(e) => Err(e),
+    };
+
+    let recorded = match outcome {
+        Ok(report) => job.complete(report.as_ref()),
+        Err(e) => job.fail(&e.msg).map(|status| {
+            if status == JobStatus::Failed {
+                error!(
+                    "Worker {}: Job {} ({}) failed after {} attempts: {}",
+                    worker, job.id, job.kind, job.attempts, e.msg
+                );
+            } else {
+                debug!(
+                    "Worker {}: Job {} ({}) failed. Retrying: {}",
+                    worker, job.id, job.kind, e.msg
+                );
+            }
+        }),
+    };
+
+    if let Err(e) = recorded {
+        error!(
+            "Worker {}: Could not record outcome of job {}: {}",
+            worker, job.id, e.msg
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod models;
+pub mod moderation;
+pub mod utils;
+pub mod views;
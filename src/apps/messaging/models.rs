@@ -0,0 +1,485 @@
+//! Conversations between finders and claimants
+//!
+//! A conversation is held over a matched Claim and Identification,
+//! between the claimant and the user who posted the Identification.
+//! Neither sees the other's emails or phone number unless they
+//! choose to share them.
+
+use super::moderation::{check_message, report_hooks};
+use crate::{
+    apps::{
+        events::models::EventKind,
+        ids::models::{ClaimableIdentification, Identification, MatchedIdtJson},
+        jobs::models::JobKind,
+        notifications::models::Notification,
+        user::{models::User, utils::from_timestamp},
+    },
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{conversations, message_reports, messages},
+    },
+    errors::error::ResError,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use validator_derive::Validate;
+
+use std::borrow::Cow;
+
+/// Queryable model of the conversations table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "conversations"]
+pub struct Conversation {
+    pub id: i32,
+    pub claim_id: i32,
+    pub identification_id: i32,
+
+    /// Owner of the claim
+    pub claimant_id: i32,
+
+    /// Poster of the Identification
+    pub finder_id: i32,
+
+    pub claimant_shares_contact: bool,
+    pub finder_shares_contact: bool,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+
+    /// Time of the last message
+    #[serde(deserialize_with = "from_timestamp")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// Queryable model of the messages table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "messages"]
+pub struct Message {
+    pub id: i32,
+    pub conversation_id: i32,
+    pub sender_id: i32,
+    pub body: String,
+
+    /// When the recipient read the message
+    pub read_at: Option<NaiveDateTime>,
+
+    /// Hidden from the recipient after abuse reports
+    pub hidden: bool,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+}
+
+/// Queryable model of the message_reports table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "message_reports"]
+pub struct MessageReport {
+    pub id: i32,
+    pub message_id: i32,
+    pub reported_by: i32,
+    pub reason: String,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+}
+
+/// A new message to a conversation
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct NewMessage<'a> {
+    #[validate(length(min = 1, max = 2000, message = "should be 1 to 2000 letters long"))]
+    pub body: Cow<'a, str>,
+}
+
+/// An abuse report on a message
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct NewMessageReport<'a> {
+    #[validate(length(min = 1, max = 500, message = "should be 1 to 500 letters long"))]
+    pub reason: Cow<'a, str>,
+}
+
+/// Whether a participant shows their contacts to the other
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContactSharing {
+    pub share: bool,
+}
+
+/// The other participant of a conversation.
+///
+/// Contacts are only given if the participant shares them.
+#[derive(Serialize, Deserialize)]
+pub struct Participant {
+    pub user_id: i32,
+    pub username: String,
+    pub emails: Option<Vec<String>>,
+    pub phone: Option<String>,
+}
+
+/// A conversation as seen by one of its participants
+#[derive(Serialize, Deserialize)]
+pub struct ConversationDetail {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+
+    pub counterpart: Participant,
+
+    /// Messages to the participant not read yet
+    pub unread: i64,
+}
+
+impl Conversation {
+    /// Finds or starts the conversation over a matched Claim
+    /// and Identification.
+    ///
+    /// Either the claimant or the finder may start it.
+    pub fn open(data: &MatchedIdtJson, usr: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::conversations::dsl::{
+            claim_id, claimant_id, conversations as all_conversations, finder_id, identification_id,
+        };
+        use crate::diesel_cfg::schema::matched_identifications::dsl as matches;
+        use diesel::pg::upsert::on_constraint;
+
+        let is_matched = diesel::select(diesel::dsl::exists(
+            matches::matched_identifications.filter(
+                matches::claim_id
+                    .eq(data.claim)
+                    .and(matches::identification_id.eq(data.idt)),
+            ),
+        ))
+        .get_result::<bool>(&connect_to_db())?;
+        if !is_matched {
+            return Err(ResError::new(
+                "The claim doesn't match this identification".into(),
+                404,
+            ));
+        }
+
+        let claim = ClaimableIdentification::find_by_id(data.claim)?;
+        let idt = Identification::find_by_id(data.idt)?;
+        let finder = idt.posted_by.ok_or_else(|| {
+            ResError::new(
+                "The finder of this identification can't be reached".into(),
+                409,
+            )
+        })?;
+        if usr.id != claim.user_id && usr.id != finder {
+            return Err(ResError::unauthorized());
+        }
+
+        let conn = connect_to_db();
+        diesel::insert_into(all_conversations)
+            .values((
+                claim_id.eq(claim.id),
+                identification_id.eq(idt.id),
+                claimant_id.eq(claim.user_id),
+                finder_id.eq(finder),
+            ))
+            .on_conflict(on_constraint("conversations_pair_unique"))
+            .do_nothing()
+            .execute(&conn)?;
+
+        Ok(all_conversations
+            .filter(claim_id.eq(claim.id).and(identification_id.eq(idt.id)))
+            .first::<Self>(&conn)?)
+    }
+
+    /// Finds a conversation of the user by its primary key
+    pub fn find_for(pk: i32, usr: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::conversations::dsl::conversations;
+
+        let conversation = conversations.find(pk).first::<Self>(&connect_to_db())?;
+        if !conversation.has_participant(usr.id) {
+            return Err(ResError::unauthorized());
+        }
+        Ok(conversation)
+    }
+
+    /// Retrieves the conversations of the user, latest first
+    pub async fn mine(usr: &User) -> Result<Vec<ConversationDetail>, ResError> {
+        use crate::diesel_cfg::schema::conversations::dsl::*;
+
+        let mine = conversations
+            .filter(claimant_id.eq(usr.id).or(finder_id.eq(usr.id)))
+            .order(updated_at.desc())
+            .load::<Self>(&connect_to_db())?;
+
+        let mut details = vec![];
+        for conversation in mine {
+            details.push(conversation.detail(usr).await?);
+        }
+        Ok(details)
+    }
+
+    /// Whether the user takes part in the conversation
+    pub fn has_participant(&self, user_id: i32) -> bool {
+        self.claimant_id == user_id || self.finder_id == user_id
+    }
+
+    /// The other participant to the user
+    pub fn counterpart_of(&self, user_id: i32) -> i32 {
+        if self.claimant_id == user_id {
+            self.finder_id
+        } else {
+            self.claimant_id
+        }
+    }
+
+    /// Whether the participant shows their contacts to the other
+    fn shares_contact(&self, user_id: i32) -> bool {
+        if self.claimant_id == user_id {
+            self.claimant_shares_contact
+        } else {
+            self.finder_shares_contact
+        }
+    }
+
+    /// The conversation as seen by the user
+    pub async fn detail(self, usr: &User) -> Result<ConversationDetail, ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::{
+            conversation_id, hidden, messages as all_messages, read_at, sender_id,
+        };
+
+        let other = self.counterpart_of(usr.id);
+        let (other_user, profile) = User::find_by_pk(other, Some(1))?;
+
+        let counterpart = if self.shares_contact(other) {
+            Participant {
+                user_id: other,
+                username: other_user.username,
+                emails: Some(User::all_emails(other).await?),
+                phone: profile.and_then(|p| p.phone().map(String::from)),
+            }
+        } else {
+            Participant {
+                user_id: other,
+                username: other_user.username,
+                emails: None,
+                phone: None,
+            }
+        };
+
+        let unread = all_messages
+            .filter(
+                conversation_id
+                    .eq(self.id)
+                    .and(sender_id.ne(usr.id))
+                    .and(read_at.is_null())
+                    .and(hidden.eq(false)),
+            )
+            .count()
+            .get_result::<i64>(&connect_to_db())?;
+
+        Ok(ConversationDetail {
+            conversation: self,
+            counterpart,
+            unread,
+        })
+    }
+
+    /// Retrieves the messages of the conversation, oldest first.
+    ///
+    /// Hidden messages are left out, except to their sender.
+    pub fn messages(&self, usr: &User) -> Result<Vec<Message>, ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::*;
+
+        Ok(messages
+            .filter(
+                conversation_id
+                    .eq(self.id)
+                    .and(hidden.eq(false).or(sender_id.eq(usr.id))),
+            )
+            .order(id.asc())
+            .load::<Message>(&connect_to_db())?)
+    }
+
+    /// Sends a message to the other participant.
+    ///
+    /// The recipient is emailed in the background.
+    pub fn send(&self, usr: &User, data: &NewMessage) -> Result<Message, ResError> {
+        use crate::diesel_cfg::schema::conversations::dsl::updated_at;
+        use crate::diesel_cfg::schema::messages::dsl::{body, conversation_id, sender_id};
+
+        check_message(&data.body)?;
+
+        let conn = connect_to_db();
+        let message = diesel::insert_into(messages::table)
+            .values((
+                conversation_id.eq(self.id),
+                sender_id.eq(usr.id),
+                body.eq(data.body.trim()),
+            ))
+            .get_result::<Message>(&conn)?;
+        diesel::update(self)
+            .set(updated_at.eq(message.created_at))
+            .execute(&conn)?;
+
+        Notification::notify(
+            &[self.counterpart_of(usr.id)],
+            EventKind::Message,
+            &format!("New message from {}", usr.username),
+            &preview(&message.body),
+            json!({"conversation_id": self.id, "message_id": message.id}),
+        );
+        JobKind::SendMessageNotice {
+            message_id: message.id,
+        }
+        .enqueue()?;
+        Ok(message)
+    }
+
+    /// Marks the messages to the user as read
+    ///
+    /// # Returns
+    /// The number of messages marked
+    pub fn mark_read(&self, usr: &User) -> Result<usize, ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::*;
+
+        Ok(diesel::update(
+            messages.filter(
+                conversation_id
+                    .eq(self.id)
+                    .and(sender_id.ne(usr.id))
+                    .and(read_at.is_null()),
+            ),
+        )
+        .set(read_at.eq(Utc::now().naive_utc()))
+        .execute(&connect_to_db())?)
+    }
+
+    /// Shows or hides the user's contacts to the other participant
+    pub fn share_contact(&self, usr: &User, share: bool) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::conversations::dsl::{
+            claimant_shares_contact, finder_shares_contact,
+        };
+
+        let update = diesel::update(self);
+        let conn = connect_to_db();
+
+        Ok(if self.claimant_id == usr.id {
+            update
+                .set(claimant_shares_contact.eq(share))
+                .get_result::<Self>(&conn)?
+        } else {
+            update
+                .set(finder_shares_contact.eq(share))
+                .get_result::<Self>(&conn)?
+        })
+    }
+}
+
+impl Message {
+    /// Finds a message by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::messages;
+
+        Ok(messages.find(pk).first::<Self>(&connect_to_db())?)
+    }
+
+    /// Whether this is the earliest unread message of its sender
+    /// in the conversation.
+    ///
+    /// Recipients are only emailed of these, so a burst of
+    /// messages sends a single email.
+    pub fn is_first_unread(&self) -> Result<bool, ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::*;
+
+        if self.read_at.is_some() || self.hidden {
+            return Ok(false);
+        }
+        let earlier_unread = messages
+            .filter(
+                conversation_id
+                    .eq(self.conversation_id)
+                    .and(sender_id.eq(self.sender_id))
+                    .and(read_at.is_null())
+                    .and(id.lt(self.id)),
+            )
+            .count()
+            .get_result::<i64>(&connect_to_db())?;
+        Ok(earlier_unread == 0)
+    }
+
+    /// Hides the message from its recipient
+    pub fn hide(&self) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::messages::dsl::hidden;
+
+        diesel::update(self)
+            .set(hidden.eq(true))
+            .execute(&connect_to_db())?;
+        Ok(())
+    }
+
+    /// Reports the message as abusive.
+    ///
+    /// Only the recipient may report a message, once.
+    pub fn report(&self, usr: &User, data: &NewMessageReport) -> Result<MessageReport, ResError> {
+        use crate::diesel_cfg::schema::message_reports::dsl::{
+            message_id, message_reports as all_reports, reason, reported_by,
+        };
+        use diesel::pg::upsert::on_constraint;
+
+        Conversation::find_for(self.conversation_id, usr)?;
+        if self.sender_id == usr.id {
+            return Err(ResError::new(
+                "You can't report your own message".into(),
+                400,
+            ));
+        }
+
+        let conn = connect_to_db();
+        let report = diesel::insert_into(all_reports)
+            .values((
+                message_id.eq(self.id),
+                reported_by.eq(usr.id),
+                reason.eq(data.reason.trim()),
+            ))
+            .on_conflict(on_constraint("message_reports_unique"))
+            .do_nothing()
+            .get_result::<MessageReport>(&conn)
+            .optional()?
+            .ok_or_else(|| ResError::new("You already reported this message".into(), 409))?;
+
+        let reports = all_reports
+            .filter(message_id.eq(self.id))
+            .count()
+            .get_result::<i64>(&conn)?;
+
+        for hook in report_hooks() {
+            hook.on_report(self, &report, reports)?;
+        }
+        Ok(report)
+    }
+}
+
+impl MessageReport {
+    /// Retrieves all reports, latest first
+    pub fn retrieve_all() -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::message_reports::dsl::{id, message_reports};
+
+        Ok(message_reports
+            .order(id.desc())
+            .load::<Self>(&connect_to_db())?)
+    }
+}
+
+/// The start of a message body, as shown in notifications
+///
+/// ```rust
+/// use got_ya_id::apps::messaging::models::preview;
+///
+/// assert_eq!(preview("See you at noon"), "See you at noon");
+/// assert_eq!(preview(&"a".repeat(100)).chars().count(), 81);
+/// ```
+pub fn preview(body: &str) -> String {
+    const PREVIEW_LEN: usize = 80;
+
+    match body.char_indices().nth(PREVIEW_LEN) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
@@ -0,0 +1,142 @@
+//! Abuse checks on messages
+//!
+//! Messages pass the content checks before they are saved.
+//! Reports on a message are recorded and handed to the report
+//! hooks, which decide what becomes of the message.
+
+use super::models::{Message, MessageReport};
+use crate::errors::error::ResError;
+
+use std::env;
+
+/// Reports after which a message is hidden
+const DEFAULT_REPORT_THRESHOLD: i64 = 1;
+
+/// A check of the text of a message before it is sent
+pub trait ContentCheck {
+    /// # Returns
+    /// Why the text can't be sent, if it can't
+    fn check(&self, body: &str) -> Option<String>;
+}
+
+/// A hook run when a message is reported
+pub trait ReportHook {
+    /// `reports` is the number of reports on the message so far
+    fn on_report(
+        &self,
+        message: &Message,
+        report: &MessageReport,
+        reports: i64,
+    ) -> Result<(), ResError>;
+}
+
+/// Rejects messages with blocked words.
+///
+/// Words are matched whole, ignoring case.
+///
+/// ```rust
+/// use got_ya_id::apps::messaging::moderation::{BlockedWords, ContentCheck};
+///
+/// let blocked = BlockedWords::new(vec!["darn".into()]);
+///
+/// assert!(blocked.check("Darn, that's mine!").is_some());
+/// assert!(blocked.check("Darnell found it").is_none());
+/// ```
+pub struct BlockedWords {
+    words: Vec<String>,
+}
+
+impl BlockedWords {
+    pub fn new(words: Vec<String>) -> Self {
+        BlockedWords {
+            words: words.into_iter().map(|w| w.to_lowercase()).collect(),
+        }
+    }
+
+    /// Words from the comma separated `BLOCKED_WORDS` ENV variable
+    pub fn from_env() -> Self {
+        Self::new(
+            env::var("BLOCKED_WORDS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|w| w.trim().to_string())
+                .filter(|w| !w.is_empty())
+                .collect(),
+        )
+    }
+}
+
+impl ContentCheck for BlockedWords {
+    fn check(&self, body: &str) -> Option<String> {
+        let blocked = body
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| self.words.contains(&word.to_lowercase()));
+
+        if blocked {
+            Some("The message has words that aren't allowed".into())
+        } else {
+            None
+        }
+    }
+}
+
+/// Hides a message from its recipient once reported
+/// `MESSAGE_REPORT_THRESHOLD` times
+pub struct HideReported;
+
+impl ReportHook for HideReported {
+    fn on_report(
+        &self,
+        message: &Message,
+        _report: &MessageReport,
+        reports: i64,
+    ) -> Result<(), ResError> {
+        let threshold = env::var("MESSAGE_REPORT_THRESHOLD")
+            .ok()
+            .and_then(|n| n.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_REPORT_THRESHOLD);
+
+        if reports >= threshold && !message.hidden {
+            message.hide()?;
+        }
+        Ok(())
+    }
+}
+
+/// Logs reports for moderators to follow up
+pub struct LogReport;
+
+impl ReportHook for LogReport {
+    fn on_report(
+        &self,
+        message: &Message,
+        report: &MessageReport,
+        reports: i64,
+    ) -> Result<(), ResError> {
+        warn!(
+            "Message {} of conversation {} reported by user {} ({} reports): {}",
+            message.id, message.conversation_id, report.reported_by, reports, report.reason
+        );
+        Ok(())
+    }
+}
+
+/// Checks run on every message
+pub fn content_checks() -> Vec<Box<dyn ContentCheck>> {
+    vec![Box::new(BlockedWords::from_env())]
+}
+
+/// Hooks run on every report
+pub fn report_hooks() -> Vec<Box<dyn ReportHook>> {
+    vec![Box::new(LogReport), Box::new(HideReported)]
+}
+
+/// Runs the content checks on the text of a message
+pub fn check_message(body: &str) -> Result<(), ResError> {
+    for check in content_checks() {
+        if let Some(reason) = check.check(body) {
+            return Err(ResError::new(reason, 400));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Messaging helpers
+use super::models::{preview, Conversation, Message};
+use crate::{
+    apps::{
+        events::models::EventKind, ids::models::Identification,
+        notifications::dispatcher::send_email, user::models::User,
+    },
+    diesel_cfg::config::connect_to_db,
+    errors::error::ResError,
+};
+
+use diesel::prelude::*;
+use tera::Context;
+
+/// Emails the recipient of a message.
+///
+/// Only the first of a burst of unread messages is notified.
+/// The sender's contacts are left out of the email.
+pub async fn send_message_notice(message: &Message) -> Result<(), ResError> {
+    use crate::diesel_cfg::schema::conversations::dsl::conversations;
+
+    if !message.is_first_unread()? {
+        return Ok(());
+    }
+
+    let conversation = conversations
+        .find(message.conversation_id)
+        .first::<Conversation>(&connect_to_db())?;
+    let idt = Identification::find_by_id(conversation.identification_id)?;
+    let sender = User::find_by_pk(message.sender_id, None)?.0.username;
+
+    let mut context = Context::new();
+    context.insert("sender", &sender);
+    context.insert("id_name", &idt.name);
+    context.insert("document_type", idt.document_type.label());
+    context.insert("preview", &preview(&message.body));
+
+    send_email(
+        conversation.counterpart_of(message.sender_id),
+        EventKind::Message,
+        "new_message",
+        context,
+    )
+    .await
+}
@@ -0,0 +1,240 @@
+//! Implementations of Http endpoints for the Conversations resource
+
+use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
+
+use super::models::{
+    ContactSharing, Conversation, Message, MessageReport, NewMessage, NewMessageReport,
+};
+use crate::{
+    apps::{ids::models::MatchedIdtJson, user::models::User},
+    core::response::{err, respond},
+    errors::error::ResError,
+    hashmap,
+};
+
+use validator::Validate;
+
+/// Starts a conversation over a matched Claim and Identification,
+/// or retrieves the one already started.
+///
+/// # Url
+/// `/conversations`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The claimant or the poster of the Identification
+///
+/// ## Example
+/// ```json
+/// {
+///     idt: 1,
+///     claim: 1
+/// }
+/// ```
+pub async fn open_conversation(
+    req: HttpRequest,
+    data: web::Json<MatchedIdtJson>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let conversation = Conversation::open(&data, &user)?.detail(&user).await?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Conversation retrieved"];
+
+    respond(msg, Some(conversation), None).unwrap().await
+}
+
+/// Retrieves the conversations of the user
+///
+/// # Url
+/// `/conversations`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_conversations(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let conversations = Conversation::mine(&user).await?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Conversations retrieved"];
+
+    respond(msg, Some(conversations), None).unwrap().await
+}
+
+/// Retrieves a conversation of the user
+///
+/// # Url
+/// `/conversations/{pk}`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_conversation(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let conversation = Conversation::find_for(*pk, &user)?.detail(&user).await?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Conversation retrieved"];
+
+    respond(msg, Some(conversation), None).unwrap().await
+}
+
+/// Retrieves the messages of a conversation
+///
+/// # Url
+/// `/conversations/{pk}/messages`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_messages(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let messages = Conversation::find_for(*pk, &user)?.messages(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Messages retrieved"];
+
+    respond(msg, Some(messages), None).unwrap().await
+}
+
+/// Sends a message to the other participant of a conversation
+///
+/// # Url
+/// `/conversations/{pk}/messages`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+///
+/// ## Example
+/// ```json
+/// {
+///     "body": "I can bring it to the library tomorrow"
+/// }
+/// ```
+pub async fn send_message(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<NewMessage<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let message = Conversation::find_for(*pk, &user)?.send(&user, &data)?;
+    let msg = hashmap!["status" => "201",
+            "message" => "Success. Message sent"];
+
+    respond(msg, Some(message), None).unwrap().await
+}
+
+/// Marks the messages to the user in a conversation as read
+///
+/// # Url
+/// `/conversations/{pk}/read`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+pub async fn mark_messages_read(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let marked = Conversation::find_for(*pk, &user)?.mark_read(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Messages marked read"];
+
+    respond(msg, Some(marked), None).unwrap().await
+}
+
+/// Shows or hides the user's emails and phone number to the
+/// other participant of a conversation
+///
+/// # Url
+/// `/conversations/{pk}/contact`
+///
+/// # Method
+/// `PUT`
+///
+/// #### Authentication required
+///
+/// ## Example
+/// ```json
+/// {
+///     "share": true
+/// }
+/// ```
+pub async fn share_contact(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<ContactSharing>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let conversation = Conversation::find_for(*pk, &user)?.share_contact(&user, data.share)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Contact sharing updated"];
+
+    respond(msg, Some(conversation), None).unwrap().await
+}
+
+/// Reports a message as abusive
+///
+/// # Url
+/// `/conversations/messages/{pk}/report`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The recipient of the message
+pub async fn report_message(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+    data: web::Json<NewMessageReport<'_>>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let report = Message::find_by_id(*pk)?.report(&user, &data)?;
+    let msg = hashmap!["status" => "201",
+            "message" => "Success. Message reported"];
+
+    respond(msg, Some(report), None).unwrap().await
+}
+
+/// Retrieves the abuse reports on messages
+///
+/// # Url
+/// `/conversations/reports`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+/// Moderators only
+pub async fn get_message_reports(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+    if !user.is_moderator() {
+        return Err(ResError::unauthorized().into());
+    }
+
+    let reports = MessageReport::retrieve_all()?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Reports retrieved"];
+
+    respond(msg, Some(reports), None).unwrap().await
+}
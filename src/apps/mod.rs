@@ -2,7 +2,12 @@ pub mod api;
 pub mod auth;
 pub mod core;
 pub mod email;
+pub mod events;
 pub mod ids;
 pub mod institution;
+pub mod jobs;
+pub mod messaging;
+pub mod notifications;
 pub mod profiles;
 pub mod user;
+pub mod webhooks;
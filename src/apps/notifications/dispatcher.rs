@@ -0,0 +1,338 @@
+//! Routes notifications to the channels of their users
+//!
+//! All notifications go through here: in-app notifications from
+//! `Notification::notify`, and emails from `send_email`. Each channel
+//! is only used if the user has it on for the kind of notification.
+//!
+//! Emails only go to the active email of the user, through the email
+//! outbox, and not at all while mail to it bounces. Those held back for a digest or during quiet hours are
+//! sent by `send_digests`: as a digest, or as themselves once the quiet
+//! hours of a user not taking digests end.
+
+use super::{
+    models::Notification,
+    preferences::{Channel, NotificationSettings},
+};
+use crate::{
+    apps::{
+        email::outbox::OutboxEmail,
+        events::models::{Event, EventKind},
+        jobs::models::JobKind,
+        user::{
+            models::User,
+            utils::{render_mail, render_subject},
+        },
+        webhooks::utils::signed_post,
+    },
+    core::mail::Mail,
+    diesel_cfg::{config::connect_to_db, schema::deferred_emails},
+    errors::error::ResError,
+};
+
+use chrono::Utc;
+use diesel::{self, prelude::*};
+use serde::Serialize;
+use tera::Context;
+
+use std::env;
+
+/// Numbers of users sent held back emails
+#[derive(Serialize, Debug, Default)]
+pub struct DigestReport {
+    pub sent: usize,
+    pub failed: usize,
+
+    /// Held back emails sent as themselves
+    pub released: usize,
+}
+
+/// An email held back for a digest, or until quiet hours end
+#[derive(Queryable, Serialize)]
+struct DeferredEmail {
+    id: i32,
+    kind: EventKind,
+    subject: String,
+
+    /// None for emails held before templates were kept
+    #[serde(skip)]
+    template: Option<String>,
+    #[serde(skip)]
+    context: Option<serde_json::Value>,
+}
+
+/// Pushes a recorded notification out on the channels
+/// it has on, other than email.
+///
+/// Webhooks and texts are delivered in the background.
+pub fn dispatch(notification: &Notification, channels: &[Channel]) -> Result<(), ResError> {
+    for &channel in channels {
+        match channel {
+            Channel::InApp => Event::new(
+                notification.kind,
+                notification.user_id,
+                serde_json::to_value(notification).unwrap(),
+            )
+            .publish(),
+            Channel::Webhook | Channel::Sms => {
+                JobKind::DeliverNotification {
+                    notification_id: notification.id,
+                    channel,
+                }
+                .enqueue()?;
+            }
+            Channel::Email => (),
+        }
+    }
+    Ok(())
+}
+
+/// Delivers a notification over a webhook, or as a text.
+///
+/// Webhook posts are signed with the user's webhook secret, as
+/// institution webhooks are, and never go to internal addresses.
+pub async fn deliver(notification: &Notification, channel: Channel) -> Result<(), ResError> {
+    match channel {
+        Channel::Webhook => {
+            let settings = NotificationSettings::of(notification.user_id)?;
+            let url = match settings.webhook_url {
+                Some(url) => url,
+                None => return Ok(()),
+            };
+
+            let body = serde_json::to_string(notification).unwrap();
+            let headers = [("X-Gotya-Event", notification.kind.as_str().to_string())];
+            let (status, _) = signed_post(&url, &settings.signing_secret()?, body, &headers)
+                .await
+                .map_err(|e| ResError::new(format!("Webhook: {}", e), 502))?;
+            if !(200..300).contains(&status) {
+                return Err(ResError::new(
+                    format!("Webhook: {} responded {}", url, status),
+                    502,
+                ));
+            }
+        }
+        Channel::Sms => {
+            let (_, profile) = User::find_by_pk(notification.user_id, Some(1))?;
+
+            if let Some(phone) = profile.as_ref().and_then(|p| p.phone()) {
+                send_sms(
+                    phone,
+                    &format!("{}: {}", notification.title, notification.body),
+                );
+            }
+        }
+        Channel::Email | Channel::InApp => (),
+    }
+    Ok(())
+}
+
+/// Stand-in for an SMS gateway. The text is logged.
+fn send_sms(phone: &str, text: &str) {
+    info!("SMS to {}: {}", phone, text);
+}
+
+/// Renders a set of templates and emails them to the active
+/// email of a User, if they have emails of the kind on.
+///
+/// The `username`, dashboard `link` and `unsubscribe_link` are
+/// added to the context. The email is held back, with its
+/// template and context, if the user takes digests or is in
+/// their quiet hours.
+pub async fn send_email(
+    user_id: i32,
+    kind: EventKind,
+    template: &str,
+    mut context: Context,
+) -> Result<(), ResError> {
+    if !NotificationSettings::channels_of(user_id, kind)?.contains(&Channel::Email) {
+        debug!("Notifications: User {} is off {} emails", user_id, kind);
+        return Ok(());
+    }
+
+    let settings = NotificationSettings::of(user_id)?;
+    if settings.digest.period().is_some() || settings.is_quiet(Utc::now().naive_utc()) {
+        // Listed in the digest by its subject
+        let locale = User::find_by_pk(user_id, None)?.0.locale();
+        let subject = render_subject(template, locale, &context)?;
+        diesel::insert_into(deferred_emails::table)
+            .values((
+                deferred_emails::user_id.eq(user_id),
+                deferred_emails::kind.eq(kind),
+                deferred_emails::subject.eq(&subject),
+                deferred_emails::template.eq(template),
+                deferred_emails::context.eq(context.into_json()),
+            ))
+            .execute(&connect_to_db())?;
+        return Ok(());
+    }
+
+    let unsubscribe = unsubscribe_link(&settings.unsubscribe_token, Some(kind));
+    mail_active_email(user_id, template, &unsubscribe, context).await
+}
+
+/// Sends the held back emails of users whose digest is due,
+/// one digest per user.
+///
+/// Users not taking digests get the emails held during their
+/// quiet hours as they were, each on its own.
+pub async fn send_digests() -> Result<DigestReport, ResError> {
+    use crate::diesel_cfg::schema::deferred_emails::dsl::{
+        context, deferred_emails, id, kind, subject, template, user_id,
+    };
+    use crate::diesel_cfg::schema::notification_settings::dsl::last_digest_at;
+
+    let mut report = DigestReport::default();
+    let now = Utc::now().naive_utc();
+
+    let users = deferred_emails
+        .select(user_id)
+        .distinct()
+        .load::<i32>(&connect_to_db())?;
+
+    for uid in users {
+        let settings = NotificationSettings::of(uid)?;
+        if !settings.digest_due(now) {
+            continue;
+        }
+
+        let mut items = deferred_emails
+            .filter(user_id.eq(uid))
+            .select((id, kind, subject, template, context))
+            .order(id.asc())
+            .load::<DeferredEmail>(&connect_to_db())?;
+
+        if settings.digest.period().is_none() {
+            let (rest, released) = release(items, &settings).await?;
+            items = rest;
+            report.released += released;
+        }
+        let ids = items.iter().map(|item| item.id).collect::<Vec<_>>();
+        if ids.is_empty() {
+            continue;
+        }
+
+        let mut digest_context = Context::new();
+        digest_context.insert("items", &items);
+        let unsubscribe = unsubscribe_link(&settings.unsubscribe_token, None);
+
+        match mail_active_email(uid, "notification_digest", &unsubscribe, digest_context).await {
+            Ok(_) => {
+                diesel::delete(deferred_emails.filter(id.eq_any(ids))).execute(&connect_to_db())?;
+                diesel::update(&settings)
+                    .set(last_digest_at.eq(now))
+                    .execute(&connect_to_db())?;
+                report.sent += 1;
+            }
+            Err(e) => {
+                error!(
+                    "Notifications: Error sending digest to user {}: {}",
+                    uid, e.msg
+                );
+                report.failed += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Sends held back emails as themselves, and drops them.
+///
+/// Emails that fail are kept for the next run.
+///
+/// # Returns
+/// The emails held without a template, and the number sent
+async fn release(
+    items: Vec<DeferredEmail>,
+    settings: &NotificationSettings,
+) -> Result<(Vec<DeferredEmail>, usize), ResError> {
+    use crate::diesel_cfg::schema::deferred_emails::dsl::deferred_emails;
+
+    let mut rest = vec![];
+    let mut released = 0;
+    for item in items {
+        let (template, value) = match (&item.template, &item.context) {
+            (Some(template), Some(value)) => (template, value),
+            _ => {
+                rest.push(item);
+                continue;
+            }
+        };
+
+        let sent = match Context::from_value(value.clone()) {
+            Ok(context) => {
+                let unsubscribe = unsubscribe_link(&settings.unsubscribe_token, Some(item.kind));
+                mail_active_email(settings.user_id, template, &unsubscribe, context).await
+            }
+            Err(e) => Err(e.into()),
+        };
+        match sent {
+            Ok(_) => {
+                diesel::delete(deferred_emails.find(item.id)).execute(&connect_to_db())?;
+                released += 1;
+            }
+            Err(e) => error!(
+                "Notifications: Error sending held email {} to user {}: {}",
+                item.id, settings.user_id, e.msg
+            ),
+        }
+    }
+    Ok((rest, released))
+}
+
+/// Renders a set of templates in the locale of a User, and
+/// adds a mail of them to their active email to the outbox.
+///
+/// The unsubscribe link goes in the context, and in the
+/// `List-Unsubscribe` header of the mail.
+async fn mail_active_email(
+    user_id: i32,
+    template: &str,
+    unsubscribe: &str,
+    mut context: Context,
+) -> Result<(), ResError> {
+    let claim_rdct_link: String = env::var("CLAIM_REDIRECT_LINK").unwrap_or_else(|_| "".into());
+
+    let user = User::find_by_pk(user_id, None)?.0;
+    let user_email = match user.deliverable_email()? {
+        Some(address) => address,
+        None => {
+            debug!("Notifications: Active email of user {} bounces", user_id);
+            return Ok(());
+        }
+    };
+
+    context.insert("username", &user.username);
+    context.insert("link", &claim_rdct_link);
+    context.insert("unsubscribe_link", unsubscribe);
+    let (subject, html, text) = render_mail(template, user.locale(), context)?;
+
+    let mut mail = Mail::new(&user_email, &user.username, &subject, &html).with_text(&text);
+    if !unsubscribe.is_empty() {
+        mail = mail.with_unsubscribe(unsubscribe);
+    }
+    OutboxEmail::enqueue(&mail)?;
+    Ok(())
+}
+
+/// Link that unsubscribes the holder of the token from emails
+/// of the kind, or from all emails.
+///
+/// Empty if `UNSUBSCRIBE_LINK` is not set.
+fn unsubscribe_link(token: &str, kind: Option<EventKind>) -> String {
+    let base = env::var("UNSUBSCRIBE_LINK").unwrap_or_else(|_| "".into());
+    if base.is_empty() {
+        return base;
+    }
+
+    let mut params = vec![("token", token)];
+    if let Some(kind) = kind.as_ref() {
+        params.push(("kind", kind.as_str()));
+    }
+    match url::Url::parse_with_params(&base, &params) {
+        Ok(link) => link.into_string(),
+        Err(e) => {
+            error!("Notifications: Invalid UNSUBSCRIBE_LINK {}: {}", base, e);
+            "".into()
+        }
+    }
+}
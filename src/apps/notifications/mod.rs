@@ -0,0 +1,4 @@
+pub mod dispatcher;
+pub mod models;
+pub mod preferences;
+pub mod views;
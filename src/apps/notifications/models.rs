@@ -0,0 +1,189 @@
+//! In-app notifications
+//!
+//! Every notification-producing event is recorded here, whatever
+//! other channels (e.g email) it goes out on. It is then routed to
+//! the channels of the user by `notifications::dispatcher`.
+
+use super::{
+    dispatcher,
+    preferences::{Channel, NotificationSettings},
+};
+use crate::{
+    apps::{
+        events::models::EventKind,
+        user::{models::User, utils::from_timestamp},
+    },
+    diesel_cfg::{config::connect_to_db, schema::notifications},
+    errors::error::ResError,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Queryable model of the notifications table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "notifications"]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: EventKind,
+    pub title: String,
+    pub body: String,
+
+    /// Ids of the records involved
+    pub data: Value,
+    pub read_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+}
+
+/// Insertable notification
+#[derive(Insertable)]
+#[table_name = "notifications"]
+struct NewNotification<'a> {
+    user_id: i32,
+    kind: EventKind,
+    title: &'a str,
+    body: &'a str,
+    data: &'a Value,
+    read_at: Option<NaiveDateTime>,
+}
+
+/// Filter of notification listings
+#[derive(Deserialize)]
+pub struct NotificationQuery {
+    /// Only list unread notifications
+    #[serde(default)]
+    pub unread: bool,
+}
+
+/// Number of unread notifications
+#[derive(Serialize, Deserialize)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
+
+impl Notification {
+    /// Notifies each of the users.
+    ///
+    /// Notifications are best-effort: they are sent once the change
+    /// they tell of is saved, so a failure to notify is logged, and
+    /// doesn't fail the change.
+    pub fn notify(user_ids: &[i32], kind: EventKind, title: &str, body: &str, data: Value) {
+        if let Err(e) = Self::record(user_ids, kind, title, body, data) {
+            error!(
+                "Notifications: Error notifying users {:?} of {}: {}",
+                user_ids,
+                kind.as_str(),
+                e.msg
+            );
+        }
+    }
+
+    /// Records the notifications of the users, then dispatches
+    /// them to the channels the users have on. Notifications of
+    /// users with in-app notifications off are recorded as read.
+    fn record(
+        user_ids: &[i32],
+        kind: EventKind,
+        title: &str,
+        body: &str,
+        data: Value,
+    ) -> Result<Vec<Self>, ResError> {
+        let routes = user_ids
+            .iter()
+            .map(|&user_id| Ok((user_id, NotificationSettings::channels_of(user_id, kind)?)))
+            .collect::<Result<Vec<(i32, Vec<Channel>)>, ResError>>()?;
+
+        let now = Utc::now().naive_utc();
+        let records = routes
+            .iter()
+            .map(|(user_id, channels)| NewNotification {
+                user_id: *user_id,
+                kind,
+                title,
+                body,
+                data: &data,
+                read_at: if channels.contains(&Channel::InApp) {
+                    None
+                } else {
+                    Some(now)
+                },
+            })
+            .collect::<Vec<NewNotification>>();
+
+        let saved = diesel::insert_into(notifications::table)
+            .values(&records)
+            .get_results::<Self>(&connect_to_db())?;
+
+        for notification in &saved {
+            if let Some((_, channels)) = routes.iter().find(|(u, _)| *u == notification.user_id) {
+                dispatcher::dispatch(notification, channels)?;
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Finds a notification by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::notifications::dsl::notifications;
+
+        Ok(notifications.find(pk).first::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the notifications of the user, latest first
+    pub fn belonging_to_me(usr: &User, unread_only: bool) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::notifications::dsl::*;
+
+        let mut query = notifications.filter(user_id.eq(usr.id)).into_boxed();
+        if unread_only {
+            query = query.filter(read_at.is_null());
+        }
+        Ok(query.order(id.desc()).load::<Self>(&connect_to_db())?)
+    }
+
+    /// Counts the unread notifications of the user
+    pub fn unread_count(usr: &User) -> Result<UnreadCount, ResError> {
+        use crate::diesel_cfg::schema::notifications::dsl::*;
+
+        let unread = notifications
+            .filter(user_id.eq(usr.id).and(read_at.is_null()))
+            .count()
+            .get_result::<i64>(&connect_to_db())?;
+        Ok(UnreadCount { unread })
+    }
+
+    /// Marks a notification of the user as read
+    pub fn mark_read(pk: i32, usr: &User) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::notifications::dsl::*;
+
+        let notification = notifications.find(pk).first::<Self>(&connect_to_db())?;
+        if notification.user_id != usr.id {
+            return Err(ResError::unauthorized());
+        }
+        if notification.read_at.is_some() {
+            return Ok(notification);
+        }
+
+        Ok(diesel::update(&notification)
+            .set(read_at.eq(Utc::now().naive_utc()))
+            .get_result::<Self>(&connect_to_db())?)
+    }
+
+    /// Marks all notifications of the user as read
+    ///
+    /// # Returns
+    /// The number of notifications marked
+    pub fn mark_all_read(usr: &User) -> Result<usize, ResError> {
+        use crate::diesel_cfg::schema::notifications::dsl::*;
+
+        Ok(
+            diesel::update(notifications.filter(user_id.eq(usr.id).and(read_at.is_null())))
+                .set(read_at.eq(Utc::now().naive_utc()))
+                .execute(&connect_to_db())?,
+        )
+    }
+}
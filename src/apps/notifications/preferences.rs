@@ -0,0 +1,411 @@
+//! Notification preferences
+//!
+//! A user chooses the channels each kind of notification reaches
+//! them on. Kinds the user has no preference of go out on the
+//! default channels: email, in-app and webhook.
+//!
+//! Emails may be held back for a daily or weekly digest, and during
+//! the quiet hours of the user. Every email carries a link that
+//! unsubscribes the user from its kind of emails, without signing in.
+
+use crate::{
+    apps::{events::models::EventKind, user::utils::from_timestamp},
+    diesel_cfg::{config::connect_to_db, schema::notification_settings},
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use diesel::{self, prelude::*, sql_types::Varchar};
+use serde::{Deserialize, Serialize};
+use validator_derive::Validate;
+
+/// Length of generated unsubscribe tokens
+const UNSUBSCRIBE_TOKEN_LEN: usize = 32;
+
+/// Length of generated webhook signing secrets
+const WEBHOOK_SECRET_LEN: usize = 40;
+
+/// Bounds of UTC offsets, in minutes
+const MIN_UTC_OFFSET: i32 = -12 * 60;
+const MAX_UTC_OFFSET: i32 = 14 * 60;
+
+/// Ways notifications reach users
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Email,
+    /// The notification center, and live event streams
+    InApp,
+    /// A POST to the webhook url of the user
+    Webhook,
+    /// A text to the phone number on the user's profile
+    Sms,
+}
+
+varchar_enum!(Channel {
+    Email => "email",
+    InApp => "in_app",
+    Webhook => "webhook",
+    Sms => "sms"
+});
+
+impl Channel {
+    /// All channels
+    pub const ALL: [Channel; 4] = [
+        Channel::Email,
+        Channel::InApp,
+        Channel::Webhook,
+        Channel::Sms,
+    ];
+
+    /// Whether the channel is on for kinds the user has
+    /// no preference of
+    pub fn is_default(self) -> bool {
+        !matches!(self, Channel::Sms)
+    }
+}
+
+/// How often held back emails are sent
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum Digest {
+    /// Emails are sent as they come, outside quiet hours
+    Off,
+    Daily,
+    Weekly,
+}
+
+varchar_enum!(Digest {
+    Off => "off",
+    Daily => "daily",
+    Weekly => "weekly"
+});
+
+impl Default for Digest {
+    fn default() -> Self {
+        Digest::Off
+    }
+}
+
+impl Digest {
+    /// Least time between two digests
+    pub fn period(self) -> Option<Duration> {
+        match self {
+            Digest::Off => None,
+            Digest::Daily => Some(Duration::days(1)),
+            Digest::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+}
+
+/// Whether a notification kind goes out on a channel
+#[derive(Queryable, Serialize, Deserialize, Clone, Copy)]
+pub struct ChannelPreference {
+    pub kind: EventKind,
+    pub channel: Channel,
+    pub enabled: bool,
+}
+
+/// Preferences to save
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewPreferences {
+    pub preferences: Vec<ChannelPreference>,
+}
+
+/// Queryable model of the notification_settings table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "notification_settings"]
+#[primary_key(user_id)]
+pub struct NotificationSettings {
+    pub user_id: i32,
+    pub digest: Digest,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+
+    /// Minutes the user's local time is ahead of UTC
+    pub utc_offset: i32,
+    pub webhook_url: Option<String>,
+
+    #[serde(skip)]
+    pub unsubscribe_token: String,
+    pub last_digest_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    updated_at: NaiveDateTime,
+
+    /// Signs the notifications posted to the webhook url
+    pub webhook_secret: Option<String>,
+}
+
+/// Settings to save.
+///
+/// Missing quiet hours and webhook url are cleared.
+#[derive(AsChangeset, Deserialize, Validate)]
+#[table_name = "notification_settings"]
+#[changeset_options(treat_none_as_null = "true")]
+#[serde(deny_unknown_fields)]
+pub struct UpdatableSettings {
+    pub digest: Digest,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+
+    #[serde(default)]
+    pub utc_offset: i32,
+
+    #[validate(url(message = "Webhook url invalid"))]
+    pub webhook_url: Option<String>,
+}
+
+/// The settings and channel preferences of a user
+#[derive(Serialize, Deserialize)]
+pub struct PreferencesDetail {
+    pub settings: NotificationSettings,
+
+    /// A preference of every kind on every channel
+    pub preferences: Vec<ChannelPreference>,
+}
+
+/// Query of an unsubscribe link
+#[derive(Deserialize)]
+pub struct UnsubscribeQuery {
+    pub token: String,
+
+    /// Missing to unsubscribe from all emails
+    pub kind: Option<EventKind>,
+}
+
+impl NotificationSettings {
+    /// Retrieves the settings of the user, creating the
+    /// default settings if they have none
+    pub fn of(uid: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::notification_settings::dsl::*;
+
+        let conn = connect_to_db();
+        diesel::insert_into(notification_settings)
+            .values((
+                user_id.eq(uid),
+                unsubscribe_token.eq(random_token(UNSUBSCRIBE_TOKEN_LEN)),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&conn)?;
+
+        Ok(notification_settings.find(uid).first::<Self>(&conn)?)
+    }
+
+    /// Saves the settings of the user.
+    ///
+    /// A new webhook url gets a new signing secret.
+    pub fn update(uid: i32, data: &UpdatableSettings) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::notification_settings::dsl::webhook_secret;
+
+        if data.quiet_hours_start.is_some() != data.quiet_hours_end.is_some() {
+            return Err(ResError::new(
+                "Give both the start and end of quiet hours".into(),
+                400,
+            ));
+        }
+        if !(MIN_UTC_OFFSET..=MAX_UTC_OFFSET).contains(&data.utc_offset) {
+            return Err(ResError::new(
+                "Give the UTC offset in minutes, between -720 and 840".into(),
+                400,
+            ));
+        }
+        let settings = Self::of(uid)?;
+        let secret = match &data.webhook_url {
+            Some(url) if settings.webhook_url.as_ref() == Some(url) => {
+                settings.webhook_secret.clone()
+            }
+            Some(_) => Some(random_token(WEBHOOK_SECRET_LEN)),
+            None => None,
+        };
+
+        Ok(diesel::update(&settings)
+            .set((data, webhook_secret.eq(secret)))
+            .get_result::<Self>(&connect_to_db())?)
+    }
+
+    /// The secret signing the notifications posted to the webhook
+    /// of the user. Settings saved without one are given one.
+    pub fn signing_secret(&self) -> Result<String, ResError> {
+        use crate::diesel_cfg::schema::notification_settings::dsl::webhook_secret;
+
+        if let Some(secret) = &self.webhook_secret {
+            return Ok(secret.clone());
+        }
+        let secret = random_token(WEBHOOK_SECRET_LEN);
+        diesel::update(self)
+            .set(webhook_secret.eq(&secret))
+            .execute(&connect_to_db())?;
+        Ok(secret)
+    }
+
+    /// The channels the user gets a kind of notification on
+    pub fn channels_of(uid: i32, event_kind: EventKind) -> Result<Vec<Channel>, ResError> {
+        use crate::diesel_cfg::schema::notification_preferences::dsl::*;
+
+        let saved = notification_preferences
+            .filter(user_id.eq(uid).and(kind.eq(event_kind)))
+            .select((channel, enabled))
+            .load::<(Channel, bool)>(&connect_to_db())?;
+
+        Ok(Channel::ALL
+            .iter()
+            .copied()
+            .filter(|&ch| {
+                saved
+                    .iter()
+                    .find(|(saved_ch, _)| *saved_ch == ch)
+                    .map_or(ch.is_default(), |(_, on)| *on)
+            })
+            .collect())
+    }
+
+    /// The settings of the user, with a preference of
+    /// every kind on every channel
+    pub fn detail(uid: i32) -> Result<PreferencesDetail, ResError> {
+        use crate::diesel_cfg::schema::notification_preferences::dsl::*;
+
+        let saved = notification_preferences
+            .filter(user_id.eq(uid))
+            .select((kind, channel, enabled))
+            .load::<ChannelPreference>(&connect_to_db())?;
+
+        let mut preferences = vec![];
+        for &event_kind in EventKind::ALL.iter() {
+            for &ch in Channel::ALL.iter() {
+                let on = saved
+                    .iter()
+                    .find(|p| p.kind == event_kind && p.channel == ch)
+                    .map_or(ch.is_default(), |p| p.enabled);
+                preferences.push(ChannelPreference {
+                    kind: event_kind,
+                    channel: ch,
+                    enabled: on,
+                });
+            }
+        }
+
+        Ok(PreferencesDetail {
+            settings: Self::of(uid)?,
+            preferences,
+        })
+    }
+
+    /// Saves channel preferences of the user
+    pub fn save_preferences(uid: i32, data: &[ChannelPreference]) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::notification_preferences::dsl::*;
+        use diesel::pg::upsert::{excluded, on_constraint};
+
+        let rows = data
+            .iter()
+            .map(|p| {
+                (
+                    user_id.eq(uid),
+                    kind.eq(p.kind),
+                    channel.eq(p.channel),
+                    enabled.eq(p.enabled),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(notification_preferences)
+            .values(&rows)
+            .on_conflict(on_constraint("notification_preferences_unique"))
+            .do_update()
+            .set(enabled.eq(excluded(enabled)))
+            .execute(&connect_to_db())?;
+        Ok(())
+    }
+
+    /// Turns off emails of the kind, or all emails, for the
+    /// user holding the unsubscribe token
+    pub fn unsubscribe(token: &str, event_kind: Option<EventKind>) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::notification_settings::dsl::{
+            notification_settings, unsubscribe_token, user_id,
+        };
+
+        let uid = notification_settings
+            .filter(unsubscribe_token.eq(token))
+            .select(user_id)
+            .first::<i32>(&connect_to_db())
+            .optional()?
+            .ok_or_else(|| ResError::new("Unsubscribe link not recognised".into(), 404))?;
+
+        let kinds = match event_kind {
+            Some(k) => vec![k],
+            None => EventKind::ALL.to_vec(),
+        };
+        let preferences = kinds
+            .into_iter()
+            .map(|k| ChannelPreference {
+                kind: k,
+                channel: Channel::Email,
+                enabled: false,
+            })
+            .collect::<Vec<_>>();
+        Self::save_preferences(uid, &preferences)
+    }
+
+    /// Whether emails to the user are held back at the
+    /// given UTC time
+    pub fn is_quiet(&self, now: NaiveDateTime) -> bool {
+        match (self.quiet_hours_start, self.quiet_hours_end) {
+            (Some(start), Some(end)) => {
+                let local = now + Duration::minutes(self.utc_offset.into());
+                in_quiet_hours(start, end, local.time())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether held back emails of the user may be sent
+    /// at the given UTC time
+    pub fn digest_due(&self, now: NaiveDateTime) -> bool {
+        if self.is_quiet(now) {
+            return false;
+        }
+        match (self.digest.period(), self.last_digest_at) {
+            (Some(period), Some(last)) => last + period <= now,
+            _ => true,
+        }
+    }
+}
+
+/// A random alphanumeric string of the given length
+fn random_token(len: usize) -> String {
+    use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .collect::<String>()
+}
+
+/// Whether a time falls within quiet hours.
+///
+/// Quiet hours may run past midnight.
+///
+/// ```rust
+/// use chrono::NaiveTime;
+/// use got_ya_id::apps::notifications::preferences::in_quiet_hours;
+///
+/// let (start, end) = (NaiveTime::from_hms(22, 0, 0), NaiveTime::from_hms(7, 0, 0));
+///
+/// assert!(in_quiet_hours(start, end, NaiveTime::from_hms(23, 30, 0)));
+/// assert!(in_quiet_hours(start, end, NaiveTime::from_hms(6, 59, 0)));
+/// assert!(!in_quiet_hours(start, end, NaiveTime::from_hms(7, 0, 0)));
+/// assert!(!in_quiet_hours(end, start, NaiveTime::from_hms(23, 30, 0)));
+/// ```
+pub fn in_quiet_hours(start: NaiveTime, end: NaiveTime, time: NaiveTime) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        time >= start || time < end
+    }
+}
@@ -0,0 +1,252 @@
+//! Implementations of Http endpoints for the Notifications resource
+
+use actix_web::{http, web, Error, HttpRequest, HttpResponse, Result};
+
+use super::{
+    models::{Notification, NotificationQuery},
+    preferences::{NewPreferences, NotificationSettings, UnsubscribeQuery, UpdatableSettings},
+};
+use crate::{
+    apps::user::{models::User, utils::TEMPLATE},
+    core::response::{err, respond},
+    hashmap,
+};
+
+use tera::Context;
+use validator::Validate;
+
+/// Retrieves the notifications of the user, latest first
+///
+/// # Url
+/// `/notifications`
+///
+/// # Method
+/// `GET`
+///
+/// ## Query
+/// `unread=true` lists only the unread notifications
+///
+/// #### Authentication required
+pub async fn get_notifications(
+    req: HttpRequest,
+    query: web::Query<NotificationQuery>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let notifications = Notification::belonging_to_me(&user, query.unread)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Notifications retrieved"];
+
+    respond(msg, Some(notifications), None).unwrap().await
+}
+
+/// Retrieves the number of unread notifications of the user
+///
+/// # Url
+/// `/notifications/unread`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_unread_count(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let count = Notification::unread_count(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Unread count retrieved"];
+
+    respond(msg, Some(count), None).unwrap().await
+}
+
+/// Marks a notification as read
+///
+/// # Url
+/// `/notifications/{pk}/read`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+pub async fn mark_read(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let notification = Notification::mark_read(*pk, &user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Notification marked read"];
+
+    respond(msg, Some(notification), None).unwrap().await
+}
+
+/// Marks all notifications of the user as read
+///
+/// # Url
+/// `/notifications/read`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+pub async fn mark_all_read(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let marked = Notification::mark_all_read(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Notifications marked read"];
+
+    respond(msg, Some(marked), None).unwrap().await
+}
+
+/// Retrieves the notification settings of the user, and whether
+/// each kind of notification is on for each channel
+///
+/// # Url
+/// `/notifications/preferences`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_preferences(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let preferences = NotificationSettings::detail(user.id)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Preferences retrieved"];
+
+    respond(msg, Some(preferences), None).unwrap().await
+}
+
+/// Turns kinds of notifications on or off for channels.
+///
+/// Channels are `email`, `in_app`, `webhook` and `sms`.
+///
+/// # Url
+/// `/notifications/preferences`
+///
+/// # Method
+/// `PUT`
+///
+/// #### Authentication required
+///
+/// ## Example
+/// ```json
+/// {
+///     "preferences": [
+///         {"kind": "message", "channel": "email", "enabled": false},
+///         {"kind": "match_recorded", "channel": "sms", "enabled": true}
+///     ]
+/// }
+/// ```
+pub async fn update_preferences(
+    req: HttpRequest,
+    data: web::Json<NewPreferences>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    NotificationSettings::save_preferences(user.id, &data.preferences)?;
+    let preferences = NotificationSettings::detail(user.id)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Preferences updated"];
+
+    respond(msg, Some(preferences), None).unwrap().await
+}
+
+/// Updates the digest, quiet hours and webhook of the user.
+///
+/// Digests are `off`, `daily` or `weekly`. Quiet hours are in the
+/// user's local time, `utc_offset` minutes ahead of UTC. Missing
+/// quiet hours or webhook url are cleared.
+///
+/// A new webhook url gets a new `webhook_secret`. Notifications posted
+/// to the webhook are signed with it, in the `X-Gotya-Signature` header.
+///
+/// # Url
+/// `/notifications/settings`
+///
+/// # Method
+/// `PUT`
+///
+/// #### Authentication required
+///
+/// ## Example
+/// ```json
+/// {
+///     "digest": "daily",
+///     "quiet_hours_start": "22:00:00",
+///     "quiet_hours_end": "07:00:00",
+///     "utc_offset": 180,
+///     "webhook_url": "https://example.com/hooks/gotya"
+/// }
+/// ```
+pub async fn update_settings(
+    req: HttpRequest,
+    data: web::Json<UpdatableSettings>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let settings = NotificationSettings::update(user.id, &data)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Settings updated"];
+
+    respond(msg, Some(settings), None).unwrap().await
+}
+
+/// Asks to confirm turning off emails of a kind, or all
+/// notification emails, from the link at the bottom of an email.
+///
+/// Opening the link changes nothing, so link scanners and
+/// prefetchers don't unsubscribe users.
+///
+/// # Url
+/// `/notifications/unsubscribe?token={token}&kind={kind}`
+///
+/// # Method
+/// `GET`
+pub async fn confirm_unsubscribe(
+    req: HttpRequest,
+    query: web::Query<UnsubscribeQuery>,
+) -> HttpResponse {
+    let mut ctx = Context::new();
+    ctx.insert("kind", &query.kind.map(|k| k.as_str().replace('_', " ")));
+    ctx.insert("action", &format!("?{}", req.query_string()));
+
+    let s = TEMPLATE.render("unsubscribe.html", &ctx).unwrap();
+    HttpResponse::Ok().content_type("text/html").body(s)
+}
+
+/// Turns off emails of a kind, or all notification emails.
+///
+/// Posted by the confirmation page, and by mail clients
+/// unsubscribing in one click from the `List-Unsubscribe`
+/// header (RFC 8058).
+///
+/// # Url
+/// `/notifications/unsubscribe?token={token}&kind={kind}`
+///
+/// # Method
+/// `POST`
+pub async fn unsubscribe(query: web::Query<UnsubscribeQuery>) -> HttpResponse {
+    let mut ctx = Context::new();
+    ctx.insert("kind", &query.kind.map(|k| k.as_str().replace('_', " ")));
+
+    let status = match NotificationSettings::unsubscribe(&query.token, query.kind) {
+        Ok(_) => {
+            ctx.insert("unsubscribed", &true);
+            http::StatusCode::OK
+        }
+        Err(e) => {
+            debug!("Notifications: Unsubscribe failed: {}", e.msg);
+            ctx.insert("unsubscribed", &false);
+            http::StatusCode::from_u16(e.status).unwrap_or(http::StatusCode::BAD_REQUEST)
+        }
+    };
+
+    let s = TEMPLATE.render("unsubscribed.html", &ctx).unwrap();
+    HttpResponse::build(status)
+        .content_type("text/html")
+        .body(s)
+}
@@ -29,9 +29,16 @@ pub struct Profile<'a> {
     pub institution_id: Option<i32>,
     created_at: NaiveDateTime,
     updated_at: NaiveDateTime,
+    /// Language of the user's emails, e.g `sw`
+    pub locale: Option<String>,
 }
 
 impl<'a> Profile<'a> {
+    /// The phone number on the profile
+    pub fn phone(&self) -> Option<&str> {
+        self.phone.as_deref()
+    }
+
     /// Finds a given profile by its Primary Key
     ///
     /// # Returns
@@ -130,6 +137,7 @@ pub struct UpdtProfile<'a> {
     name: Option<Cow<'a, str>>,
     institution_id: Option<i32>,
     about: Option<Cow<'a, str>>,
+    pub locale: Option<Cow<'a, str>>,
 }
 
 /// User Profile Avatar struct
@@ -3,7 +3,10 @@
 use super::models::{Profile, UpdtProfile};
 use super::utils::make_temp_file;
 
-use crate::apps::user::models::User;
+use crate::apps::user::{
+    models::User,
+    utils::{supported_locale, LOCALES},
+};
 use crate::core::{
     py_interface::create_py_mod,
     response::{err, respond},
@@ -58,6 +61,9 @@ pub fn get_all_profiles() -> HttpResponse {
 
 /// Updates the details of an existing User profile
 ///
+/// The `locale` picks the language of the user's emails,
+/// `en` or `sw`.
+///
 /// # url
 /// ## `/user/profile/{id}`
 ///
@@ -72,6 +78,16 @@ pub async fn update_profile(
 ) -> Result<HttpResponse, Error> {
     let this_user = User::from_token(&req)?;
 
+    if let Some(locale) = &data.locale {
+        if supported_locale(locale) != Some(locale.as_ref()) {
+            return err(
+                "400",
+                format!("Emails are only available in {}", LOCALES.join(", ")),
+            )
+            .await;
+        }
+    }
+
     match Profile::find_by_key(*id) {
         Ok(p_vec) => {
             let profile = &p_vec.0[0];
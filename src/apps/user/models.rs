@@ -1,18 +1,21 @@
 //! This module holds items related to data manipulation
 //! for the User Object
 
-use super::utils::{from_timestamp, serialize_username, validate_email, validate_name};
+use super::utils::{
+    from_timestamp, serialize_username, supported_locale, validate_email, validate_name,
+    DEFAULT_LOCALE,
+};
 
 use std::borrow::Cow;
 
 use crate::{
     apps::{
         auth::validate::{self, Claims},
-        email::models::Email,
+        email::{models::Email, outbox::OutboxEmail},
         profiles::models::{Avatar, NewProfile, Profile},
     },
     config::configs as config,
-    core::py_interface::remove_py_mod,
+    core::{mail::Mail, py_interface::remove_py_mod},
     diesel_cfg::{
         config::connect_to_db,
         schema::{emails as emails_table, oath_users, refresh_tokens, users},
@@ -161,6 +164,8 @@ pub enum AccessLevel {
     Admin,
     Moderator,
     Usualuser,
+    /// Account of an integrator's API client
+    ApiClient,
 }
 
 #[derive(Validate, Deserialize, Serialize)]
@@ -169,7 +174,7 @@ pub struct NewUserLevel<'a> {
     #[validate(email(message = "Email format not invented yet"))]
     pub email: Cow<'a, str>,
     /// User access level to change to
-    #[validate(range(min = 0, max = 2))]
+    #[validate(range(min = 0, max = 3))]
     new_level: i32,
 }
 
@@ -179,6 +184,19 @@ impl<'a> NewUser<'a> {
     /// # Returns
     /// User
     pub fn save(&mut self, new_email: &str) -> Result<User, Box<dyn stdError>> {
+        self.save_with_mail(new_email, None)
+    }
+
+    /// Saves a new user record to the db, adding a mail to the
+    /// email outbox in the same transaction
+    ///
+    /// # Returns
+    /// User
+    pub fn save_with_mail(
+        &mut self,
+        new_email: &str,
+        mail: Option<&Mail>,
+    ) -> Result<User, Box<dyn stdError>> {
         use crate::diesel_cfg::schema::emails::*;
 
         match self.is_unique(new_email) {
@@ -199,15 +217,22 @@ impl<'a> NewUser<'a> {
                 return Err(format!("Failed to hash password: {}", e).into());
             }
         };
-        let usr = diesel::insert_into(users::table)
-            .values(&*self) // diesel::Insertable unimplemented for &mut
-            .get_result::<User>(&connect_to_db())?;
+        let conn = connect_to_db();
+        let usr = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let usr = diesel::insert_into(users::table)
+                .values(&*self) // diesel::Insertable unimplemented for &mut
+                .get_result::<User>(&conn)?;
 
-        let email_data = (email.eq(new_email), user_id.eq(usr.id), active.eq(true));
+            let email_data = (email.eq(new_email), user_id.eq(usr.id), active.eq(true));
 
-        diesel::insert_into(emails_table::table)
-            .values(&email_data)
-            .load::<Email>(&connect_to_db())?;
+            diesel::insert_into(emails_table::table)
+                .values(&email_data)
+                .load::<Email>(&conn)?;
+            if let Some(mail) = mail {
+                OutboxEmail::enqueue_on(&conn, mail)?;
+            }
+            Ok(usr)
+        })?;
         NewProfile::create(usr.id, None)?;
         Ok(usr)
     }
@@ -291,13 +316,65 @@ impl User {
             .get_result::<String>(&connect_to_db())?)
     }
 
+    /// The locale the user is emailed in
+    ///
+    /// The locale chosen on their profile is preferred to that
+    /// of their Google account. English if neither is supported.
+    pub fn locale(&self) -> &'static str {
+        use crate::diesel_cfg::schema::oath_users::dsl::{acc_id, locale as google_locale};
+        use crate::diesel_cfg::schema::profiles::dsl::{locale, profiles, user_id};
+
+        let conn = connect_to_db();
+        let chosen = profiles
+            .filter(user_id.eq(self.id))
+            .select(locale)
+            .first::<Option<String>>(&conn)
+            .optional()
+            .unwrap_or_default()
+            .flatten();
+        let google = match (&chosen, &self.social_id) {
+            (None, Some(social_id)) => oath_users::table
+                .filter(acc_id.eq(social_id))
+                .select(google_locale)
+                .first::<Option<String>>(&conn)
+                .optional()
+                .unwrap_or_default()
+                .flatten(),
+            _ => None,
+        };
+
+        chosen
+            .or(google)
+            .and_then(|tag| supported_locale(&tag))
+            .unwrap_or(DEFAULT_LOCALE)
+    }
+
+    /// Gives the Active email of a User, unless mail to
+    /// it bounces
+    pub fn deliverable_email(&self) -> Result<Option<String>, ResError> {
+        use crate::diesel_cfg::schema::emails::dsl::{active, email, undeliverable};
+
+        Ok(Email::belonging_to(self)
+            .filter(active.eq(true).and(undeliverable.eq(false)))
+            .select(email)
+            .first::<String>(&connect_to_db())
+            .optional()?)
+    }
+
     /// Returns all verified email addresses belonging to
-    /// the given user id.
+    /// the given user id, leaving out undeliverable ones.
     pub async fn all_emails(usr_id: i32) -> Result<Vec<String>, ResError> {
-        use crate::diesel_cfg::schema::emails::dsl::{email, emails, user_id, verified};
+        use crate::diesel_cfg::schema::emails::dsl::{
+            email, emails, undeliverable, user_id, verified,
+        };
 
         let all_em = emails
-            .filter(user_id.eq(usr_id).and(verified.eq(true)))
+            .filter(
+                user_id
+                    .eq(usr_id)
+                    .and(verified.eq(true))
+                    .and(undeliverable.eq(false)),
+            )
             .select(email)
             .load::<String>(&connect_to_db())?;
         Ok(all_em)
@@ -499,6 +576,21 @@ impl User {
         Ok(user_vec)
     }
 
+    /// Checks if the user has Moderator or Admin access
+    pub fn is_moderator(&self) -> bool {
+        self.access_level <= AccessLevel::Moderator as i32
+    }
+
+    /// Checks if the user has Admin access
+    pub fn is_admin(&self) -> bool {
+        self.access_level == AccessLevel::Admin as i32
+    }
+
+    /// Checks if the account is that of an API client
+    pub fn is_api_client(&self) -> bool {
+        self.access_level == AccessLevel::ApiClient as i32
+    }
+
     /// Alters an account activation status
     /// Activates or Deactivates a User account
     pub fn alter_activation_status(&self) -> Result<User, ResError> {
@@ -569,9 +661,7 @@ impl User {
             return Err("Invalid token. Problem finding user".into());
         };
 
-        if level.new_level < granter.access_level
-            || granter.access_level == AccessLevel::Usualuser as i32
-        {
+        if level.new_level < granter.access_level || !granter.is_moderator() {
             return Err("Oopsy! You are not allowed to do that".into());
         }
 
@@ -610,12 +700,27 @@ impl User {
     /// Gives the User whose email matches the subject of the decoded
     /// authorization token
     pub fn from_token(auth_header: &HttpRequest) -> Result<Self, ResError> {
+        Self::from_token_subject(&Self::token_subject(auth_header)?)
+    }
+
+    /// Gives the subject of the decoded authorization token:
+    /// the email it was issued to
+    pub fn token_subject(auth_header: &HttpRequest) -> Result<String, ResError> {
         let auth = User::extract_auth_header(auth_header)?;
         let auth_tk = &auth.split(' ').collect::<Vec<&str>>()[1];
 
-        let grant_email = validate::decode_auth_token(auth_tk, Some("auth".into()))?.sub;
+        Ok(validate::decode_auth_token(auth_tk, Some("auth".into()))?.sub)
+    }
+
+    /// Gives the User whose email matches the subject of a
+    /// token of the given issuer
+    pub fn from_issued_token(token: &str, issuer: &str) -> Result<Self, ResError> {
+        Self::from_token_subject(&validate::decode_auth_token(token, Some(issuer.into()))?.sub)
+    }
 
-        let mut granter = Email::load_user(&grant_email)?;
+    /// Gives the User of the email a token was issued to
+    fn from_token_subject(grant_email: &str) -> Result<Self, ResError> {
+        let mut granter = Email::load_user(grant_email)?;
 
         if !granter.is_empty() {
             Ok(granter.pop().unwrap())
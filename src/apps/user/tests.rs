@@ -2,10 +2,15 @@ use super::{
     models::{NewJsonUser, SignInUser, User},
     views::{change_activation_status, get_user, login, register_user, verify},
 };
-use crate::apps::user::models::NewUser;
+use crate::{
+    apps::{email::outbox::OutboxEmail, user::models::NewUser},
+    core::mail::{MailTransport, MemoryMail},
+    diesel_cfg::config::connect_to_db,
+};
 
 use actix_web::{http::StatusCode, test, web, App};
-use std::{borrow::Cow, env};
+use diesel::prelude::*;
+use std::{borrow::Cow, env, sync::Arc};
 
 const BASE: &str = "/api";
 lazy_static! {
@@ -34,6 +39,14 @@ fn create_user(username: &str, password: &str, email: &str) {
     if user.save(&email).is_ok() {}
 }
 
+/// Returns a mail transport keeping sent mails in memory,
+/// and the same as shared with the app
+fn memory_mailer() -> (Arc<MemoryMail>, Arc<dyn MailTransport>) {
+    let memory = Arc::new(MemoryMail::default());
+    let shared: Arc<dyn MailTransport> = memory.clone();
+    (memory, shared)
+}
+
 /// Returns an auth for the encoded with the passed email
 fn _auth_token(email: &str) -> String {
     let t = User::create_token(email, None, "auth".into()).unwrap();
@@ -67,13 +80,29 @@ async fn register_valid_user() {
         .set_json(&body)
         .uri(&url)
         .to_request();
-    let _resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
 
-    // Email is sent on registration.
-    // The req will err unless a mock of the email sending
-    // is done.
     let user = User::find_by_email(&body.email).unwrap();
     assert_eq!(user[0].username, body.username.to_string());
+
+    // The activation email is queued with the user
+    let queued = {
+        use crate::diesel_cfg::schema::email_outbox::dsl::*;
+
+        email_outbox
+            .filter(to_addr.eq(body.email.as_ref()))
+            .load::<OutboxEmail>(&connect_to_db())
+            .unwrap()
+    };
+    assert_eq!(queued.len(), 1);
+
+    // and sent through the transport the outbox worker is given
+    let (sent, mailer) = memory_mailer();
+    queued[0].attempt(&*mailer).unwrap();
+    let mails = sent.sent();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].to_addr, body.email.to_string());
 }
 
 #[actix_rt::test]
@@ -5,7 +5,14 @@ use validator::ValidationError;
 use super::models::User;
 
 use crate::{
-    apps::{core::response, ids::models::Identification, institution::models::Institution},
+    apps::{
+        core::response,
+        ids::models::Identification,
+        institution::{
+            models::Institution,
+            pickup::{hours_summary, PickupPoint},
+        },
+    },
     errors::error::ResError,
 };
 
@@ -140,7 +147,13 @@ pub async fn get_notif_context(
     context.insert("id_name", &idt.name);
     context.insert("id_institution", &institution_info.name);
     context.insert("id_inst_location", &institution_info.location);
-    context.insert("id_course", &idt.course);
+    context.insert("id_course", &idt.course.as_deref().unwrap_or(""));
+
+    if let Some(point_id) = idt.pickup_point_id {
+        let point = PickupPoint::find_by_id(point_id)?;
+        context.insert("pickup_location", &point.place()?);
+        context.insert("pickup_hours", &hours_summary(&point.hours()));
+    }
 
     Ok(context)
 }
@@ -155,6 +168,91 @@ pub fn get_reset_context<'a>(data: &User, path: &'a str) -> Context {
     context
 }
 
+/// Locale emails fall back to
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Locales emails are translated to.
+///
+/// Templates of locales other than English are kept
+/// in a directory named after the locale, e.g `sw/`.
+pub const LOCALES: [&str; 2] = ["en", "sw"];
+
+/// The supported locale of a language tag, ignoring its region
+///
+/// ```rust
+/// use got_ya_id::apps::user::utils::supported_locale;
+///
+/// assert_eq!(supported_locale("sw-KE"), Some("sw"));
+/// assert_eq!(supported_locale("EN_gb"), Some("en"));
+/// assert_eq!(supported_locale("fr"), None);
+/// ```
+pub fn supported_locale(tag: &str) -> Option<&'static str> {
+    let language = tag
+        .split(|c| c == '-' || c == '_')
+        .next()?
+        .trim()
+        .to_lowercase();
+
+    LOCALES.iter().copied().find(|locale| *locale == language)
+}
+
+/// Renders the subject, HTML and plain text parts of an email
+///
+/// `template` names a set of templates, e.g `email_activation`
+/// for `email_activation.subject`, `email_activation.html` and
+/// `email_activation.txt`. The set of the locale is used if there
+/// is one, else the English set.
+///
+/// # Returns
+/// (subject, html, text)
+pub fn render_mail(
+    template: &str,
+    locale: &str,
+    mut context: Context,
+) -> Result<(String, String, String), tera::Error> {
+    context.insert("locale", locale);
+
+    let subject = render_subject(template, locale, &context)?;
+    let html = TEMPLATE.render(&localized(template, "html", locale), &context)?;
+    let text = TEMPLATE.render(&localized(template, "txt", locale), &context)?;
+    Ok((subject, html, text))
+}
+
+/// Renders the subject of an email in the locale
+///
+/// ```rust
+/// use got_ya_id::apps::user::utils::render_subject;
+/// use tera::Context;
+///
+/// let mut context = Context::new();
+/// context.insert("sender", "Wanjiru");
+///
+/// let subject = render_subject("new_message", "sw", &context).unwrap();
+/// assert_eq!(subject, "Ujumbe mpya kutoka kwa Wanjiru");
+/// let subject = render_subject("new_message", "en", &context).unwrap();
+/// assert_eq!(subject, "New message from Wanjiru");
+/// ```
+pub fn render_subject(
+    template: &str,
+    locale: &str,
+    context: &Context,
+) -> Result<String, tera::Error> {
+    let subject = TEMPLATE.render(&localized(template, "subject", locale), context)?;
+    Ok(subject.trim().to_string())
+}
+
+/// Name of the template of the locale, or of
+/// the English template if there is none
+fn localized(template: &str, extension: &str, locale: &str) -> String {
+    let name = format!("{}/{}.{}", locale, template, extension);
+
+    if locale != DEFAULT_LOCALE && TEMPLATE.get_template_names().any(|n| n == name) {
+        name
+    } else {
+        format!("{}.{}", template, extension)
+    }
+}
+
 /// NaiveDateTime Serialize Deserialize implementation
 pub mod naive_date_format {
     use chrono::NaiveDateTime;
@@ -5,12 +5,12 @@ use super::{
         GoogleUser, NewJsonUser, NewRfToken, NewUserLevel, OClient, OauthGgUser, OauthInfo,
         Reftoken, ResetPassData, SignInUser, User, UserEmail,
     },
-    utils::{err_response, get_context, get_reset_context, get_url, TEMPLATE},
+    utils::{err_response, get_context, get_reset_context, get_url, render_mail, DEFAULT_LOCALE},
 };
 use crate::{
-    apps::auth::validate,
+    apps::{auth::validate, email::outbox::OutboxEmail},
     core::{
-        mail,
+        mail::Mail,
         response::{self, err, respond},
     },
     hashmap,
@@ -85,8 +85,17 @@ pub async fn register_user(
     };
 
     let mut user = user_.to_savable();
+    let mail = activation_mail(
+        &data.email,
+        Some(&data.username),
+        &path,
+        "email_activation",
+        DEFAULT_LOCALE,
+        false,
+    )?;
 
-    match user.save(&data.0.email) {
+    // The activation mail is queued with the user
+    match user.save_with_mail(&data.0.email, Some(&mail)) {
         Ok(saved_user) => saved_user,
         Err(e) => {
             let res: response::JsonErrResponse<_> =
@@ -97,16 +106,6 @@ pub async fn register_user(
         }
     };
 
-    // Mail
-    send_activation_link(
-        &data.email,
-        Some(&data.username),
-        &path,
-        "email_activation.html",
-        false,
-        Some("Complete your registration"),
-    )
-    .await?;
     let res: response::JsonResponse<_> = response::JsonResponse::new(
         http::StatusCode::CREATED.to_string(),
         format!("sucess. An activation link sent to {}", &data.0.email),
@@ -143,26 +142,27 @@ pub async fn send_account_activation_link(
             .json(e)
             .await;
     }
-    if let Err(e) = User::find_by_email(&email.email) {
-        return HttpResponse::build(http::StatusCode::NOT_FOUND)
-            .json(e)
-            .await;
-    }
+    let user = match User::find_by_email(&email.email) {
+        Ok(usr) => usr,
+        Err(e) => {
+            return HttpResponse::build(http::StatusCode::NOT_FOUND)
+                .json(e)
+                .await
+        }
+    };
 
     let token = User::create_token(&email.email, Some(24 * 60), "verification".into()).unwrap();
     let host = format!("{:?}", req.headers().get("host").unwrap());
     let path = get_url(&host, "api/auth/verify", &token);
 
-    let sub = "Complete your registration";
     send_activation_link(
         &email.email,
         None,
         &path,
-        "email_activation.html",
+        "email_activation",
+        user[0].locale(),
         true,
-        Some(sub),
-    )
-    .await?;
+    )?;
 
     let data = hashmap!["status" => "200", "message" => "sucess. Activation link sent"];
     Ok(respond(data, Some("".to_string()), None).unwrap())
@@ -373,18 +373,11 @@ pub async fn send_reset_email(
     let host = format!("{:?}", req.headers().get("host").unwrap());
     let path = get_url(&host, "api/auth/password/reset", &token);
     let context: Context = get_reset_context(&user, &path);
-    match TEMPLATE.render("password_reset.html", &context) {
-        Ok(s) => {
-            let mut mail = mail::Mail::new(
-                &data.email,
-                &user.username,
-                "Account password reset",
-                s.as_str(),
-            )
-            .await
-            .map_err(ErrorInternalServerError)?;
-
-            mail.send().await.map_err(ErrorInternalServerError)?;
+    match render_mail("password_reset", user.locale(), context) {
+        Ok((subject, html, text)) => {
+            let mail =
+                Mail::new(&data.email, &user.username, &subject, html.as_str()).with_text(&text);
+            OutboxEmail::enqueue(&mail)?;
         }
 
         Err(e) => return err("500", e.to_string()).await,
@@ -676,41 +669,56 @@ pub async fn register_g_oauth(req: HttpRequest) -> HttpResponse {
 
 /// Sends an account activation link to a user email
 ///
+/// The mail is added to the email outbox, and sent
+/// in the background.
+///
 /// # Arguments
+/// template: Name of the set of subject, text and HTML templates
+/// locale: Language of the mail
 /// resend: Whether a  response to an activation link
 /// re-send request.
-pub async fn send_activation_link(
+pub fn send_activation_link(
     user_email: &str,
     user_name: Option<&str>,
     reset_link: &str,
     template: &str,
+    locale: &str,
     resend: bool,
-    subject: Option<&str>,
 ) -> Result<(), Error> {
+    let mail = activation_mail(user_email, user_name, reset_link, template, locale, resend)?;
+
+    OutboxEmail::enqueue(&mail)?;
+    Ok(())
+}
+
+/// Renders an account activation mail
+///
+/// # Arguments
+/// template: Name of the set of subject, text and HTML templates
+/// locale: Language of the mail
+/// resend: Whether a  response to an activation link
+/// re-send request.
+pub fn activation_mail(
+    user_email: &str,
+    user_name: Option<&str>,
+    reset_link: &str,
+    template: &str,
+    locale: &str,
+    resend: bool,
+) -> Result<Mail, Error> {
     let mut context = get_context(user_name, reset_link);
     if resend {
         context.insert("resend", "1");
     }
     let mut username = "";
 
-    let s = TEMPLATE
-        .render(template, &context)
-        .map_err(ErrorInternalServerError)?;
+    let (subject, html, text) =
+        render_mail(template, locale, context).map_err(ErrorInternalServerError)?;
     if let Some(name) = user_name {
         username = name;
     }
 
-    let subject = if let Some(sub) = subject {
-        sub
-    } else {
-        "Email activation"
-    };
-
-    let mut mail = mail::Mail::new(user_email, username, subject, &s)
-        .await
-        .map_err(ErrorInternalServerError)?;
-    mail.send().await.map_err(ErrorInternalServerError)?;
-    Ok(())
+    Ok(Mail::new(user_email, username, &subject, &html).with_text(&text))
 }
 
 async fn generate_tokens(
@@ -750,7 +758,7 @@ async fn generate_tokens(
 /// For a user to increase another's prilidge,
 /// they must have a higher/equal priviledge than/equal to the one requested.
 ///
-/// Ordinary users can't change another's access.
+/// Ordinary users and API clients can't change another's access.
 ///
 /// # url
 /// `/auth/access`
@@ -0,0 +1,413 @@
+//! Outgoing webhooks
+//!
+//! Institutions and integrators subscribe a url to kinds of events.
+//! Each event is logged as a delivery to every matching subscription,
+//! and posted in the background, retried with backoff until the
+//! endpoint accepts it. Deliveries can be replayed.
+//!
+//! Admins of an institution manage its subscriptions. Subscriptions
+//! to the events of all institutions are for moderators. Integrators
+//! subscribe through the account of their API client, which may
+//! subscribe to the events of any institution, or of all, and manages
+//! the subscriptions it created.
+
+use super::utils::post_delivery;
+use crate::{
+    apps::{
+        institution::memberships::{InstitutionRole, Membership},
+        jobs::models::JobKind,
+        user::models::User,
+        user::utils::from_timestamp,
+    },
+    diesel_cfg::{
+        config::connect_to_db,
+        schema::{webhook_deliveries, webhook_subscriptions},
+    },
+    errors::error::ResError,
+    varchar_enum,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{self, prelude::*, sql_types::Varchar};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator_derive::Validate;
+
+/// Length of generated signing secrets
+const SECRET_LEN: usize = 40;
+
+/// Characters of an endpoint's response kept in the delivery log
+const RESPONSE_LOG_LEN: usize = 1000;
+
+/// Kinds of events posted to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+pub enum WebhookEvent {
+    /// A found Identification was posted
+    #[serde(rename = "identification.created")]
+    IdentificationCreated,
+    /// An Identification was marked found
+    #[serde(rename = "identification.found")]
+    IdentificationFound,
+    /// An Identification matched a claim
+    #[serde(rename = "claim.matched")]
+    ClaimMatched,
+    /// An Identification was handed over to its owner
+    #[serde(rename = "ownership.transferred")]
+    OwnershipTransferred,
+}
+
+varchar_enum!(WebhookEvent {
+    IdentificationCreated => "identification.created",
+    IdentificationFound => "identification.found",
+    ClaimMatched => "claim.matched",
+    OwnershipTransferred => "ownership.transferred"
+});
+
+/// Status of a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, AsExpression, FromSqlRow, Serialize, Deserialize)]
+#[sql_type = "Varchar"]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not attempted yet
+    Pending,
+    /// Accepted by the endpoint
+    Delivered,
+    /// The last attempt failed. Retried until the job gives up
+    Failed,
+}
+
+varchar_enum!(DeliveryStatus {
+    Pending => "pending",
+    Delivered => "delivered",
+    Failed => "failed"
+});
+
+/// Queryable model of the webhook_subscriptions table
+#[derive(Queryable, Identifiable, Serialize, Deserialize)]
+#[table_name = "webhook_subscriptions"]
+pub struct WebhookSubscription {
+    pub id: i32,
+    /// None to get the events of all institutions
+    pub institution_id: Option<i32>,
+    pub created_by: Option<i32>,
+    pub url: String,
+
+    #[serde(skip)]
+    pub secret: String,
+
+    /// Names of the events subscribed to
+    pub events: Vec<String>,
+    pub active: bool,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// A new subscription
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct NewSubscription {
+    #[validate(url(message = "Webhook url invalid"))]
+    pub url: String,
+
+    #[validate(length(min = 1, message = "Subscribe to at least one event"))]
+    pub events: Vec<WebhookEvent>,
+
+    pub institution_id: Option<i32>,
+}
+
+/// A created subscription, with the secret its deliveries are
+/// signed with. The secret isn't shown again.
+#[derive(Serialize, Deserialize)]
+pub struct CreatedSubscription {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+/// Queryable model of the webhook_deliveries table
+#[derive(Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[belongs_to(WebhookSubscription, foreign_key = "subscription_id")]
+#[table_name = "webhook_deliveries"]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub subscription_id: i32,
+    pub event: WebhookEvent,
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+
+    /// HTTP status of the last response
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+
+    #[serde(deserialize_with = "from_timestamp")]
+    pub created_at: NaiveDateTime,
+    #[serde(deserialize_with = "from_timestamp")]
+    pub updated_at: NaiveDateTime,
+}
+
+impl WebhookEvent {
+    /// Logs a delivery of the event to every active subscription
+    /// of the institution, and of all institutions, then posts
+    /// the deliveries in the background.
+    ///
+    /// Webhooks are best-effort: a failure to queue deliveries is
+    /// logged, and doesn't fail the change that raised the event.
+    pub fn publish(self, institution: Option<i32>, data: Value) {
+        if let Err(e) = self.queue_deliveries(institution, data) {
+            error!("Webhooks: Error queuing {}: {}", self, e.msg);
+        }
+    }
+
+    fn queue_deliveries(self, institution: Option<i32>, data: Value) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::*;
+
+        let mut query = webhook_subscriptions
+            .filter(active.eq(true).and(events.contains(vec![self.as_str()])))
+            .select(id)
+            .into_boxed();
+        query = match institution {
+            Some(inst) => query.filter(institution_id.eq(inst).or(institution_id.is_null())),
+            None => query.filter(institution_id.is_null()),
+        };
+        let subscriptions = query.load::<i32>(&connect_to_db())?;
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+
+        let rows = subscriptions
+            .into_iter()
+            .map(|sub| {
+                (
+                    webhook_deliveries::subscription_id.eq(sub),
+                    webhook_deliveries::event.eq(self),
+                    webhook_deliveries::payload.eq(&data),
+                )
+            })
+            .collect::<Vec<_>>();
+        let delivery_ids = diesel::insert_into(webhook_deliveries::table)
+            .values(&rows)
+            .returning(webhook_deliveries::id)
+            .get_results::<i32>(&connect_to_db())?;
+
+        for delivery_id in delivery_ids {
+            JobKind::DeliverWebhook { delivery_id }.enqueue()?;
+        }
+        Ok(())
+    }
+}
+
+impl WebhookSubscription {
+    /// Subscribes a url to events, if the user is an API client,
+    /// or an admin of the institution
+    pub fn create(data: &NewSubscription, usr: &User) -> Result<CreatedSubscription, ResError> {
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::*;
+        use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+        if !usr.is_api_client() {
+            Membership::require(usr, data.institution_id, InstitutionRole::Admin)?;
+        }
+
+        let key = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .collect::<String>();
+        let mut names = data
+            .events
+            .iter()
+            .map(|e| e.as_str().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+
+        let subscription = diesel::insert_into(webhook_subscriptions)
+            .values((
+                institution_id.eq(data.institution_id),
+                created_by.eq(usr.id),
+                url.eq(&data.url),
+                secret.eq(&key),
+                events.eq(names),
+            ))
+            .get_result::<Self>(&connect_to_db())?;
+
+        Ok(CreatedSubscription {
+            subscription,
+            secret: key,
+        })
+    }
+
+    /// Finds a subscription by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::webhook_subscriptions;
+
+        Ok(webhook_subscriptions
+            .find(pk)
+            .first::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves all subscriptions
+    pub fn retrieve_all() -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::{id, webhook_subscriptions};
+
+        Ok(webhook_subscriptions
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the subscriptions the user created, and those
+    /// of the institutions they are an admin of
+    pub fn visible_to(usr: &User) -> Result<Vec<Self>, ResError> {
+        use crate::diesel_cfg::schema::institution_memberships::dsl::{
+            institution_id as member_institution, institution_memberships, role, user_id,
+        };
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::*;
+
+        if usr.is_moderator() {
+            return Self::retrieve_all();
+        }
+        let administered = institution_memberships
+            .filter(user_id.eq(usr.id).and(role.eq(InstitutionRole::Admin)))
+            .select(member_institution.nullable());
+
+        Ok(webhook_subscriptions
+            .filter(
+                created_by
+                    .eq(usr.id)
+                    .or(institution_id.eq_any(administered)),
+            )
+            .order(id.asc())
+            .load::<Self>(&connect_to_db())?)
+    }
+
+    /// Ensures the user may manage the subscription: its
+    /// creator, or an admin of its institution
+    pub fn authorize(&self, usr: &User) -> Result<(), ResError> {
+        if self.created_by == Some(usr.id) {
+            return Ok(());
+        }
+        Membership::require(usr, self.institution_id, InstitutionRole::Admin)
+    }
+
+    /// Stops posting events to the subscription.
+    ///
+    /// Its delivery log is kept.
+    pub fn deactivate(&self) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::webhook_subscriptions::dsl::active;
+
+        Ok(diesel::update(self)
+            .set(active.eq(false))
+            .get_result::<Self>(&connect_to_db())?)
+    }
+
+    /// Retrieves the deliveries to the subscription, newest first
+    pub fn deliveries(&self) -> Result<Vec<WebhookDelivery>, ResError> {
+        use crate::diesel_cfg::schema::webhook_deliveries::dsl::id;
+
+        Ok(WebhookDelivery::belonging_to(self)
+            .order(id.desc())
+            .load::<WebhookDelivery>(&connect_to_db())?)
+    }
+}
+
+impl WebhookDelivery {
+    /// Finds a delivery by its primary key
+    pub fn find_by_id(pk: i32) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::webhook_deliveries::dsl::webhook_deliveries;
+
+        Ok(webhook_deliveries
+            .find(pk)
+            .first::<Self>(&connect_to_db())?)
+    }
+
+    /// Posts the delivery to its subscription, logging the attempt.
+    ///
+    /// Deliveries to inactive subscriptions are dropped. An attempt
+    /// the endpoint doesn't accept is an error, so the job retries it.
+    pub async fn attempt(&self) -> Result<(), ResError> {
+        use crate::diesel_cfg::schema::webhook_deliveries::dsl::*;
+
+        let subscription = WebhookSubscription::find_by_id(self.subscription_id)?;
+        if !subscription.active {
+            debug!(
+                "Webhooks: Dropped delivery {} to inactive subscription {}",
+                self.id, subscription.id
+            );
+            return Ok(());
+        }
+
+        let outcome = post_delivery(&subscription, self).await;
+        let conn = connect_to_db();
+        let tries = attempts.eq(attempts + 1);
+
+        match outcome {
+            Ok((code, body)) if (200..300).contains(&code) => {
+                diesel::update(self)
+                    .set((
+                        tries,
+                        status.eq(DeliveryStatus::Delivered),
+                        response_status.eq(i32::from(code)),
+                        response_body.eq(truncate(&body)),
+                        last_error.eq(None::<String>),
+                        delivered_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(&conn)?;
+                Ok(())
+            }
+            Ok((code, body)) => {
+                diesel::update(self)
+                    .set((
+                        tries,
+                        status.eq(DeliveryStatus::Failed),
+                        response_status.eq(i32::from(code)),
+                        response_body.eq(truncate(&body)),
+                        last_error.eq(format!("Endpoint responded {}", code)),
+                    ))
+                    .execute(&conn)?;
+                Err(ResError::new(
+                    format!("Webhooks: Delivery {} got {}", self.id, code),
+                    502,
+                ))
+            }
+            Err(e) => {
+                diesel::update(self)
+                    .set((
+                        tries,
+                        status.eq(DeliveryStatus::Failed),
+                        response_status.eq(None::<i32>),
+                        response_body.eq(None::<String>),
+                        last_error.eq(&e),
+                    ))
+                    .execute(&conn)?;
+                Err(ResError::new(
+                    format!("Webhooks: Delivery {} failed: {}", self.id, e),
+                    502,
+                ))
+            }
+        }
+    }
+
+    /// Posts the delivery again, in the background
+    pub fn replay(&self) -> Result<Self, ResError> {
+        use crate::diesel_cfg::schema::webhook_deliveries::dsl::status;
+
+        let delivery = diesel::update(self)
+            .set(status.eq(DeliveryStatus::Pending))
+            .get_result::<Self>(&connect_to_db())?;
+        JobKind::DeliverWebhook {
+            delivery_id: delivery.id,
+        }
+        .enqueue()?;
+        Ok(delivery)
+    }
+}
+
+/// The start of a response body, as kept in the log
+fn truncate(body: &str) -> String {
+    body.chars().take(RESPONSE_LOG_LEN).collect()
+}
@@ -0,0 +1,63 @@
+use super::models::{NewSubscription, WebhookEvent, WebhookSubscription};
+use crate::apps::user::{
+    models::{AccessLevel, NewUser, User},
+    tests::DB_URL,
+};
+
+use std::borrow::Cow;
+
+/// Creates a user of the access level, or finds the one
+/// created by an earlier run
+fn user(username: &str, level: AccessLevel) -> User {
+    let email = format!("{}@webhooks.co", username);
+    let mut new_user = NewUser {
+        username: Cow::Borrowed(username),
+        password: Cow::Borrowed("password"),
+        access_level: Some(level as i32),
+    };
+
+    new_user
+        .save(&email)
+        .or_else(|_| User::find_by_email(&email).map(|mut found| found.remove(0)))
+        .unwrap()
+}
+
+/// A subscription to the events of all institutions
+fn all_institutions() -> NewSubscription {
+    NewSubscription {
+        url: "https://integrator.example.com/hooks".into(),
+        events: vec![WebhookEvent::IdentificationCreated],
+        institution_id: None,
+    }
+}
+
+#[test]
+fn api_clients_manage_the_subscriptions_they_create() {
+    let _ = *DB_URL;
+    let client = user("webhookclient", AccessLevel::ApiClient);
+    let other_client = user("webhookotherclient", AccessLevel::ApiClient);
+
+    let created = WebhookSubscription::create(&all_institutions(), &client).unwrap();
+    let subscription = created.subscription;
+    assert_eq!(subscription.created_by, Some(client.id));
+
+    assert!(subscription.authorize(&client).is_ok());
+    assert!(WebhookSubscription::visible_to(&client)
+        .unwrap()
+        .iter()
+        .any(|s| s.id == subscription.id));
+
+    let err = subscription.authorize(&other_client).unwrap_err();
+    assert_eq!(err.status, 401);
+}
+
+#[test]
+fn users_cant_subscribe_to_all_institutions() {
+    let _ = *DB_URL;
+    let usr = user("webhookuser", AccessLevel::Usualuser);
+
+    let err = WebhookSubscription::create(&all_institutions(), &usr)
+        .err()
+        .unwrap();
+    assert_eq!(err.status, 401);
+}
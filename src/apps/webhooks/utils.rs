@@ -0,0 +1,217 @@
+//! Webhook helpers
+use super::models::{WebhookDelivery, WebhookSubscription};
+
+use chrono::Utc;
+use futures::future::{ready, Ready};
+use hyper::{
+    client::{connect::dns::Name, HttpConnector},
+    header::CONTENT_TYPE,
+    service::Service,
+    Body, Client, Request,
+};
+use hyper_tls::HttpsConnector;
+use ring::hmac;
+use serde_json::json;
+
+use std::{
+    fmt::Write,
+    io,
+    net::IpAddr,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// How long an endpoint has to respond
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signs a message with HMAC-SHA256.
+///
+/// # Returns
+/// The hex encoded signature, prefixed with the algorithm
+///
+/// ```rust
+/// use got_ya_id::apps::webhooks::utils::sign;
+///
+/// assert_eq!(
+///     sign("key", "The quick brown fox jumps over the lazy dog"),
+///     "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+/// );
+/// ```
+pub fn sign(secret: &str, message: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, message.as_bytes());
+
+    let mut signature = String::from("sha256=");
+    for byte in tag.as_ref() {
+        write!(signature, "{:02x}", byte).unwrap();
+    }
+    signature
+}
+
+/// Whether an address is internal: loopback, private, link-local,
+/// or otherwise not on the public internet. Webhooks aren't posted
+/// to internal addresses.
+///
+/// ```rust
+/// use got_ya_id::apps::webhooks::utils::is_internal;
+///
+/// assert!(is_internal("127.0.0.1".parse().unwrap()));
+/// assert!(is_internal("10.1.2.3".parse().unwrap()));
+/// assert!(is_internal("169.254.169.254".parse().unwrap()));
+/// assert!(is_internal("100.64.0.1".parse().unwrap()));
+/// assert!(is_internal("::1".parse().unwrap()));
+/// assert!(is_internal("fd12::1".parse().unwrap()));
+/// assert!(is_internal("::ffff:192.168.0.1".parse().unwrap()));
+/// assert!(!is_internal("93.184.216.34".parse().unwrap()));
+/// assert!(!is_internal("2606:2800:220:1::".parse().unwrap()));
+/// ```
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Shared address space, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7, and link-local, fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                // IPv4 mapped and compatible addresses
+                || ip.to_ipv4().map_or(false, |ip| is_internal(IpAddr::V4(ip)))
+        }
+    }
+}
+
+/// Resolves the host of a webhook url, and fails if it isn't
+/// an http url, or resolves to an internal address
+///
+/// # Returns
+/// The addresses the host resolved to, all of them public
+pub async fn check_public(link: &str) -> Result<Vec<IpAddr>, String> {
+    let parsed = url::Url::parse(link).map_err(|e| format!("{}: {}", link, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{} isn't an http url", link));
+    }
+    let port = parsed.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<IpAddr> = match parsed.host() {
+        Some(url::Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(url::Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(url::Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| format!("{}: {}", link, e))?
+            .map(|addr| addr.ip())
+            .collect(),
+        None => return Err(format!("{} has no host", link)),
+    };
+    if addrs.is_empty() || addrs.iter().copied().any(is_internal) {
+        return Err(format!("{} doesn't resolve to a public address", link));
+    }
+    Ok(addrs)
+}
+
+/// Resolves every host to the addresses `check_public` vetted,
+/// so the connection can't be pointed elsewhere by resolving
+/// the host a second time
+#[derive(Clone)]
+struct PinnedResolver(Vec<IpAddr>);
+
+impl Service<Name> for PinnedResolver {
+    type Response = std::vec::IntoIter<IpAddr>;
+    type Error = io::Error;
+    type Future = Ready<Result<Self::Response, io::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Name) -> Self::Future {
+        ready(Ok(self.0.clone().into_iter()))
+    }
+}
+
+/// Posts a signed JSON body to a webhook url, with any extra headers.
+///
+/// The `X-Gotya-Signature` header signs `"{timestamp}.{body}"`
+/// with the secret, the timestamp being sent in
+/// `X-Gotya-Timestamp`. Receivers should reject stale timestamps.
+///
+/// Urls resolving to internal addresses are refused, the connection
+/// only goes to the addresses that were checked, and redirects
+/// aren't followed.
+///
+/// # Returns
+/// The status and body of the response
+pub async fn signed_post(
+    link: &str,
+    secret: &str,
+    body: String,
+    headers: &[(&str, String)],
+) -> Result<(u16, String), String> {
+    let addrs = check_public(link).await?;
+
+    let timestamp = Utc::now().timestamp().to_string();
+    let signature = sign(secret, &format!("{}.{}", timestamp, body));
+
+    let mut request = Request::post(link)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Gotya-Timestamp", timestamp.as_str())
+        .header("X-Gotya-Signature", signature.as_str());
+    for (name, value) in headers {
+        request = request.header(*name, value.as_str());
+    }
+    let request = request.body(Body::from(body)).map_err(|e| e.to_string())?;
+
+    let mut http = HttpConnector::new_with_resolver(PinnedResolver(addrs));
+    http.enforce_http(false);
+    http.set_connect_timeout(Some(TIMEOUT));
+    let tls = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    let client = Client::builder().build::<_, Body>(HttpsConnector::from((http, tls.into())));
+
+    let exchange = async {
+        let res = client.request(request).await?;
+        let status = res.status().as_u16();
+        let bytes = hyper::body::to_bytes(res.into_body()).await?;
+        Ok::<_, hyper::Error>((status, String::from_utf8_lossy(&bytes).into_owned()))
+    };
+    tokio::time::timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("{} didn't respond in time", link))?
+        .map_err(|e| e.to_string())
+}
+
+/// Posts a delivery to the url of its subscription, signed
+/// with the subscription secret
+///
+/// # Returns
+/// The status and body of the response
+pub async fn post_delivery(
+    subscription: &WebhookSubscription,
+    delivery: &WebhookDelivery,
+) -> Result<(u16, String), String> {
+    let body = json!({
+        "id": delivery.id,
+        "event": delivery.event,
+        "created_at": delivery.created_at.timestamp(),
+        "data": delivery.payload,
+    })
+    .to_string();
+
+    let headers = [
+        ("X-Gotya-Event", delivery.event.as_str().to_string()),
+        ("X-Gotya-Delivery", delivery.id.to_string()),
+    ];
+    signed_post(&subscription.url, &subscription.secret, body, &headers).await
+}
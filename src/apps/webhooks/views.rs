@@ -0,0 +1,149 @@
+//! Implementations of Http endpoints for the Webhooks resource
+
+use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
+
+use super::models::{NewSubscription, WebhookDelivery, WebhookSubscription};
+use crate::{
+    apps::user::models::User,
+    core::response::{err, respond},
+    hashmap,
+};
+
+use validator::Validate;
+
+/// Subscribes a url to events of an institution, or of all
+/// institutions if no `institution_id` is given.
+///
+/// Subscriptions are created through the account of the API
+/// client, or staff member, that manages them.
+///
+/// Events are `identification.created`, `identification.found`,
+/// `claim.matched` and `ownership.transferred`. The secret that
+/// deliveries are signed with is only shown in this response.
+///
+/// # Url
+/// `/webhooks`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// API clients, admins of the institution of the subscription,
+/// or moderators
+///
+/// ## Example
+/// ```json
+/// {
+///     "url": "https://security.example.ac.ke/lost-and-found/hooks",
+///     "events": ["identification.created", "ownership.transferred"],
+///     "institution_id": 1
+/// }
+/// ```
+pub async fn create_subscription(
+    req: HttpRequest,
+    data: web::Json<NewSubscription>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = data.validate() {
+        return err("400", e.to_string()).await;
+    }
+    let user = User::from_token(&req)?;
+
+    let subscription = WebhookSubscription::create(&data, &user)?;
+    let msg = hashmap!["status" => "201",
+            "message" => "Success. Webhook subscribed"];
+
+    respond(msg, Some(subscription), None).unwrap().await
+}
+
+/// Retrieves the webhook subscriptions the user created, and
+/// those of the institutions they are an admin of. Moderators
+/// get all subscriptions.
+///
+/// # Url
+/// `/webhooks`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+pub async fn get_subscriptions(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let subscriptions = WebhookSubscription::visible_to(&user)?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Webhooks retrieved"];
+
+    respond(msg, Some(subscriptions), None).unwrap().await
+}
+
+/// Stops posting events to a subscription. Its deliveries are kept.
+///
+/// # Url
+/// `/webhooks/{pk}`
+///
+/// # Method
+/// `DELETE`
+///
+/// #### Authentication required
+/// The creator of the subscription, admins of its institution,
+/// or moderators
+pub async fn deactivate_subscription(
+    req: HttpRequest,
+    pk: web::Path<i32>,
+) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let subscription = WebhookSubscription::find_by_id(*pk)?;
+    subscription.authorize(&user)?;
+    let subscription = subscription.deactivate()?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Webhook deactivated"];
+
+    respond(msg, Some(subscription), None).unwrap().await
+}
+
+/// Retrieves the delivery log of a subscription, newest first
+///
+/// # Url
+/// `/webhooks/{pk}/deliveries`
+///
+/// # Method
+/// `GET`
+///
+/// #### Authentication required
+/// The creator of the subscription, admins of its institution,
+/// or moderators
+pub async fn get_deliveries(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let subscription = WebhookSubscription::find_by_id(*pk)?;
+    subscription.authorize(&user)?;
+    let deliveries = subscription.deliveries()?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Deliveries retrieved"];
+
+    respond(msg, Some(deliveries), None).unwrap().await
+}
+
+/// Posts a delivery to its subscription again
+///
+/// # Url
+/// `/webhooks/deliveries/{pk}/replay`
+///
+/// # Method
+/// `POST`
+///
+/// #### Authentication required
+/// The creator of the subscription, admins of its institution,
+/// or moderators
+pub async fn replay_delivery(req: HttpRequest, pk: web::Path<i32>) -> Result<HttpResponse, Error> {
+    let user = User::from_token(&req)?;
+
+    let delivery = WebhookDelivery::find_by_id(*pk)?;
+    WebhookSubscription::find_by_id(delivery.subscription_id)?.authorize(&user)?;
+    let delivery = delivery.replay()?;
+    let msg = hashmap!["status" => "200",
+            "message" => "Success. Delivery queued"];
+
+    respond(msg, Some(delivery), None).unwrap().await
+}
@@ -13,8 +13,12 @@ use tera::Tera;
 use got_ya_id::{
     apps::{
         api,
+        events::broker,
+        institution::import::{self, InstitutionRow},
+        jobs::{scheduler, worker},
         user::{models::OClient, utils::create_oauth_client},
     },
+    core::mail,
     diesel_cfg::config::seed_admin_user,
 };
 
@@ -25,9 +29,32 @@ async fn main() -> io::Result<()> {
 
     // env::set_var("RUST_LOG", "debug");
     env_logger::init();
+
+    // Subcommands run instead of the server
+    let args = env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some(import::CLI_COMMAND) {
+        let report = InstitutionRow::import_from_cli(&args[2..])
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.msg))?;
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+
+        // Rows failed, and the import was undone
+        if !report.dry_run && !report.committed {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Import rolled back. Fix the rows reported, or import with --partial",
+            ));
+        }
+        return Ok(());
+    }
+
     let tera = Tera::new("src/templates/**/*").unwrap();
     seed_admin_user().await;
 
+    let mailer = mail::from_env().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    worker::spawn_workers(mailer.clone());
+    scheduler::spawn_scheduler();
+    broker::spawn_listener();
+
     let data = OClient {
         client: create_oauth_client(),
     };
@@ -42,6 +69,7 @@ async fn main() -> io::Result<()> {
             .data(web::JsonConfig::default().limit(8192))
             .data(data.clone())
             .data(tera.clone())
+            .data(mailer.clone())
             .app_data(web::JsonConfig::default().error_handler(|err, _req| {
                 error::InternalError::from_response(
                     "",
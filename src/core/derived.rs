@@ -8,3 +8,69 @@ macro_rules! hashmap {
     $(map.insert($key, $val);)*
         map
 }}}
+
+#[macro_export]
+/// Maps a fieldless enum onto a `VARCHAR` column
+///
+/// Each variant is stored as the string given to it. The enum
+/// should derive `AsExpression` and `FromSqlRow` with
+/// `#[sql_type = "diesel::sql_types::Varchar"]`.
+///
+/// # Example
+/// ```ignore
+/// varchar_enum!(JobStatus {
+///     Queued => "queued",
+///     Done => "done"
+/// });
+/// ```
+macro_rules! varchar_enum {
+    ($name: ident { $($variant: ident => $value: expr), * }) => {
+        impl $name {
+            /// The stored representation of the variant
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $value,)*
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err(format!("Unknown {} `{}`", stringify!($name), s)),
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}", self.as_str())
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Varchar, diesel::pg::Pg> for $name {
+            fn to_sql<W: std::io::Write>(
+                &self,
+                out: &mut diesel::serialize::Output<W, diesel::pg::Pg>,
+            ) -> diesel::serialize::Result {
+                <str as diesel::serialize::ToSql<diesel::sql_types::Varchar, diesel::pg::Pg>>::to_sql(
+                    self.as_str(),
+                    out,
+                )
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Varchar, diesel::pg::Pg> for $name {
+            fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+                let value = <String as diesel::deserialize::FromSql<
+                    diesel::sql_types::Varchar,
+                    diesel::pg::Pg,
+                >>::from_sql(bytes)?;
+                value.parse::<$name>().map_err(|e| e.into())
+            }
+        }
+    };
+}
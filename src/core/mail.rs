@@ -1,83 +1,253 @@
 //! Handles Mail realed functions
+//!
+//! Mails are handed to a `MailTransport`, picked by the
+//! `MAIL_TRANSPORT` ENV variable:
+//! - `smtp`: Sends over SMTP. The default
+//! - `file`: Drops `.eml` files in `MAIL_DROP_DIR`
+//! - `log`: Logs the mails
+//! - `memory`: Keeps the mails in memory. Meant for tests
+//!
+//! The transport is configured once, and shared by the server
+//! workers through app data, and by the job workers sending the
+//! email outbox.
+use lettre::{
+    smtp::authentication::Credentials, SendableEmail, SmtpClient, SmtpTransport, Transport,
+};
 use lettre_email::Email;
 
-use lettre::smtp::authentication::Credentials;
-use lettre::{smtp, SmtpClient, SmtpTransport, Transport};
-use std::{env, process};
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
-/// Enables sending of email
-///
-/// # Fields
-/// - email: Email
-///     Creates the email instance holding the data to
-///     be sent
-/// - sender: SmtpTransport || SmtpClient
-///     SMTP Protocol instance to send the email
-///     over network
+/// Sender address used if `MAIL_ADDR` is missing,
+/// by transports other than SMTP
+const DEFAULT_SENDER: &str = "no-reply@got-ya-id.local";
+
+/// Directory `.eml` files are dropped in if
+/// `MAIL_DROP_DIR` is missing
+const DEFAULT_DROP_DIR: &str = "mail";
+
+/// An email to a single recipient
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mail {
-    email: Email,
-    mailer: SmtpTransport,
+    pub to_addr: String,
+    pub to_name: String,
+    pub subject: String,
+    /// HTML body
+    pub content: String,
+    /// Plain text alternative of the HTML body
+    pub text: Option<String>,
+    /// Link sent in the `List-Unsubscribe` header
+    pub unsubscribe: Option<String>,
 }
 
-/// Returns a Mail struct
 impl Mail {
-    pub async fn new<'a>(
-        to_addr: &'a str,
-        to_name: &'a str,
-        subject: &'a str,
-        content: &'a str,
-    ) -> Result<Mail, String> {
-        // ENV
-        //let SMTP_CLIENT;
-        //let MAIL_USERNAME;
-        //let MAIL PASS
-        let mut mail_vars: Vec<String> = vec![];
-        get_env_var(
-            vec!["SMTP_CLIENT", "MAIL_ADDR", "MAIL_USERNAME", "MAIL_PASS"],
-            &mut mail_vars,
-        );
-        let email = Email::builder()
-            .to((to_addr, to_name))
-            .from(mail_vars[1].as_str())
-            .subject(subject)
-            .html(content)
-            .build();
-        let email = match email {
-            Ok(em) => em,
-            Err(e) => return Err(e.to_string()),
-        };
-
-        let creds = Credentials::new(
-            mail_vars.get(2).unwrap().into(),
-            mail_vars.get(3).unwrap().into(),
-        );
-        let mail_client = SmtpClient::new_simple(
-            mail_vars[0].as_ref(), //smtpclient//
+    pub fn new(to_addr: &str, to_name: &str, subject: &str, content: &str) -> Self {
+        Mail {
+            to_addr: to_addr.into(),
+            to_name: to_name.into(),
+            subject: subject.into(),
+            content: content.into(),
+            text: None,
+            unsubscribe: None,
+        }
+    }
+
+    /// Adds a plain text alternative, sending the
+    /// mail as multipart
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Adds a `List-Unsubscribe` header of the link, that mail
+    /// clients may POST to unsubscribe in one click (RFC 8058)
+    pub fn with_unsubscribe(mut self, link: &str) -> Self {
+        self.unsubscribe = Some(link.into());
+        self
+    }
+
+    /// Builds the message sent by `sender`
+    fn build(&self, sender: &str) -> Result<Email, String> {
+        let mut builder = Email::builder()
+            .to((self.to_addr.as_str(), self.to_name.as_str()))
+            .from(sender)
+            .subject(self.subject.as_str());
+        if let Some(link) = &self.unsubscribe {
+            builder = builder
+                .header(("List-Unsubscribe", format!("<{}>", link)))
+                .header(("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"));
+        }
+
+        match &self.text {
+            Some(text) => builder.alternative(self.content.as_str(), text.as_str()),
+            None => builder.html(self.content.as_str()),
+        }
+        .build()
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Hands mails over for delivery
+pub trait MailTransport: Send + Sync {
+    /// Sends the mail.
+    ///
+    /// # Returns
+    /// The response of the transport, e.g that of the SMTP server
+    fn send(&self, mail: &Mail) -> Result<String, String>;
+}
+
+/// Sends mails over SMTP
+pub struct SmtpMail {
+    sender: String,
+    mailer: Mutex<SmtpTransport>,
+}
+
+impl SmtpMail {
+    /// Configures the SMTP transport from the `SMTP_CLIENT`,
+    /// `MAIL_ADDR`, `MAIL_USERNAME` and `MAIL_PASS` ENV variables
+    pub fn from_env() -> Result<Self, String> {
+        let vars = get_env_var(&["SMTP_CLIENT", "MAIL_ADDR", "MAIL_USERNAME", "MAIL_PASS"])?;
+
+        let creds = Credentials::new(vars[2].clone(), vars[3].clone());
+        let mailer = SmtpClient::new_simple(&vars[0])
+            .map_err(|e| e.to_string())?
+            .credentials(creds)
+            .transport();
+
+        Ok(SmtpMail {
+            sender: vars[1].clone(),
+            mailer: Mutex::new(mailer),
+        })
+    }
+}
+
+impl MailTransport for SmtpMail {
+    fn send(&self, mail: &Mail) -> Result<String, String> {
+        let email = mail.build(&self.sender)?;
+
+        let response = self
+            .mailer
+            .lock()
+            .unwrap()
+            .send(email.into())
+            .map_err(|e| e.to_string())?;
+        Ok(format!("{} {}", response.code, response.message.join(" ")))
+    }
+}
+
+/// Writes mails as `.eml` files to a directory
+pub struct FileMail {
+    sender: String,
+    dir: PathBuf,
+}
+
+impl FileMail {
+    pub fn new(sender: &str, dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        Ok(FileMail {
+            sender: sender.into(),
+            dir,
+        })
+    }
+}
+
+impl MailTransport for FileMail {
+    fn send(&self, mail: &Mail) -> Result<String, String> {
+        let email: SendableEmail = mail.build(&self.sender)?.into();
+        let name = format!(
+            "{}.eml",
+            email
+                .message_id()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-')
+                .collect::<String>()
         );
 
-        let mailer = if let Err(e) = mail_client {
-            return Err(e.to_string());
-        } else {
-            mail_client.unwrap().credentials(creds).transport()
-        };
+        let message = email.message_to_string().map_err(|e| e.to_string())?;
+        let path = self.dir.join(name);
+        fs::write(&path, message).map_err(|e| format!("{}: {}", path.display(), e))?;
 
-        Ok(Mail { email, mailer })
+        Ok(format!("Written to {}", path.display()))
     }
+}
+
+/// Logs mails instead of sending them
+pub struct LogMail;
 
-    /// Sends the email
-    pub async fn send(&mut self) -> Result<smtp::response::Response, smtp::error::Error> {
-        self.mailer.send(self.email.clone().into())
+impl MailTransport for LogMail {
+    fn send(&self, mail: &Mail) -> Result<String, String> {
+        info!(
+            "Mail to {} <{}>: {}\n{}",
+            mail.to_name, mail.to_addr, mail.subject, mail.content
+        );
+        Ok("Logged".into())
     }
 }
 
-/// Retrieves ENV Variable given the Keys
-pub fn get_env_var<'a>(keys: Vec<&'a str>, values: &mut Vec<String>) {
-    for key in keys.iter() {
-        let val = env::var((*key).to_lowercase()).unwrap_or_else(|_er| {
-            error!("Error configuring mail");
-            error!("Missing ENV variable -> {}", key);
-            process::exit(0);
-        });
-        values.push(val);
+/// Keeps mails in memory
+///
+/// ```rust
+/// use got_ya_id::core::mail::{Mail, MailTransport, MemoryMail};
+///
+/// let transport = MemoryMail::default();
+/// transport.send(&Mail::new("a@b.co", "a", "Hi", "<p>Hi</p>")).unwrap();
+///
+/// assert_eq!(transport.sent()[0].subject, "Hi");
+/// ```
+#[derive(Default)]
+pub struct MemoryMail {
+    sent: Mutex<Vec<Mail>>,
+}
+
+impl MemoryMail {
+    /// The mails sent so far
+    pub fn sent(&self) -> Vec<Mail> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl MailTransport for MemoryMail {
+    fn send(&self, mail: &Mail) -> Result<String, String> {
+        self.sent.lock().unwrap().push(mail.clone());
+        Ok("Kept in memory".into())
     }
 }
+
+/// Configures the transport named by `MAIL_TRANSPORT`
+pub fn from_env() -> Result<Arc<dyn MailTransport>, String> {
+    let kind = env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "smtp".into());
+    let sender = env::var("mail_addr").unwrap_or_else(|_| DEFAULT_SENDER.into());
+
+    let transport: Arc<dyn MailTransport> = match kind.to_lowercase().as_str() {
+        "smtp" => Arc::new(SmtpMail::from_env()?),
+        "file" => {
+            let dir = env::var("MAIL_DROP_DIR").unwrap_or_else(|_| DEFAULT_DROP_DIR.into());
+            Arc::new(FileMail::new(&sender, dir.into())?)
+        }
+        "log" => Arc::new(LogMail),
+        "memory" => Arc::new(MemoryMail::default()),
+        other => return Err(format!("Unknown MAIL_TRANSPORT {}", other)),
+    };
+    Ok(transport)
+}
+
+/// Retrieves ENV Variables given the Keys
+///
+/// # Returns
+/// The values, in the order of the keys. An error naming
+/// the first missing variable.
+pub fn get_env_var(keys: &[&str]) -> Result<Vec<String>, String> {
+    keys.iter()
+        .map(|key| {
+            env::var(key.to_lowercase()).map_err(|_| {
+                error!("Error configuring mail");
+                error!("Missing ENV variable -> {}", key);
+                format!("Missing ENV variable {}", key)
+            })
+        })
+        .collect()
+}
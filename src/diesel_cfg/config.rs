@@ -36,6 +36,16 @@ pub fn connect_to_db() -> PgConnection {
     PgConnection::establish(&DB_URL).expect("Error Initializing the database connection")
 }
 
+/// Connects to the database, for callers that outlive an outage
+pub fn try_connect_to_db() -> ConnectionResult<PgConnection> {
+    PgConnection::establish(&DB_URL)
+}
+
+/// The Postgres connection URL of the database
+pub fn database_url() -> &'static str {
+    &DB_URL
+}
+
 /// Seeds a user with admin access level to the database
 pub async fn seed_admin_user() {
     let uname = env::var("ADMIN_USERNAME").unwrap_or({
@@ -0,0 +1,18 @@
+-- Background jobs queue
+-- Workers pick runnable jobs with `FOR UPDATE SKIP LOCKED`
+CREATE TABLE IF NOT EXISTS jobs (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    run_at timestamp without time zone not null default (now() at time zone 'utc'),
+    last_error TEXT,
+    created_at timestamp without time zone not null default (now() at time zone 'utc'),
+    updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+CREATE INDEX IF NOT EXISTS jobs_runnable_idx ON jobs (status, run_at);
+
+SELECT diesel_manage_updated_at('jobs');
@@ -0,0 +1,5 @@
+-- Last runs of periodic jobs
+CREATE TABLE IF NOT EXISTS job_schedules (
+    name VARCHAR PRIMARY KEY,
+    last_run_at timestamp without time zone
+);
@@ -0,0 +1,3 @@
+DELETE FROM jobs WHERE kind = 'rematch_sweep' AND status = 'queued';
+DROP TABLE IF EXISTS claim_blocks;
+DROP TABLE IF EXISTS identification_blocks;
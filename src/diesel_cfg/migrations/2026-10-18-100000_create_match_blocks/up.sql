@@ -0,0 +1,22 @@
+-- Blocking keys of Identifications and Claims.
+-- Only pairs sharing a key are scored by the matcher.
+-- Keys of existing rows are filled by the match sweep queued below.
+CREATE TABLE identification_blocks (
+    identification_id INTEGER REFERENCES identifications (id) ON DELETE CASCADE NOT NULL,
+    block_key VARCHAR NOT NULL,
+    PRIMARY KEY (identification_id, block_key)
+);
+
+CREATE INDEX identification_blocks_key_idx ON identification_blocks (block_key);
+
+CREATE TABLE claim_blocks (
+    claim_id INTEGER REFERENCES claimed_identifications (id) ON DELETE CASCADE NOT NULL,
+    block_key VARCHAR NOT NULL,
+    PRIMARY KEY (claim_id, block_key)
+);
+
+CREATE INDEX claim_blocks_key_idx ON claim_blocks (block_key);
+
+-- Keys are computed in the app. Until a sweep fills them, existing
+-- claims and Identifications share no key, and match nothing
+INSERT INTO jobs (kind, payload) VALUES ('rematch_sweep', '{"type": "rematch_sweep"}');
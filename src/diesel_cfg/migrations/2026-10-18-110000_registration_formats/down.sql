@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS claims_reg_no_canonical_idx;
+DROP INDEX IF EXISTS identifications_reg_no_canonical_idx;
+
+ALTER TABLE claimed_identifications DROP COLUMN IF EXISTS registration_no_canonical;
+ALTER TABLE identifications DROP COLUMN IF EXISTS registration_no_canonical;
+
+DROP TABLE IF EXISTS registration_formats;
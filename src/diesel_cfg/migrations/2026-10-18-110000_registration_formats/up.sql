@@ -0,0 +1,31 @@
+-- Registration number formats of an institution.
+-- `pattern` is matched against the trimmed, uppercased registration number,
+-- and `canonical` is the replacement template, e.g `$1$2-$3/$4`
+CREATE TABLE registration_formats (
+    id SERIAL PRIMARY KEY,
+    institution_id INTEGER REFERENCES institutions (id) ON DELETE CASCADE NOT NULL,
+    pattern VARCHAR NOT NULL,
+    canonical VARCHAR NOT NULL,
+    checksum VARCHAR,
+    description VARCHAR,
+    created_at timestamp without time zone not null default (now() at time zone 'utc'),
+    updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('registration_formats');
+
+-- Canonical registration numbers.
+-- Existing rows get the default canonical form: uppercased alphanumerics
+ALTER TABLE identifications ADD COLUMN IF NOT EXISTS registration_no_canonical VARCHAR;
+ALTER TABLE claimed_identifications ADD COLUMN IF NOT EXISTS registration_no_canonical VARCHAR;
+
+UPDATE identifications
+SET registration_no_canonical = upper(regexp_replace(registration_no, '[^[:alnum:]]', '', 'g'))
+WHERE registration_no IS NOT NULL;
+
+UPDATE claimed_identifications
+SET registration_no_canonical = upper(regexp_replace(registration_no, '[^[:alnum:]]', '', 'g'))
+WHERE registration_no IS NOT NULL;
+
+CREATE INDEX identifications_reg_no_canonical_idx ON identifications (registration_no_canonical);
+CREATE INDEX claims_reg_no_canonical_idx ON claimed_identifications (registration_no_canonical);
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS claimed_identifications_user_id_idx;
+
+ALTER TABLE claimed_identifications DROP COLUMN IF EXISTS document_type;
+
+-- Fails while users hold several claims. Claims aren't deleted
+-- to make room: resolve them before reverting
+ALTER TABLE claimed_identifications
+ADD CONSTRAINT unique_user_id_claims UNIQUE (user_id);
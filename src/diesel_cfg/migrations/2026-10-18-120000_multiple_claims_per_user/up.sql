@@ -0,0 +1,7 @@
+-- Users may hold several claims, one per lost document
+ALTER TABLE claimed_identifications DROP CONSTRAINT IF EXISTS unique_user_id_claims;
+
+ALTER TABLE claimed_identifications
+ADD COLUMN IF NOT EXISTS document_type VARCHAR NOT NULL DEFAULT 'student_id';
+
+CREATE INDEX claimed_identifications_user_id_idx ON claimed_identifications (user_id);
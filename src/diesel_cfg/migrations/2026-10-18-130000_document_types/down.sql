@@ -0,0 +1,8 @@
+UPDATE claimed_identifications SET course = '' WHERE course IS NULL;
+UPDATE identifications SET course = '' WHERE course IS NULL;
+
+ALTER TABLE claimed_identifications ALTER COLUMN course SET NOT NULL;
+ALTER TABLE identifications ALTER COLUMN course SET NOT NULL;
+
+DROP INDEX IF EXISTS identifications_document_type_idx;
+ALTER TABLE identifications DROP COLUMN IF EXISTS document_type;
@@ -0,0 +1,9 @@
+-- The kind of document an Identification is of
+ALTER TABLE identifications
+ADD COLUMN IF NOT EXISTS document_type VARCHAR NOT NULL DEFAULT 'student_id';
+
+CREATE INDEX identifications_document_type_idx ON identifications (document_type, is_found);
+
+-- Only some document types carry a course
+ALTER TABLE identifications ALTER COLUMN course DROP NOT NULL;
+ALTER TABLE claimed_identifications ALTER COLUMN course DROP NOT NULL;
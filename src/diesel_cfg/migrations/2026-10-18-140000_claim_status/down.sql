@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS claimed_identifications_status_idx;
+
+ALTER TABLE claimed_identifications DROP COLUMN IF EXISTS expiry_reminded_at;
+ALTER TABLE claimed_identifications DROP COLUMN IF EXISTS status_changed_at;
+ALTER TABLE claimed_identifications DROP COLUMN IF EXISTS status;
@@ -0,0 +1,15 @@
+-- Claim lifecycle: open, matched, verified, fulfilled, withdrawn, expired
+ALTER TABLE claimed_identifications
+ADD COLUMN IF NOT EXISTS status VARCHAR NOT NULL DEFAULT 'open';
+
+-- Claims expire some time after their last status change
+ALTER TABLE claimed_identifications
+ADD COLUMN IF NOT EXISTS status_changed_at timestamp without time zone not null default (now() at time zone 'utc');
+
+ALTER TABLE claimed_identifications
+ADD COLUMN IF NOT EXISTS expiry_reminded_at timestamp without time zone;
+
+UPDATE claimed_identifications c SET status = 'matched'
+WHERE EXISTS (SELECT 1 FROM matched_identifications m WHERE m.claim_id = c.id);
+
+CREATE INDEX claimed_identifications_status_idx ON claimed_identifications (status, status_changed_at);
@@ -0,0 +1,22 @@
+-- Requests of claimants for the ownership of an Identification,
+-- decided by its poster or a custodian of its institution
+CREATE TABLE IF NOT EXISTS ownership_requests (
+  id SERIAL PRIMARY KEY,
+  claim_id INTEGER NOT NULL REFERENCES claimed_identifications(id) ON DELETE CASCADE,
+  identification_id INTEGER NOT NULL REFERENCES identifications(id) ON DELETE CASCADE,
+  requested_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  status VARCHAR NOT NULL DEFAULT 'pending',
+  reason TEXT,
+  decided_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  decided_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('ownership_requests');
+
+-- A claim has at most one pending request per Identification
+CREATE UNIQUE INDEX ownership_requests_pending_unique
+ON ownership_requests (claim_id, identification_id) WHERE status = 'pending';
+
+CREATE INDEX ownership_requests_identification_idx ON ownership_requests (identification_id);
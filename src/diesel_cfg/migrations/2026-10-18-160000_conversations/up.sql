@@ -0,0 +1,41 @@
+-- Conversations between the finder and the claimant
+-- of a matched Identification and Claim
+CREATE TABLE IF NOT EXISTS conversations (
+  id SERIAL PRIMARY KEY,
+  claim_id INTEGER NOT NULL REFERENCES claimed_identifications(id) ON DELETE CASCADE,
+  identification_id INTEGER NOT NULL REFERENCES identifications(id) ON DELETE CASCADE,
+  claimant_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  finder_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  -- Whether each participant shows their contacts to the other
+  claimant_shares_contact BOOLEAN NOT NULL DEFAULT 'f',
+  finder_shares_contact BOOLEAN NOT NULL DEFAULT 'f',
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT conversations_pair_unique UNIQUE (claim_id, identification_id)
+);
+
+SELECT diesel_manage_updated_at('conversations');
+
+CREATE TABLE IF NOT EXISTS messages (
+  id SERIAL PRIMARY KEY,
+  conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+  sender_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  body TEXT NOT NULL,
+  -- When the recipient read the message
+  read_at timestamp without time zone,
+  -- Hidden from the recipient once reported enough
+  hidden BOOLEAN NOT NULL DEFAULT 'f',
+  created_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+CREATE INDEX messages_conversation_idx ON messages (conversation_id, id);
+
+-- Abuse reports on messages
+CREATE TABLE IF NOT EXISTS message_reports (
+  id SERIAL PRIMARY KEY,
+  message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+  reported_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  reason TEXT NOT NULL,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT message_reports_unique UNIQUE (message_id, reported_by)
+);
@@ -0,0 +1,15 @@
+-- In-app notifications of users
+CREATE TABLE IF NOT EXISTS notifications (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  kind VARCHAR NOT NULL,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  -- Ids of the records involved
+  data JSONB NOT NULL DEFAULT '{}',
+  read_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+CREATE INDEX notifications_user_idx ON notifications (user_id, id);
+CREATE INDEX notifications_unread_idx ON notifications (user_id) WHERE read_at IS NULL;
@@ -0,0 +1,44 @@
+-- Channels users receive each kind of notification on.
+-- A missing row falls back to the channel default
+CREATE TABLE IF NOT EXISTS notification_preferences (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  kind VARCHAR NOT NULL,
+  channel VARCHAR NOT NULL,
+  enabled BOOLEAN NOT NULL,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT notification_preferences_unique UNIQUE (user_id, kind, channel)
+);
+
+SELECT diesel_manage_updated_at('notification_preferences');
+
+-- Delivery settings of users' notifications
+CREATE TABLE IF NOT EXISTS notification_settings (
+  user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  digest VARCHAR NOT NULL DEFAULT 'off',
+  -- Local times emails are held back between
+  quiet_hours_start TIME,
+  quiet_hours_end TIME,
+  -- Minutes the user's local time is ahead of UTC
+  utc_offset INTEGER NOT NULL DEFAULT 0,
+  webhook_url VARCHAR,
+  -- Authorizes one-click unsubscribe links
+  unsubscribe_token VARCHAR NOT NULL UNIQUE,
+  last_digest_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('notification_settings');
+
+-- Emails held back for a digest, or until quiet hours end
+CREATE TABLE IF NOT EXISTS deferred_emails (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  kind VARCHAR NOT NULL,
+  subject VARCHAR NOT NULL,
+  created_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+CREATE INDEX deferred_emails_user_idx ON deferred_emails (user_id, id);
@@ -0,0 +1,38 @@
+-- Endpoints of institutions and integrators that events are posted to.
+-- Subscriptions without an institution get the events of all institutions
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+  id SERIAL PRIMARY KEY,
+  institution_id INTEGER REFERENCES institutions(id) ON DELETE CASCADE,
+  created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  url VARCHAR NOT NULL,
+  -- Key of the HMAC-SHA256 signatures of deliveries
+  secret VARCHAR NOT NULL,
+  events VARCHAR[] NOT NULL,
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('webhook_subscriptions');
+
+CREATE INDEX webhook_subscriptions_institution_idx ON webhook_subscriptions (institution_id);
+
+-- Log of the events posted to subscriptions
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id SERIAL PRIMARY KEY,
+  subscription_id INTEGER NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+  event VARCHAR NOT NULL,
+  payload JSONB NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  response_status INTEGER,
+  response_body TEXT,
+  last_error TEXT,
+  delivered_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('webhook_deliveries');
+
+CREATE INDEX webhook_deliveries_subscription_idx ON webhook_deliveries (subscription_id, id);
@@ -0,0 +1,22 @@
+-- Emails waiting to be sent, and a log of those sent.
+-- Rows are added in the transaction of the change that sends
+-- the email, and sent in the background
+CREATE TABLE IF NOT EXISTS email_outbox (
+  id SERIAL PRIMARY KEY,
+  to_addr VARCHAR NOT NULL,
+  to_name VARCHAR NOT NULL,
+  subject VARCHAR NOT NULL,
+  content TEXT NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  -- Response of the transport to the last attempt, e.g the SMTP reply
+  response TEXT,
+  last_error TEXT,
+  sent_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('email_outbox');
+
+CREATE INDEX email_outbox_status_idx ON email_outbox (status, id);
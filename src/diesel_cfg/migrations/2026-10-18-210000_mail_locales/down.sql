@@ -0,0 +1,2 @@
+ALTER TABLE email_outbox DROP COLUMN text_content;
+ALTER TABLE profiles DROP COLUMN locale;
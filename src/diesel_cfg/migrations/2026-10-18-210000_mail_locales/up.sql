@@ -0,0 +1,6 @@
+-- Language of the emails of a user, e.g 'sw'.
+-- The locale of their Google account is used if missing
+ALTER TABLE profiles ADD COLUMN locale VARCHAR;
+
+-- Plain text alternative of outbox emails
+ALTER TABLE email_outbox ADD COLUMN text_content TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE emails DROP COLUMN bounces;
+ALTER TABLE emails DROP COLUMN undeliverable;
@@ -0,0 +1,4 @@
+-- Bounces and complaints reported for an email address.
+-- Undeliverable emails are no longer mailed
+ALTER TABLE emails ADD COLUMN undeliverable BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE emails ADD COLUMN bounces INTEGER NOT NULL DEFAULT 0;
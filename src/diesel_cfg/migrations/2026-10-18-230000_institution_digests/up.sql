@@ -0,0 +1,17 @@
+-- Subscriptions to periodic emails of the IDs newly posted
+-- at an institution
+CREATE TABLE IF NOT EXISTS institution_digests (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  institution_id INTEGER NOT NULL REFERENCES institutions(id) ON DELETE CASCADE,
+  frequency VARCHAR NOT NULL DEFAULT 'daily',
+  -- Newest identification in a digest sent to the subscriber.
+  -- Only identifications posted after it are sent
+  last_identification_id INTEGER NOT NULL DEFAULT 0,
+  last_sent_at timestamp without time zone,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT institution_digests_unique UNIQUE (user_id, institution_id)
+);
+
+SELECT diesel_manage_updated_at('institution_digests');
@@ -0,0 +1,36 @@
+-- Searches users save to be alerted of matching Identifications,
+-- without filing a claim
+CREATE TABLE IF NOT EXISTS saved_searches (
+  id SERIAL PRIMARY KEY,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  label VARCHAR NOT NULL,
+  -- Part of the name on the Identification, matched case insensitively
+  name_fragment VARCHAR,
+  institution_id INTEGER REFERENCES institutions(id) ON DELETE CASCADE,
+  -- Centre and radius of the area the Identification was found in
+  latitude DOUBLE PRECISION,
+  longitude DOUBLE PRECISION,
+  radius_km DOUBLE PRECISION,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT saved_searches_area CHECK (
+    (latitude IS NULL) = (longitude IS NULL) AND (latitude IS NULL) = (radius_km IS NULL)
+  ),
+  CONSTRAINT saved_searches_criteria CHECK (
+    name_fragment IS NOT NULL OR institution_id IS NOT NULL OR latitude IS NOT NULL
+  )
+);
+
+SELECT diesel_manage_updated_at('saved_searches');
+
+CREATE INDEX saved_searches_user_idx ON saved_searches (user_id);
+
+-- Identifications a saved search alerted of.
+-- Each Identification alerts once per search
+CREATE TABLE IF NOT EXISTS saved_search_alerts (
+  id SERIAL PRIMARY KEY,
+  saved_search_id INTEGER NOT NULL REFERENCES saved_searches(id) ON DELETE CASCADE,
+  identification_id INTEGER NOT NULL REFERENCES identifications(id) ON DELETE CASCADE,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT saved_search_alerts_unique UNIQUE (saved_search_id, identification_id)
+);
@@ -0,0 +1,19 @@
+-- Email domains verified as belonging to an institution.
+-- A verified email on one of them proves membership
+CREATE TABLE IF NOT EXISTS institution_domains (
+  id SERIAL PRIMARY KEY,
+  institution_id INTEGER NOT NULL REFERENCES institutions(id) ON DELETE CASCADE,
+  -- Lowercase, e.g `uonbi.ac.ke`
+  domain VARCHAR NOT NULL,
+  -- Whether emails on subdomains, e.g `students.uonbi.ac.ke`, match too
+  include_subdomains BOOLEAN NOT NULL DEFAULT TRUE,
+  added_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  -- A domain belongs to one institution
+  CONSTRAINT institution_domains_unique UNIQUE (domain)
+);
+
+SELECT diesel_manage_updated_at('institution_domains');
+
+CREATE INDEX institution_domains_institution_idx ON institution_domains (institution_id);
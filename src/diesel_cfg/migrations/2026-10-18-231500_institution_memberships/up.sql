@@ -0,0 +1,17 @@
+-- Roles of users in an institution.
+-- Admins manage the institution and its staff, custodians hold
+-- and hand over its IDs, and members joined on a verified domain
+CREATE TABLE IF NOT EXISTS institution_memberships (
+  id SERIAL PRIMARY KEY,
+  institution_id INTEGER NOT NULL REFERENCES institutions(id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  role VARCHAR NOT NULL DEFAULT 'member',
+  invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT institution_memberships_unique UNIQUE (institution_id, user_id)
+);
+
+SELECT diesel_manage_updated_at('institution_memberships');
+
+CREATE INDEX institution_memberships_user_idx ON institution_memberships (user_id);
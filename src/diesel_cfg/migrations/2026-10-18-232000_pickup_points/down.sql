@@ -0,0 +1,3 @@
+ALTER TABLE identifications DROP COLUMN IF EXISTS pickup_point_id;
+DROP TABLE IF EXISTS pickup_points;
+DROP TABLE IF EXISTS campuses;
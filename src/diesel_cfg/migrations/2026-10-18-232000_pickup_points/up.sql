@@ -0,0 +1,37 @@
+-- Campuses of an institution
+CREATE TABLE IF NOT EXISTS campuses (
+  id SERIAL PRIMARY KEY,
+  institution_id INTEGER NOT NULL REFERENCES institutions(id) ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  latitude DOUBLE PRECISION,
+  longitude DOUBLE PRECISION,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc'),
+  CONSTRAINT campuses_unique UNIQUE (institution_id, name)
+);
+
+SELECT diesel_manage_updated_at('campuses');
+
+-- Official places IDs are collected from, e.g a security desk or library
+CREATE TABLE IF NOT EXISTS pickup_points (
+  id SERIAL PRIMARY KEY,
+  institution_id INTEGER NOT NULL REFERENCES institutions(id) ON DELETE CASCADE,
+  campus_id INTEGER REFERENCES campuses(id) ON DELETE SET NULL,
+  name VARCHAR NOT NULL,
+  directions TEXT,
+  latitude DOUBLE PRECISION,
+  longitude DOUBLE PRECISION,
+  -- [{"day": "mon", "opens": "08:00", "closes": "17:00"}, ...]
+  opening_hours JSONB NOT NULL DEFAULT '[]',
+  -- Inactive points take no new IDs
+  is_active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at timestamp without time zone not null default (now() at time zone 'utc'),
+  updated_at timestamp without time zone not null default (now() at time zone 'utc')
+);
+
+SELECT diesel_manage_updated_at('pickup_points');
+
+CREATE INDEX pickup_points_institution_idx ON pickup_points (institution_id);
+
+ALTER TABLE identifications
+ADD COLUMN pickup_point_id INTEGER REFERENCES pickup_points(id) ON DELETE SET NULL;
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS institution_domains_verified_idx;
+ALTER TABLE institution_domains DROP CONSTRAINT institution_domains_unique;
+-- Fails while institutions propose the same domain
+ALTER TABLE institution_domains
+  ADD CONSTRAINT institution_domains_unique UNIQUE (domain);
+ALTER TABLE institution_domains
+  DROP COLUMN verified_by,
+  DROP COLUMN verified_at;
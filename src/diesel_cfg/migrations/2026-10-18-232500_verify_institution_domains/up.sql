@@ -0,0 +1,13 @@
+-- Domains only let users join an institution once a moderator
+-- has verified that they belong to it. Domains added before
+-- are left for a moderator to verify
+ALTER TABLE institution_domains
+  ADD COLUMN verified_at timestamp without time zone,
+  ADD COLUMN verified_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Institutions may propose the same domain. Only one can have it verified
+ALTER TABLE institution_domains DROP CONSTRAINT institution_domains_unique;
+ALTER TABLE institution_domains
+  ADD CONSTRAINT institution_domains_unique UNIQUE (institution_id, domain);
+CREATE UNIQUE INDEX institution_domains_verified_idx ON institution_domains (domain)
+  WHERE verified_at IS NOT NULL;
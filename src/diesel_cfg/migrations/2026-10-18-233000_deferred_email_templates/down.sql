@@ -0,0 +1,3 @@
+ALTER TABLE deferred_emails
+  DROP COLUMN context,
+  DROP COLUMN template;
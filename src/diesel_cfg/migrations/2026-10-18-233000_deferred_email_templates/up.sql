@@ -0,0 +1,6 @@
+-- Held back emails keep the templates and context they are
+-- rendered from, so they can be sent as themselves once quiet
+-- hours end. Emails held before only make it to a digest
+ALTER TABLE deferred_emails
+  ADD COLUMN template VARCHAR,
+  ADD COLUMN context JSONB;
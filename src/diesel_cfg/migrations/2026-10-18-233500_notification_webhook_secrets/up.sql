@@ -0,0 +1,3 @@
+-- Signs the notifications posted to users' webhooks.
+-- Generated when a webhook url is set, or on the first delivery
+ALTER TABLE notification_settings ADD COLUMN webhook_secret VARCHAR;
@@ -0,0 +1,2 @@
+-- Sent in the List-Unsubscribe header of notification emails
+ALTER TABLE email_outbox ADD COLUMN unsubscribe_link VARCHAR;
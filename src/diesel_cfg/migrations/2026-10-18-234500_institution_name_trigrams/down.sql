@@ -0,0 +1,2 @@
+-- The extension is left in place, as other objects may use it
+DROP INDEX IF EXISTS institutions_name_trgm_idx;
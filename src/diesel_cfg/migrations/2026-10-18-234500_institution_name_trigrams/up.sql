@@ -0,0 +1,5 @@
+-- Trigram index of institution names, for searches by word
+-- prefix and by resemblance
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX institutions_name_trgm_idx ON institutions USING gist (lower(name) gist_trgm_ops);
@@ -0,0 +1,3 @@
+-- Reports of completed jobs, such as match sweeps
+ALTER TABLE jobs
+ADD COLUMN IF NOT EXISTS result JSONB;
@@ -24,6 +24,76 @@ table! {
     }
 }
 
+table! {
+    /// Representation of the `campuses` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    campuses (id) {
+        /// The `id` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `institution_id` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Int4,
+        /// The `name` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `latitude` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Nullable<Float8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        latitude -> Nullable<Float8>,
+        /// The `longitude` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Nullable<Float8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        longitude -> Nullable<Float8>,
+        /// The `created_at` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `campuses` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `claim_blocks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    claim_blocks (claim_id, block_key) {
+        /// The `claim_id` column of the `claim_blocks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claim_id -> Int4,
+        /// The `block_key` column of the `claim_blocks` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_key -> Varchar,
+    }
+}
+
 table! {
     /// Representation of the `claimed_identifications` table.
     ///
@@ -49,10 +119,10 @@ table! {
         name -> Varchar,
         /// The `course` column of the `claimed_identifications` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        course -> Varchar,
+        course -> Nullable<Varchar>,
         /// The `entry_year` column of the `claimed_identifications` table.
         ///
         /// Its SQL type is `Nullable<Date>`.
@@ -89,6 +159,240 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         institution_id -> Nullable<Int4>,
+        /// The `registration_no_canonical` column of the `claimed_identifications` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        registration_no_canonical -> Nullable<Varchar>,
+        /// The `document_type` column of the `claimed_identifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        document_type -> Varchar,
+        /// The `status` column of the `claimed_identifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `status_changed_at` column of the `claimed_identifications` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status_changed_at -> Timestamp,
+        /// The `expiry_reminded_at` column of the `claimed_identifications` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expiry_reminded_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    /// Representation of the `conversations` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    conversations (id) {
+        /// The `id` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `claim_id` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claim_id -> Int4,
+        /// The `identification_id` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        identification_id -> Int4,
+        /// The `claimant_id` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claimant_id -> Int4,
+        /// The `finder_id` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        finder_id -> Int4,
+        /// The `claimant_shares_contact` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claimant_shares_contact -> Bool,
+        /// The `finder_shares_contact` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        finder_shares_contact -> Bool,
+        /// The `created_at` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `conversations` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `deferred_emails` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    deferred_emails (id) {
+        /// The `id` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `kind` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `subject` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subject -> Varchar,
+        /// The `created_at` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `template` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        template -> Nullable<Varchar>,
+        /// The `context` column of the `deferred_emails` table.
+        ///
+        /// Its SQL type is `Nullable<Jsonb>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        context -> Nullable<Jsonb>,
+    }
+}
+
+table! {
+    /// Representation of the `email_outbox` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    email_outbox (id) {
+        /// The `id` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `to_addr` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        to_addr -> Varchar,
+        /// The `to_name` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        to_name -> Varchar,
+        /// The `subject` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subject -> Varchar,
+        /// The `content` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        content -> Text,
+        /// The `status` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `attempts` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attempts -> Int4,
+        /// The `response` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response -> Nullable<Text>,
+        /// The `last_error` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_error -> Nullable<Text>,
+        /// The `sent_at` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sent_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `text_content` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        text_content -> Nullable<Text>,
+        /// The `unsubscribe_link` column of the `email_outbox` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        unsubscribe_link -> Nullable<Varchar>,
     }
 }
 
@@ -145,6 +449,38 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         updated_at -> Timestamp,
+        /// The `undeliverable` column of the `emails` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        undeliverable -> Bool,
+        /// The `bounces` column of the `emails` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        bounces -> Int4,
+    }
+}
+
+table! {
+    /// Representation of the `identification_blocks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    identification_blocks (identification_id, block_key) {
+        /// The `identification_id` column of the `identification_blocks` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        identification_id -> Int4,
+        /// The `block_key` column of the `identification_blocks` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_key -> Varchar,
     }
 }
 
@@ -169,10 +505,10 @@ table! {
         name -> Varchar,
         /// The `course` column of the `identifications` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        course -> Varchar,
+        course -> Nullable<Varchar>,
         /// The `valid_from` column of the `identifications` table.
         ///
         /// Its SQL type is `Nullable<Date>`.
@@ -257,17 +593,203 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         institution_id -> Nullable<Int4>,
-    }
-}
-
-table! {
-    /// Representation of the `institutions` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    institutions (id) {
-        /// The `id` column of the `institutions` table.
+        /// The `registration_no_canonical` column of the `identifications` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        registration_no_canonical -> Nullable<Varchar>,
+        /// The `document_type` column of the `identifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        document_type -> Varchar,
+        /// The `pickup_point_id` column of the `identifications` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        pickup_point_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    /// Representation of the `institution_digests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    institution_digests (id) {
+        /// The `id` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `institution_id` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Int4,
+        /// The `frequency` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        frequency -> Varchar,
+        /// The `last_identification_id` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_identification_id -> Int4,
+        /// The `last_sent_at` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_sent_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `institution_digests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `institution_domains` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    institution_domains (id) {
+        /// The `id` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `institution_id` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Int4,
+        /// The `domain` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        domain -> Varchar,
+        /// The `include_subdomains` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        include_subdomains -> Bool,
+        /// The `added_by` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        added_by -> Nullable<Int4>,
+        /// The `created_at` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `verified_at` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified_at -> Nullable<Timestamp>,
+        /// The `verified_by` column of the `institution_domains` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        verified_by -> Nullable<Int4>,
+    }
+}
+
+table! {
+    /// Representation of the `institution_memberships` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    institution_memberships (id) {
+        /// The `id` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `institution_id` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Int4,
+        /// The `user_id` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `role` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Varchar,
+        /// The `invited_by` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        invited_by -> Nullable<Int4>,
+        /// The `created_at` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `institution_memberships` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `institutions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    institutions (id) {
+        /// The `id` column of the `institutions` table.
+        ///
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
@@ -294,237 +816,903 @@ table! {
         /// Its SQL type is `Nullable<Text>`.
         ///
         /// (Automatically generated by Diesel.)
-        description -> Nullable<Text>,
-        /// The `postal_address` column of the `institutions` table.
+        description -> Nullable<Text>,
+        /// The `postal_address` column of the `institutions` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        postal_address -> Nullable<Text>,
+        /// The `created_at` column of the `institutions` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `updated_at` column of the `institutions` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `job_schedules` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    job_schedules (name) {
+        /// The `name` column of the `job_schedules` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `last_run_at` column of the `job_schedules` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_run_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    /// Representation of the `jobs` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    jobs (id) {
+        /// The `id` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `kind` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `payload` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        payload -> Jsonb,
+        /// The `status` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `attempts` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attempts -> Int4,
+        /// The `max_attempts` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_attempts -> Int4,
+        /// The `run_at` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        run_at -> Timestamp,
+        /// The `last_error` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_error -> Nullable<Text>,
+        /// The `created_at` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `result` column of the `jobs` table.
+        ///
+        /// Its SQL type is `Nullable<Jsonb>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        result -> Nullable<Jsonb>,
+    }
+}
+
+table! {
+    /// Representation of the `matched_identifications` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    matched_identifications (id) {
+        /// The `id` column of the `matched_identifications` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `claim_id` column of the `matched_identifications` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claim_id -> Int4,
+        /// The `identification_id` column of the `matched_identifications` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        identification_id -> Int4,
+        /// The `created_at` column of the `matched_identifications` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `message_reports` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    message_reports (id) {
+        /// The `id` column of the `message_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `message_id` column of the `message_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        message_id -> Int4,
+        /// The `reported_by` column of the `message_reports` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reported_by -> Int4,
+        /// The `reason` column of the `message_reports` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Text,
+        /// The `created_at` column of the `message_reports` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `messages` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    messages (id) {
+        /// The `id` column of the `messages` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `conversation_id` column of the `messages` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        conversation_id -> Int4,
+        /// The `sender_id` column of the `messages` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        sender_id -> Int4,
+        /// The `body` column of the `messages` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        body -> Text,
+        /// The `read_at` column of the `messages` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        read_at -> Nullable<Timestamp>,
+        /// The `hidden` column of the `messages` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        hidden -> Bool,
+        /// The `created_at` column of the `messages` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `notification_preferences` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    notification_preferences (id) {
+        /// The `id` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `kind` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `channel` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        channel -> Varchar,
+        /// The `enabled` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        enabled -> Bool,
+        /// The `created_at` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `notification_preferences` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `notification_settings` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    notification_settings (user_id) {
+        /// The `user_id` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `digest` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        digest -> Varchar,
+        /// The `quiet_hours_start` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Time>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        quiet_hours_start -> Nullable<Time>,
+        /// The `quiet_hours_end` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Time>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        quiet_hours_end -> Nullable<Time>,
+        /// The `utc_offset` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        utc_offset -> Int4,
+        /// The `webhook_url` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        webhook_url -> Nullable<Varchar>,
+        /// The `unsubscribe_token` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        unsubscribe_token -> Varchar,
+        /// The `last_digest_at` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_digest_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+        /// The `webhook_secret` column of the `notification_settings` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        webhook_secret -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    /// Representation of the `notifications` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    notifications (id) {
+        /// The `id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `kind` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `title` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        title -> Varchar,
+        /// The `body` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        body -> Text,
+        /// The `data` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        data -> Jsonb,
+        /// The `read_at` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        read_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `oath_users` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    oath_users (id) {
+        /// The `id` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `email` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Varchar,
+        /// The `name` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `first_name` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        first_name -> Nullable<Varchar>,
+        /// The `family_name` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        family_name -> Nullable<Varchar>,
+        /// The `is_verified` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_verified -> Bool,
+        /// The `picture` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        picture -> Nullable<Varchar>,
+        /// The `locale` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locale -> Nullable<Varchar>,
+        /// The `acc_id` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        acc_id -> Varchar,
+        /// The `is_active` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_active -> Bool,
+        /// The `provider` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider -> Varchar,
+        /// The `provider_verified` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        provider_verified -> Bool,
+        /// The `created_at` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `oath_users` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `ownership_requests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    ownership_requests (id) {
+        /// The `id` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `claim_id` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        claim_id -> Int4,
+        /// The `identification_id` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        identification_id -> Int4,
+        /// The `requested_by` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        requested_by -> Int4,
+        /// The `status` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `reason` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reason -> Nullable<Text>,
+        /// The `decided_by` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        decided_by -> Nullable<Int4>,
+        /// The `decided_at` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        decided_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `ownership_requests` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `pickup_points` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    pickup_points (id) {
+        /// The `id` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `institution_id` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Int4,
+        /// The `campus_id` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        campus_id -> Nullable<Int4>,
+        /// The `name` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `directions` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        directions -> Nullable<Text>,
+        /// The `latitude` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Nullable<Float8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        latitude -> Nullable<Float8>,
+        /// The `longitude` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Nullable<Float8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        longitude -> Nullable<Float8>,
+        /// The `opening_hours` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        opening_hours -> Jsonb,
+        /// The `is_active` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        is_active -> Bool,
+        /// The `created_at` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `pickup_points` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `profiles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    profiles (id) {
+        /// The `id` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int4,
+        /// The `phone` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        phone -> Nullable<Varchar>,
+        /// The `name` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Nullable<Varchar>,
+        /// The `about` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        about -> Nullable<Text>,
+        /// The `found_ids` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        found_ids -> Nullable<Int4>,
+        /// The `institution_id` column of the `profiles` table.
         ///
-        /// Its SQL type is `Nullable<Text>`.
+        /// Its SQL type is `Nullable<Int4>`.
         ///
         /// (Automatically generated by Diesel.)
-        postal_address -> Nullable<Text>,
-        /// The `created_at` column of the `institutions` table.
+        institution_id -> Nullable<Int4>,
+        /// The `created_at` column of the `profiles` table.
         ///
         /// Its SQL type is `Timestamptz`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamptz,
-        /// The `updated_at` column of the `institutions` table.
+        /// The `updated_at` column of the `profiles` table.
         ///
         /// Its SQL type is `Timestamptz`.
         ///
         /// (Automatically generated by Diesel.)
         updated_at -> Timestamptz,
+        /// The `locale` column of the `profiles` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locale -> Nullable<Varchar>,
     }
 }
 
 table! {
-    /// Representation of the `matched_identifications` table.
+    /// Representation of the `refresh_tokens` table.
     ///
     /// (Automatically generated by Diesel.)
-    matched_identifications (id) {
-        /// The `id` column of the `matched_identifications` table.
+    refresh_tokens (id) {
+        /// The `id` column of the `refresh_tokens` table.
         ///
         /// Its SQL type is `Int8`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int8,
-        /// The `claim_id` column of the `matched_identifications` table.
-        ///
-        /// Its SQL type is `Int4`.
-        ///
-        /// (Automatically generated by Diesel.)
-        claim_id -> Int4,
-        /// The `identification_id` column of the `matched_identifications` table.
+        /// The `body` column of the `refresh_tokens` table.
         ///
-        /// Its SQL type is `Int4`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        identification_id -> Int4,
-        /// The `created_at` column of the `matched_identifications` table.
+        body -> Varchar,
+        /// The `valid` column of the `refresh_tokens` table.
         ///
-        /// Its SQL type is `Timestamp`.
+        /// Its SQL type is `Bool`.
         ///
         /// (Automatically generated by Diesel.)
-        created_at -> Timestamp,
+        valid -> Bool,
     }
 }
 
 table! {
-    /// Representation of the `oath_users` table.
+    /// Representation of the `registration_formats` table.
     ///
     /// (Automatically generated by Diesel.)
-    oath_users (id) {
-        /// The `id` column of the `oath_users` table.
+    registration_formats (id) {
+        /// The `id` column of the `registration_formats` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `email` column of the `oath_users` table.
+        /// The `institution_id` column of the `registration_formats` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        email -> Varchar,
-        /// The `name` column of the `oath_users` table.
+        institution_id -> Int4,
+        /// The `pattern` column of the `registration_formats` table.
         ///
         /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        name -> Varchar,
-        /// The `first_name` column of the `oath_users` table.
+        pattern -> Varchar,
+        /// The `canonical` column of the `registration_formats` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        first_name -> Nullable<Varchar>,
-        /// The `family_name` column of the `oath_users` table.
+        canonical -> Varchar,
+        /// The `checksum` column of the `registration_formats` table.
         ///
         /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        family_name -> Nullable<Varchar>,
-        /// The `is_verified` column of the `oath_users` table.
-        ///
-        /// Its SQL type is `Bool`.
-        ///
-        /// (Automatically generated by Diesel.)
-        is_verified -> Bool,
-        /// The `picture` column of the `oath_users` table.
+        checksum -> Nullable<Varchar>,
+        /// The `description` column of the `registration_formats` table.
         ///
         /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        picture -> Nullable<Varchar>,
-        /// The `locale` column of the `oath_users` table.
+        description -> Nullable<Varchar>,
+        /// The `created_at` column of the `registration_formats` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        locale -> Nullable<Varchar>,
-        /// The `acc_id` column of the `oath_users` table.
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `registration_formats` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        acc_id -> Varchar,
-        /// The `is_active` column of the `oath_users` table.
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `saved_search_alerts` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    saved_search_alerts (id) {
+        /// The `id` column of the `saved_search_alerts` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        is_active -> Bool,
-        /// The `provider` column of the `oath_users` table.
+        id -> Int4,
+        /// The `saved_search_id` column of the `saved_search_alerts` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        provider -> Varchar,
-        /// The `provider_verified` column of the `oath_users` table.
+        saved_search_id -> Int4,
+        /// The `identification_id` column of the `saved_search_alerts` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
-        provider_verified -> Bool,
-        /// The `created_at` column of the `oath_users` table.
+        identification_id -> Int4,
+        /// The `created_at` column of the `saved_search_alerts` table.
         ///
         /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
         created_at -> Timestamp,
-        /// The `updated_at` column of the `oath_users` table.
-        ///
-        /// Its SQL type is `Timestamp`.
-        ///
-        /// (Automatically generated by Diesel.)
-        updated_at -> Timestamp,
     }
 }
 
 table! {
-    /// Representation of the `profiles` table.
+    /// Representation of the `saved_searches` table.
     ///
     /// (Automatically generated by Diesel.)
-    profiles (id) {
-        /// The `id` column of the `profiles` table.
+    saved_searches (id) {
+        /// The `id` column of the `saved_searches` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `user_id` column of the `profiles` table.
+        /// The `user_id` column of the `saved_searches` table.
         ///
         /// Its SQL type is `Int4`.
         ///
         /// (Automatically generated by Diesel.)
         user_id -> Int4,
-        /// The `phone` column of the `profiles` table.
+        /// The `label` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Nullable<Varchar>`.
+        /// Its SQL type is `Varchar`.
         ///
         /// (Automatically generated by Diesel.)
-        phone -> Nullable<Varchar>,
-        /// The `name` column of the `profiles` table.
+        label -> Varchar,
+        /// The `name_fragment` column of the `saved_searches` table.
         ///
         /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        name -> Nullable<Varchar>,
-        /// The `about` column of the `profiles` table.
-        ///
-        /// Its SQL type is `Nullable<Text>`.
-        ///
-        /// (Automatically generated by Diesel.)
-        about -> Nullable<Text>,
-        /// The `found_ids` column of the `profiles` table.
-        ///
-        /// Its SQL type is `Nullable<Int4>`.
-        ///
-        /// (Automatically generated by Diesel.)
-        found_ids -> Nullable<Int4>,
-        /// The `institution_id` column of the `profiles` table.
+        name_fragment -> Nullable<Varchar>,
+        /// The `institution_id` column of the `saved_searches` table.
         ///
         /// Its SQL type is `Nullable<Int4>`.
         ///
         /// (Automatically generated by Diesel.)
         institution_id -> Nullable<Int4>,
-        /// The `created_at` column of the `profiles` table.
+        /// The `latitude` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Timestamptz`.
+        /// Its SQL type is `Nullable<Float8>`.
         ///
         /// (Automatically generated by Diesel.)
-        created_at -> Timestamptz,
-        /// The `updated_at` column of the `profiles` table.
+        latitude -> Nullable<Float8>,
+        /// The `longitude` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Timestamptz`.
+        /// Its SQL type is `Nullable<Float8>`.
         ///
         /// (Automatically generated by Diesel.)
-        updated_at -> Timestamptz,
-    }
-}
-
-table! {
-    /// Representation of the `refresh_tokens` table.
-    ///
-    /// (Automatically generated by Diesel.)
-    refresh_tokens (id) {
-        /// The `id` column of the `refresh_tokens` table.
+        longitude -> Nullable<Float8>,
+        /// The `radius_km` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Int8`.
+        /// Its SQL type is `Nullable<Float8>`.
         ///
         /// (Automatically generated by Diesel.)
-        id -> Int8,
-        /// The `body` column of the `refresh_tokens` table.
+        radius_km -> Nullable<Float8>,
+        /// The `created_at` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        body -> Varchar,
-        /// The `valid` column of the `refresh_tokens` table.
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `saved_searches` table.
         ///
-        /// Its SQL type is `Bool`.
+        /// Its SQL type is `Timestamp`.
         ///
         /// (Automatically generated by Diesel.)
-        valid -> Bool,
+        updated_at -> Timestamp,
     }
 }
 
@@ -596,26 +1784,222 @@ table! {
     }
 }
 
+table! {
+    /// Representation of the `webhook_deliveries` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    webhook_deliveries (id) {
+        /// The `id` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `subscription_id` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subscription_id -> Int4,
+        /// The `event` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        event -> Varchar,
+        /// The `payload` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        payload -> Jsonb,
+        /// The `status` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> Varchar,
+        /// The `attempts` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attempts -> Int4,
+        /// The `response_status` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response_status -> Nullable<Int4>,
+        /// The `response_body` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        response_body -> Nullable<Text>,
+        /// The `last_error` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_error -> Nullable<Text>,
+        /// The `delivered_at` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        delivered_at -> Nullable<Timestamp>,
+        /// The `created_at` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `webhook_deliveries` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    /// Representation of the `webhook_subscriptions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    webhook_subscriptions (id) {
+        /// The `id` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `institution_id` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        institution_id -> Nullable<Int4>,
+        /// The `created_by` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Nullable<Int4>,
+        /// The `url` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        url -> Varchar,
+        /// The `secret` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        secret -> Varchar,
+        /// The `events` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Array<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        events -> Array<Varchar>,
+        /// The `active` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        active -> Bool,
+        /// The `created_at` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `webhook_subscriptions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
 joinable!(avatars -> users (user_id));
+joinable!(campuses -> institutions (institution_id));
+joinable!(claim_blocks -> claimed_identifications (claim_id));
 joinable!(claimed_identifications -> institutions (institution_id));
 joinable!(claimed_identifications -> users (user_id));
+joinable!(conversations -> claimed_identifications (claim_id));
+joinable!(conversations -> identifications (identification_id));
+joinable!(deferred_emails -> users (user_id));
 joinable!(emails -> users (user_id));
+joinable!(identification_blocks -> identifications (identification_id));
 joinable!(identifications -> institutions (institution_id));
+joinable!(identifications -> pickup_points (pickup_point_id));
 joinable!(identifications -> users (owner));
+joinable!(institution_digests -> institutions (institution_id));
+joinable!(institution_digests -> users (user_id));
+joinable!(institution_domains -> institutions (institution_id));
+joinable!(institution_domains -> users (added_by));
+joinable!(institution_memberships -> institutions (institution_id));
 joinable!(matched_identifications -> claimed_identifications (claim_id));
 joinable!(matched_identifications -> identifications (identification_id));
+joinable!(message_reports -> messages (message_id));
+joinable!(message_reports -> users (reported_by));
+joinable!(messages -> conversations (conversation_id));
+joinable!(messages -> users (sender_id));
+joinable!(notification_preferences -> users (user_id));
+joinable!(notification_settings -> users (user_id));
+joinable!(notifications -> users (user_id));
+joinable!(ownership_requests -> claimed_identifications (claim_id));
+joinable!(ownership_requests -> identifications (identification_id));
+joinable!(pickup_points -> campuses (campus_id));
+joinable!(pickup_points -> institutions (institution_id));
 joinable!(profiles -> institutions (institution_id));
 joinable!(profiles -> users (user_id));
+joinable!(registration_formats -> institutions (institution_id));
+joinable!(saved_search_alerts -> identifications (identification_id));
+joinable!(saved_search_alerts -> saved_searches (saved_search_id));
+joinable!(saved_searches -> institutions (institution_id));
+joinable!(saved_searches -> users (user_id));
+joinable!(webhook_deliveries -> webhook_subscriptions (subscription_id));
+joinable!(webhook_subscriptions -> institutions (institution_id));
+joinable!(webhook_subscriptions -> users (created_by));
 
 allow_tables_to_appear_in_same_query!(
     avatars,
+    campuses,
+    claim_blocks,
     claimed_identifications,
+    conversations,
+    deferred_emails,
+    email_outbox,
     emails,
+    identification_blocks,
     identifications,
+    institution_digests,
+    institution_domains,
+    institution_memberships,
     institutions,
+    job_schedules,
+    jobs,
     matched_identifications,
+    message_reports,
+    messages,
+    notification_preferences,
+    notification_settings,
+    notifications,
     oath_users,
+    ownership_requests,
+    pickup_points,
     profiles,
     refresh_tokens,
+    registration_formats,
+    saved_search_alerts,
+    saved_searches,
     users,
+    webhook_deliveries,
+    webhook_subscriptions,
 );
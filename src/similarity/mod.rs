@@ -1,2 +1,3 @@
 pub mod cosine;
 pub mod levenshtein;
+pub mod phonetic;
@@ -0,0 +1,66 @@
+//! Phonetic encoding of names
+
+/// Encodes a word with American Soundex.
+///
+/// Words that sound alike, such as common misspellings of a
+/// name, share a code. Non-ASCII letters are ignored.
+///
+/// # Returns
+/// None if the word has no letters
+///
+/// ```rust
+/// use got_ya_id::similarity::phonetic::soundex;
+///
+/// assert_eq!(soundex("Robert"), Some("R163".into()));
+/// assert_eq!(soundex("Rupert"), Some("R163".into()));
+/// assert_eq!(soundex("Ashcraft"), Some("A261".into()));
+/// assert_eq!(soundex("Wanjiku"), soundex("Wanjicu"));
+/// assert_eq!(soundex("42"), None);
+/// ```
+pub fn soundex(word: &str) -> Option<String> {
+    let mut letters = word
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase());
+
+    let first = letters.next()?;
+    let mut code = first.to_string();
+    let mut last_digit = soundex_digit(first);
+
+    for c in letters {
+        let digit = soundex_digit(c);
+        match digit {
+            Some(d) if digit != last_digit => {
+                code.push(d);
+                if code.len() == 4 {
+                    break;
+                }
+            }
+            _ => (),
+        }
+        // H and W don't separate letters of the same code
+        if c != 'H' && c != 'W' {
+            last_digit = digit;
+        }
+    }
+
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+/// The Soundex digit of an uppercase letter.
+///
+/// Vowels and H, W, Y have none.
+fn soundex_digit(c: char) -> Option<char> {
+    match c {
+        'B' | 'F' | 'P' | 'V' => Some('1'),
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+        'D' | 'T' => Some('3'),
+        'L' => Some('4'),
+        'M' | 'N' => Some('5'),
+        'R' => Some('6'),
+        _ => None,
+    }
+}